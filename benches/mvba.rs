@@ -0,0 +1,109 @@
+//! Compares checking the signature shares of the VCBC and ABBA messages one by
+//! one on arrival with checking them once combined.
+//!
+//! The messages a party receives during an MVBA instance are recorded once,
+//! then replayed to a fresh instance of that party.
+
+use blsttc::SecretKeySet;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sn_consensus::mvba::{
+    bundle::{Bundle, Outgoing},
+    consensus::Consensus,
+    tag::Domain,
+};
+use sn_consensus::NodeId;
+
+fn valid_proposal(_id: NodeId, _: &u64) -> bool {
+    true
+}
+
+struct Recording {
+    sks: SecretKeySet,
+    parties: Vec<NodeId>,
+    proposal: u64,
+    // the bundles received by the last party, in the order it received them.
+    received: Vec<Bundle<u64>>,
+}
+
+impl Recording {
+    fn consensus(&self, id: NodeId, batch_verification: bool) -> Consensus<u64> {
+        let mut consensus = Consensus::init(
+            Domain::new("bench", 0),
+            id,
+            self.sks.secret_key_share(id),
+            self.sks.public_keys(),
+            self.parties.clone(),
+            valid_proposal,
+        );
+        consensus.set_batch_verification(batch_verification);
+        consensus
+    }
+
+    // runs an MVBA instance with `n` parties to the end
+    fn record(n: u64) -> Self {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let sks = SecretKeySet::random(((n - 1) / 3) as usize, &mut rng);
+        let mut recording = Self {
+            sks,
+            parties: Vec::from_iter(0..n),
+            proposal: rng.gen(),
+            received: Vec::new(),
+        };
+        let mut cons = Vec::from_iter((0..n).map(|id| recording.consensus(id, false)));
+
+        let mut buffer = Vec::new();
+        for c in &mut cons {
+            let proposal = if c.self_id() == n - 1 {
+                recording.proposal
+            } else {
+                rng.gen()
+            };
+            buffer.append(&mut c.propose(proposal).unwrap());
+        }
+        while !buffer.is_empty() {
+            let outgoing = buffer.remove(rng.gen_range(0..buffer.len()));
+            for c in &mut cons {
+                let bundle = match &outgoing {
+                    Outgoing::Direct(id, bundle) if *id == c.self_id() => bundle,
+                    Outgoing::Direct(..) => continue,
+                    Outgoing::Gossip(bundle) => bundle,
+                };
+                if c.self_id() == n - 1 && bundle.initiator != n - 1 {
+                    recording.received.push(bundle.clone());
+                }
+                buffer.append(&mut c.process_bundle(bundle).unwrap());
+            }
+        }
+        recording
+    }
+
+    fn replay(&self, mut consensus: Consensus<u64>) {
+        consensus.propose(self.proposal).unwrap();
+        for bundle in &self.received {
+            consensus.process_bundle(bundle).unwrap();
+        }
+        assert!(consensus.decided_proposal().is_some());
+    }
+}
+
+fn bench_verification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mvba");
+    group.sample_size(10);
+    for n in [4, 7, 10, 16] {
+        let recording = Recording::record(n);
+        for (name, batch_verification) in [("eager", false), ("batched", true)] {
+            group.bench_with_input(BenchmarkId::new(name, n), &recording, |b, recording| {
+                b.iter_batched(
+                    || recording.consensus(n - 1, batch_verification),
+                    |consensus| recording.replay(consensus),
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_verification);
+criterion_main!(benches);
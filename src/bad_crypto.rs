@@ -7,16 +7,69 @@
 use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use thiserror::Error;
 
+use crate::crypto::{KeySet, MultiSignature, SignatureScheme};
+use crate::NodeId;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Failed Verification")]
     FailedVerification,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+/// Hash based fake signatures, a decision is proven by the set of voter signatures.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct BadCrypto;
+
+impl SignatureScheme for BadCrypto {
+    type SecretKeyShare = SecretKey;
+    type PublicKeySet = KeySet<PublicKey>;
+    type PublicKey = KeySet<PublicKey>;
+    type SignatureShare = Signature;
+    type Signature = MultiSignature<Signature>;
+
+    fn sign_share(secret_key: &SecretKey, msg: &[u8]) -> Signature {
+        secret_key.sign(msg)
+    }
+
+    fn verify_share(
+        voters: &KeySet<PublicKey>,
+        voter: NodeId,
+        msg: &[u8],
+        sig: &Signature,
+    ) -> bool {
+        voters
+            .keys
+            .get(&voter)
+            .is_some_and(|key| key.verify(msg, sig).is_ok())
+    }
+
+    fn threshold(voters: &KeySet<PublicKey>) -> usize {
+        voters.threshold
+    }
+
+    fn combine_shares(
+        voters: &KeySet<PublicKey>,
+        shares: &BTreeMap<NodeId, Signature>,
+    ) -> crate::Result<MultiSignature<Signature>> {
+        MultiSignature::combine(voters, shares)
+    }
+
+    fn public_key(voters: &KeySet<PublicKey>) -> KeySet<PublicKey> {
+        voters.clone()
+    }
+
+    fn verify(voters: &KeySet<PublicKey>, msg: &[u8], sig: &MultiSignature<Signature>) -> bool {
+        sig.verify(voters, |key, sig| key.verify(msg, sig).is_ok())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct PublicKey(u64);
 
 impl PublicKey {
@@ -51,7 +104,7 @@ impl core::fmt::Display for PublicKey {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SecretKey(u64);
 
 impl SecretKey {
@@ -73,5 +126,5 @@ impl SecretKey {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Signature(u64);
@@ -1,89 +1,54 @@
-use blsttc::{serde_impl::SerdeSecret, SecretKeyShare};
-use rand::{CryptoRng, Rng};
-use serde::{Deserialize, Serialize};
-use thiserror::Error;
-
-#[derive(Debug, Error)]
-pub enum Error {
-    #[error("Invalid Signature")]
-    InvalidSignature,
-    #[error("Blsttc error: {0}")]
-    Blsttc(#[from] blsttc::error::FromBytesError),
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub struct PublicKey(blsttc::PublicKeyShare);
-
-impl PublicKey {
-    pub fn random(rng: impl Rng + CryptoRng) -> Self {
-        SecretKey::random(rng).public_key()
-    }
-
-    pub fn verify(&self, msg: &[u8], signature: &Signature) -> Result<(), Error> {
-        if self.0.verify(&signature.0, msg) {
-            Ok(())
-        } else {
-            Err(Error::InvalidSignature)
-        }
-    }
-
-    pub fn public_key(&self) -> Result<blsttc::PublicKey, Error> {
-        let pk = blsttc::PublicKey::from_bytes(self.0.to_bytes())?;
-        Ok(pk)
-    }
-}
+use std::collections::BTreeMap;
 
-impl core::fmt::Display for PublicKey {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let bytes = self.0.to_bytes();
-        write!(f, "i:{}", hex::encode(&bytes[..3]))
-    }
-}
+use blsttc::{PublicKey, PublicKeySet, SecretKeyShare, Signature, SignatureShare};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SecretKey(SerdeSecret<SecretKeyShare>);
+use crate::crypto::SignatureScheme;
+use crate::{NodeId, Result};
 
-impl SecretKey {
-    pub fn from(secret_key_share: SecretKeyShare) -> Self {
-        Self(SerdeSecret(secret_key_share))
-    }
+/// Threshold BLS signatures, a decision is proven by a single section signature.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Bls;
 
-    pub fn random(mut rng: impl Rng + CryptoRng) -> Self {
-        Self(SerdeSecret(rng.gen()))
-    }
+impl SignatureScheme for Bls {
+    type SecretKeyShare = SecretKeyShare;
+    type PublicKeySet = PublicKeySet;
+    type PublicKey = PublicKey;
+    type SignatureShare = SignatureShare;
+    type Signature = Signature;
 
-    pub fn public_key(&self) -> PublicKey {
-        PublicKey(self.0 .0.public_key_share())
+    fn sign_share(secret_key: &SecretKeyShare, msg: &[u8]) -> SignatureShare {
+        secret_key.sign(msg)
     }
 
-    pub fn sign(&self, msg: &[u8]) -> Signature {
-        Signature(self.0.sign(msg))
+    fn verify_share(
+        voters: &PublicKeySet,
+        voter: NodeId,
+        msg: &[u8],
+        sig: &SignatureShare,
+    ) -> bool {
+        voters.public_key_share(voter).verify(sig, msg)
     }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Signature(blsttc::SignatureShare);
 
-impl PartialOrd for PublicKey {
-    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        Some(self.cmp(other))
+    fn threshold(voters: &PublicKeySet) -> usize {
+        voters.threshold()
     }
-}
 
-impl Ord for PublicKey {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.to_bytes().cmp(&other.0.to_bytes())
+    fn combine_shares(
+        voters: &PublicKeySet,
+        shares: &BTreeMap<NodeId, SignatureShare>,
+    ) -> Result<Signature> {
+        let shares = shares.iter().map(|(id, sig)| (*id, sig));
+        Ok(voters.combine_signatures(shares)?)
     }
-}
 
-impl PartialOrd for Signature {
-    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        Some(self.cmp(other))
+    fn public_key(voters: &PublicKeySet) -> PublicKey {
+        voters.public_key()
     }
-}
 
-impl Ord for Signature {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.to_bytes().cmp(&other.0.to_bytes())
+    fn verify(public_key: &PublicKey, msg: &[u8], sig: &Signature) -> bool {
+        public_key.verify(sig, msg)
     }
 }
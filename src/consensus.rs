@@ -1,37 +1,37 @@
 use std::collections::{BTreeMap, BTreeSet};
 
-use blsttc::{PublicKeySet, SecretKeyShare, SignatureShare};
 use log::info;
 use serde::Serialize;
 
 use crate::sn_membership::Generation;
 use crate::vote::{Ballot, Proposition, SignedVote, Vote};
-use crate::{Decision, Fault, NodeId, Result, VoteCount};
+use crate::{Bls, Decision, Fault, NodeId, Result, SignatureScheme, VoteCount};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Consensus<T: Proposition> {
-    pub elders: PublicKeySet,
+pub struct Consensus<T: Proposition, C: SignatureScheme = Bls> {
+    pub elders: C::PublicKeySet,
     pub n_elders: usize,
-    pub secret_key: (NodeId, SecretKeyShare),
-    pub processed_votes_cache: BTreeSet<SignatureShare>,
-    pub votes: BTreeMap<NodeId, SignedVote<T>>,
-    pub faults: BTreeMap<NodeId, Fault<T>>,
-    pub decision: Option<Decision<T>>,
+    pub secret_key: (NodeId, C::SecretKeyShare),
+    pub processed_votes_cache: BTreeSet<C::SignatureShare>,
+    pub votes: BTreeMap<NodeId, SignedVote<T, C>>,
+    pub faults: BTreeMap<NodeId, Fault<T, C>>,
+    pub decision: Option<Decision<T, C>>,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum VoteResponse<T: Proposition> {
+pub enum VoteResponse<T: Proposition, C: SignatureScheme = Bls> {
     WaitingForMoreVotes,
-    Broadcast(SignedVote<T>),
+    Broadcast(SignedVote<T, C>),
 }
 
-impl<T: Proposition> Consensus<T> {
+impl<T: Proposition, C: SignatureScheme> Consensus<T, C> {
     pub fn from(
-        secret_key: (NodeId, SecretKeyShare),
-        elders: PublicKeySet,
+        secret_key: (NodeId, C::SecretKeyShare),
+        elders: C::PublicKeySet,
         n_elders: usize,
     ) -> Self {
-        Consensus::<T> {
+        Consensus::<T, C> {
             elders,
             n_elders,
             secret_key,
@@ -42,15 +42,16 @@ impl<T: Proposition> Consensus<T> {
         }
     }
 
-    pub fn sign<M: Serialize>(&self, msg: &M) -> Result<SignatureShare> {
-        Ok(self.secret_key.1.sign(bincode::serialize(msg)?))
+    pub fn sign<M: Serialize>(&self, msg: &M) -> Result<C::SignatureShare> {
+        let msg_bytes = crate::signing::signing_bytes(msg)?;
+        Ok(C::sign_share(&self.secret_key.1, &msg_bytes))
     }
 
     pub fn id(&self) -> NodeId {
         self.secret_key.0
     }
 
-    pub fn faults(&self) -> BTreeSet<Fault<T>> {
+    pub fn faults(&self) -> BTreeSet<Fault<T, C>> {
         BTreeSet::from_iter(self.faults.values().cloned())
     }
 
@@ -60,13 +61,13 @@ impl<T: Proposition> Consensus<T> {
 
     pub fn build_super_majority_vote(
         &self,
-        votes: BTreeSet<SignedVote<T>>,
-        faults: BTreeSet<Fault<T>>,
+        votes: BTreeSet<SignedVote<T, C>>,
+        faults: BTreeSet<Fault<T, C>>,
         gen: Generation,
-    ) -> Result<SignedVote<T>> {
+    ) -> Result<SignedVote<T, C>> {
         let faulty = BTreeSet::from_iter(faults.iter().map(Fault::voter_at_fault));
 
-        let proposals = VoteCount::count(&votes, &faulty)
+        let proposals = VoteCount::<T, C>::count(&votes, &faulty)
             .candidate_with_most_votes()
             .map(|(candidate, _)| candidate.proposals.clone())
             .unwrap_or_default()
@@ -91,7 +92,10 @@ impl<T: Proposition> Consensus<T> {
     // membership: gen = pending_gen
     /// Handles a signed vote
     /// Returns the vote we cast and the reached consensus vote in case consensus was reached
-    pub fn handle_signed_vote(&mut self, signed_vote: SignedVote<T>) -> Result<VoteResponse<T>> {
+    pub fn handle_signed_vote(
+        &mut self,
+        signed_vote: SignedVote<T, C>,
+    ) -> Result<VoteResponse<T, C>> {
         info!("[{}] handling vote {:?}", self.id(), signed_vote);
 
         if self.decision.is_some() {
@@ -123,7 +127,7 @@ impl<T: Proposition> Consensus<T> {
         self.process_signed_vote(signed_vote)
     }
 
-    fn process_signed_vote(&mut self, signed_vote: SignedVote<T>) -> Result<VoteResponse<T>> {
+    fn process_signed_vote(&mut self, signed_vote: SignedVote<T, C>) -> Result<VoteResponse<T, C>> {
         self.log_processed_signed_vote(&signed_vote);
 
         if let Some(proposals) = signed_vote.vote_count().get_decision(&self.elders)? {
@@ -142,7 +146,7 @@ impl<T: Proposition> Consensus<T> {
             return Ok(VoteResponse::WaitingForMoreVotes);
         }
 
-        let vote_count = VoteCount::count(self.votes.values(), &self.faulty_ids());
+        let vote_count = VoteCount::<T, C>::count(self.votes.values(), &self.faulty_ids());
 
         if let Some(proposals) = vote_count.get_decision(&self.elders)? {
             info!(
@@ -226,7 +230,7 @@ impl<T: Proposition> Consensus<T> {
         }
     }
 
-    pub fn sign_vote(&self, vote: Vote<T>) -> Result<SignedVote<T>> {
+    pub fn sign_vote(&self, vote: Vote<T, C>) -> Result<SignedVote<T, C>> {
         Ok(SignedVote {
             voter: self.id(),
             sig: self.sign(&vote)?,
@@ -234,7 +238,7 @@ impl<T: Proposition> Consensus<T> {
         })
     }
 
-    pub fn cast_vote(&mut self, signed_vote: SignedVote<T>) -> Result<SignedVote<T>> {
+    pub fn cast_vote(&mut self, signed_vote: SignedVote<T, C>) -> Result<SignedVote<T, C>> {
         info!("[{}] casting vote {:?}", self.id(), signed_vote);
         match self.handle_signed_vote(signed_vote.clone())? {
             VoteResponse::WaitingForMoreVotes => Ok(signed_vote),
@@ -242,11 +246,11 @@ impl<T: Proposition> Consensus<T> {
         }
     }
 
-    fn have_we_processed_vote(&self, signed_vote: &SignedVote<T>) -> bool {
+    fn have_we_processed_vote(&self, signed_vote: &SignedVote<T, C>) -> bool {
         self.processed_votes_cache.contains(&signed_vote.sig)
     }
 
-    fn log_processed_signed_vote(&mut self, signed_vote: &SignedVote<T>) {
+    fn log_processed_signed_vote(&mut self, signed_vote: &SignedVote<T, C>) {
         for vote in signed_vote.unpack_votes() {
             if self.processed_votes_cache.insert(vote.sig.clone()) {
                 let existing_vote = self.votes.entry(vote.voter).or_insert_with(|| vote.clone());
@@ -269,7 +273,7 @@ mod tests {
         let mut rng = StdRng::from_seed([0u8; 32]);
         let elders_sk = SecretKeySet::random(10, &mut rng);
         let mut states = Vec::from_iter((1..=10).map(|id| {
-            Consensus::from(
+            Consensus::<u8>::from(
                 (id, elders_sk.secret_key_share(id as usize)),
                 elders_sk.public_keys(),
                 10,
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use core::fmt::Debug;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Error, NodeId, Result};
+
+/// The signature scheme used by the voters of a [`crate::Consensus`] instance.
+///
+/// Each voter signs its votes with its own secret key share, these shares are
+/// verified against the public key set of all voters. Once a super majority of
+/// voters have signed a proposal, their shares are aggregated into a single
+/// decision proof that can be verified with just `Self::PublicKey`.
+pub trait SignatureScheme:
+    Debug + Clone + Copy + Default + PartialEq + Eq + PartialOrd + Ord + Hash
+{
+    /// The secret key a voter signs with.
+    type SecretKeyShare: Debug + Clone + PartialEq + Eq;
+    /// The public keys of all voters.
+    type PublicKeySet: Debug + Clone + PartialEq + Eq + Serialize + DeserializeOwned;
+    /// The key used to verify an aggregated decision proof.
+    type PublicKey: Debug + Clone + PartialEq + Eq;
+    /// A signature produced by a single voter.
+    type SignatureShare: Debug
+        + Clone
+        + PartialEq
+        + Eq
+        + PartialOrd
+        + Ord
+        + Hash
+        + Serialize
+        + DeserializeOwned;
+    /// A proof that a super majority of voters have signed a message.
+    type Signature: Debug
+        + Clone
+        + PartialEq
+        + Eq
+        + PartialOrd
+        + Ord
+        + Hash
+        + Serialize
+        + DeserializeOwned;
+
+    fn sign_share(secret_key: &Self::SecretKeyShare, msg: &[u8]) -> Self::SignatureShare;
+
+    fn verify_share(
+        voters: &Self::PublicKeySet,
+        voter: NodeId,
+        msg: &[u8],
+        sig: &Self::SignatureShare,
+    ) -> bool;
+
+    /// A super majority is reached once more than `threshold` voters agree.
+    fn threshold(voters: &Self::PublicKeySet) -> usize;
+
+    /// Aggregates the (already verified) signature shares of more than
+    /// `threshold` voters into a decision proof.
+    fn combine_shares(
+        voters: &Self::PublicKeySet,
+        shares: &BTreeMap<NodeId, Self::SignatureShare>,
+    ) -> Result<Self::Signature>;
+
+    fn public_key(voters: &Self::PublicKeySet) -> Self::PublicKey;
+
+    fn verify(public_key: &Self::PublicKey, msg: &[u8], sig: &Self::Signature) -> bool;
+}
+
+/// The public keys of voters for schemes that can not aggregate signatures,
+/// each voter is identified by its own public key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct KeySet<K> {
+    pub threshold: usize,
+    pub keys: BTreeMap<NodeId, K>,
+}
+
+impl<K> KeySet<K> {
+    pub fn new(threshold: usize, keys: BTreeMap<NodeId, K>) -> Self {
+        Self { threshold, keys }
+    }
+}
+
+/// A decision proof made up of the individual signatures of a super majority of voters.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MultiSignature<S>(pub BTreeMap<NodeId, S>);
+
+impl<S: Clone> MultiSignature<S> {
+    pub fn combine<K>(voters: &KeySet<K>, shares: &BTreeMap<NodeId, S>) -> Result<Self> {
+        let sigs = BTreeMap::from_iter(
+            shares
+                .iter()
+                .filter(|(id, _)| voters.keys.contains_key(id))
+                .map(|(id, sig)| (*id, sig.clone())),
+        );
+
+        if sigs.len() > voters.threshold {
+            Ok(Self(sigs))
+        } else {
+            Err(Error::NotEnoughSignatureShares {
+                have: sigs.len(),
+                need: voters.threshold + 1,
+            })
+        }
+    }
+
+    /// Checks that more than `threshold` known voters have signed `msg`.
+    pub fn verify<K>(&self, voters: &KeySet<K>, verify: impl Fn(&K, &S) -> bool) -> bool {
+        let valid_sigs = self
+            .0
+            .iter()
+            .filter(|(id, sig)| voters.keys.get(id).is_some_and(|k| verify(k, sig)))
+            .count();
+
+        valid_sigs > voters.threshold
+    }
+}
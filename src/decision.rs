@@ -1,20 +1,20 @@
 use std::collections::BTreeMap;
 
-use blsttc::{PublicKey, Signature};
 use serde::{Deserialize, Serialize};
 
-use crate::{verify_sig, Generation, Proposition, Result};
+use crate::{verify_sig, Bls, Generation, Proposition, Result, SignatureScheme};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct Decision<T: Proposition> {
+#[serde(bound(serialize = "", deserialize = "T: Deserialize<'de>"))]
+pub struct Decision<T: Proposition, C: SignatureScheme = Bls> {
     pub generation: Generation,
-    pub proposals: BTreeMap<T, Signature>,
+    pub proposals: BTreeMap<T, C::Signature>,
 }
 
-impl<T: Proposition> Decision<T> {
-    pub fn validate(&self, public_key: &PublicKey) -> Result<()> {
+impl<T: Proposition, C: SignatureScheme> Decision<T, C> {
+    pub fn validate(&self, public_key: &C::PublicKey) -> Result<()> {
         for (proposal, sig) in self.proposals.iter() {
-            verify_sig(proposal, sig, public_key)?;
+            verify_sig::<C, _>(proposal, sig, public_key)?;
         }
 
         Ok(())
@@ -0,0 +1,41 @@
+use thiserror::Error;
+
+use crate::mvba::tag::Domain;
+use crate::NodeId;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("encoding/decoding error {0:?}")]
+    Encoding(#[from] bincode::Error),
+    #[error("blsttc Error {0}")]
+    Blsttc(#[from] blsttc::error::Error),
+    #[error("{0} is not a participant with the given key")]
+    NotParticipant(NodeId),
+    #[error("threshold {threshold} needs more than {participants} participants")]
+    ThresholdTooHigh {
+        threshold: usize,
+        participants: usize,
+    },
+    #[error("we have already dealt")]
+    AlreadyDealt,
+    #[error("invalid domain. expected: {expected}, got {got}")]
+    WrongDomain { expected: Domain, got: Domain },
+    #[error("{0} is not a participant")]
+    UnknownParticipant(NodeId),
+    #[error("dealer {0} sent two different parts")]
+    ConflictingParts(NodeId),
+    #[error("part from {dealer} has degree {degree}")]
+    InvalidDegree { dealer: NodeId, degree: usize },
+    #[error("part from {0} does not have a row for every participant")]
+    MissingRows(NodeId),
+    #[error("ack from {0} has no value for us")]
+    MissingValue(NodeId),
+    #[error("ack from {sender} has an invalid value for dealer {dealer}")]
+    InvalidValue { sender: NodeId, dealer: NodeId },
+    #[error("not enough dealers: have {have}, need {need}")]
+    NotEnoughDealers { have: usize, need: usize },
+    #[error("we can not compute our share of the contribution from dealer {0} yet")]
+    IncompleteDealer(NodeId),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+use blsttc::{poly::BivarCommitment, Ciphertext};
+use serde::{Deserialize, Serialize};
+
+use crate::mvba::tag::Domain;
+use crate::NodeId;
+
+/// The contribution of a single dealer to the key generation.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Part {
+    /// Commitment to the dealer's symmetric bivariate polynomial `f(x, y)`
+    pub commitment: BivarCommitment,
+    /// The row `f(j + 1, y)` for each participant `j`, encrypted to that participant
+    pub rows: BTreeMap<NodeId, Ciphertext>,
+}
+
+/// Confirms that the sender received a valid row from `dealer`.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Ack {
+    pub dealer: NodeId,
+    /// The value `f(i + 1, j + 1)` for each participant `j`, encrypted to that
+    /// participant, where `i` is the sender of the ack.
+    pub values: BTreeMap<NodeId, Ciphertext>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Message {
+    Part(Part),
+    Ack(Ack),
+}
+
+/// Bundle is a wrapper around the actual message
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Bundle {
+    /// The key generation session this message belongs to.
+    pub domain: Domain,
+    /// The node that sent this message.
+    pub initiator: NodeId,
+    /// This is the actual message
+    pub message: Message,
+}
+
+/// Ongoing messages definition
+#[derive(Debug, Clone)]
+pub enum Outgoing {
+    Gossip(Bundle),
+}
@@ -0,0 +1,337 @@
+//! Distributed key generation for the threshold BLS keys used by the elders.
+//!
+//! Every participant deals a random symmetric bivariate polynomial `f(x, y)` of
+//! degree `threshold`, publishing a commitment to it along with the row
+//! `f(j + 1, y)` for each participant `j`, encrypted to that participant.
+//! Participants that receive a valid row acknowledge it, and send every other
+//! participant the point of their row that the other participant needs. This
+//! lets a participant that got an invalid row from a dealer recover it from the
+//! acks of the others.
+//!
+//! Once a dealer has been acked by enough participants it is reported in
+//! [`Dkg::qualified_dealers`]. The participants must then agree on the set of
+//! dealers to use, e.g. by proposing their qualified dealers with
+//! [`crate::Consensus`] or MVBA, before calling [`Dkg::generate`]. The secret
+//! key is the sum of the dealers' `f(0, 0)` and is never known to anyone.
+
+pub mod error;
+pub mod message;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use blsttc::group::ff::Field;
+use blsttc::poly::{BivarCommitment, BivarPoly, Commitment, Poly};
+use blsttc::{Fr, PublicKey, PublicKeySet, SecretKey, SecretKeyShare};
+use rand::{CryptoRng, Rng};
+
+use self::error::{Error, Result};
+use self::message::{Ack, Bundle, Message, Outgoing, Part};
+use crate::mvba::tag::Domain;
+use crate::NodeId;
+
+/// The keys generated for this participant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub self_id: NodeId,
+    pub secret_key_share: SecretKeyShare,
+    pub public_key_set: PublicKeySet,
+}
+
+impl Outcome {
+    /// Our secret key share, as expected by `Membership::from` and `Handover::from`.
+    pub fn secret_key(&self) -> (NodeId, SecretKeyShare) {
+        (self.self_id, self.secret_key_share.clone())
+    }
+}
+
+// eval_point is where the polynomials are evaluated for the participant `id`.
+// It is `id + 1` in the field, as in `PublicKeySet::public_key_share`, so it
+// doesn't overflow and is never `0`, where the secret is.
+fn eval_point(id: NodeId) -> Fr {
+    let mut point = Fr::one();
+    point += Fr::from(id);
+    point
+}
+
+/// What we know about the contribution of a single dealer.
+#[derive(Debug)]
+struct Dealing {
+    commitment: BivarCommitment,
+    // our row of the dealer's polynomial, if the dealer sent us a valid one.
+    row: Option<Poly>,
+    // points of our row, received in the acks of the other participants.
+    values: BTreeMap<NodeId, Fr>,
+    acks: BTreeSet<NodeId>,
+}
+
+pub struct Dkg {
+    domain: Domain,
+    self_id: NodeId,
+    secret_key: SecretKey,
+    participants: BTreeMap<NodeId, PublicKey>,
+    threshold: usize,
+    dealt: bool,
+    dealings: BTreeMap<NodeId, Dealing>,
+    // acks that arrived before the part they acknowledge, keyed by dealer.
+    pending_acks: BTreeMap<NodeId, Vec<(NodeId, Ack)>>,
+    outgoings: Vec<Outgoing>,
+}
+
+impl Dkg {
+    /// `secret_key` is our long lived key, the rows and values sent to us are
+    /// encrypted to its public key in `participants`.
+    ///
+    /// Signing with the generated keys will need `threshold + 1` shares.
+    pub fn new(
+        domain: Domain,
+        self_id: NodeId,
+        secret_key: SecretKey,
+        participants: BTreeMap<NodeId, PublicKey>,
+        threshold: usize,
+    ) -> Result<Self> {
+        if participants.get(&self_id) != Some(&secret_key.public_key()) {
+            return Err(Error::NotParticipant(self_id));
+        }
+        if threshold >= participants.len() {
+            return Err(Error::ThresholdTooHigh {
+                threshold,
+                participants: participants.len(),
+            });
+        }
+
+        Ok(Self {
+            domain,
+            self_id,
+            secret_key,
+            participants,
+            threshold,
+            dealt: false,
+            dealings: BTreeMap::new(),
+            pending_acks: BTreeMap::new(),
+            outgoings: Vec::new(),
+        })
+    }
+
+    pub fn domain(&self) -> &Domain {
+        &self.domain
+    }
+
+    pub fn self_id(&self) -> NodeId {
+        self.self_id
+    }
+
+    /// Starts the key generation by dealing our contribution.
+    pub fn deal(&mut self, rng: &mut (impl Rng + CryptoRng)) -> Result<Vec<Outgoing>> {
+        if self.dealt {
+            return Err(Error::AlreadyDealt);
+        }
+        self.dealt = true;
+
+        let poly = BivarPoly::random(self.threshold, rng);
+        let mut rows = BTreeMap::new();
+        for (id, public_key) in self.participants.iter() {
+            let row = poly.row(eval_point(*id));
+            rows.insert(*id, public_key.encrypt_with_rng(rng, row.to_bytes()));
+        }
+        let part = Part {
+            commitment: poly.commitment(),
+            rows,
+        };
+
+        self.broadcast(Message::Part(part.clone()));
+        self.receive_part(self.self_id, part)?;
+        Ok(std::mem::take(&mut self.outgoings))
+    }
+
+    pub fn process_bundle(&mut self, bundle: &Bundle) -> Result<Vec<Outgoing>> {
+        if bundle.domain != self.domain {
+            return Err(Error::WrongDomain {
+                expected: self.domain.clone(),
+                got: bundle.domain.clone(),
+            });
+        }
+        if !self.participants.contains_key(&bundle.initiator) {
+            return Err(Error::UnknownParticipant(bundle.initiator));
+        }
+
+        match &bundle.message {
+            Message::Part(part) => self.receive_part(bundle.initiator, part.clone())?,
+            Message::Ack(ack) => self.receive_ack(bundle.initiator, ack.clone())?,
+        }
+        Ok(std::mem::take(&mut self.outgoings))
+    }
+
+    /// The dealers acked by at least `n - f` participants.
+    ///
+    /// These are the candidates to agree on before calling [`Self::generate`].
+    pub fn qualified_dealers(&self) -> BTreeSet<NodeId> {
+        let n = self.participants.len();
+        let faults = n.saturating_sub(1) / 3;
+        BTreeSet::from_iter(
+            self.dealings
+                .iter()
+                .filter(|(_, dealing)| dealing.acks.len() >= n - faults)
+                .map(|(dealer, _)| *dealer),
+        )
+    }
+
+    /// Combines the contributions of the agreed on `dealers` into our key share.
+    ///
+    /// At least one of the dealers must be honest, so more than `f` dealers are
+    /// required.
+    pub fn generate(&self, dealers: &BTreeSet<NodeId>) -> Result<Outcome> {
+        let faults = self.participants.len().saturating_sub(1) / 3;
+        if dealers.len() <= faults {
+            return Err(Error::NotEnoughDealers {
+                have: dealers.len(),
+                need: faults + 1,
+            });
+        }
+
+        let mut secret = Fr::zero();
+        let mut commitment = Commitment::from(vec![]);
+        for dealer in dealers {
+            let dealing = self
+                .dealings
+                .get(dealer)
+                .ok_or(Error::IncompleteDealer(*dealer))?;
+            secret += self.our_share_of(*dealer, dealing)?;
+            commitment += dealing.commitment.row(0);
+        }
+
+        Ok(Outcome {
+            self_id: self.self_id,
+            secret_key_share: SecretKeyShare::from_mut(&mut secret),
+            public_key_set: PublicKeySet::from(commitment),
+        })
+    }
+
+    // our share of a dealers contribution is `f(eval_point(self_id), 0)`.
+    fn our_share_of(&self, dealer: NodeId, dealing: &Dealing) -> Result<Fr> {
+        if let Some(row) = &dealing.row {
+            return Ok(row.evaluate(0));
+        }
+        if dealing.values.len() <= self.threshold {
+            return Err(Error::IncompleteDealer(dealer));
+        }
+        let row = Poly::interpolate(
+            dealing
+                .values
+                .iter()
+                .take(self.threshold + 1)
+                .map(|(id, value)| (eval_point(*id), *value)),
+        )?;
+        Ok(row.evaluate(0))
+    }
+
+    fn receive_part(&mut self, dealer: NodeId, part: Part) -> Result<()> {
+        if let Some(dealing) = self.dealings.get(&dealer) {
+            if dealing.commitment == part.commitment {
+                return Ok(());
+            }
+            return Err(Error::ConflictingParts(dealer));
+        }
+        if part.commitment.degree() != self.threshold {
+            return Err(Error::InvalidDegree {
+                dealer,
+                degree: part.commitment.degree(),
+            });
+        }
+        if !part.rows.keys().eq(self.participants.keys()) {
+            return Err(Error::MissingRows(dealer));
+        }
+
+        let our_row = part.rows.get(&self.self_id).and_then(|ct| {
+            let row = Poly::from_bytes(self.secret_key.decrypt(ct)?).ok()?;
+            (row.commitment() == part.commitment.row(eval_point(self.self_id))).then_some(row)
+        });
+
+        if let Some(row) = &our_row {
+            let mut values = BTreeMap::new();
+            for (id, public_key) in self.participants.iter() {
+                let value = row.evaluate(eval_point(*id)).to_bytes_be();
+                values.insert(*id, public_key.encrypt(value));
+            }
+            let ack = Ack { dealer, values };
+            self.broadcast(Message::Ack(ack.clone()));
+            self.pending_acks
+                .entry(dealer)
+                .or_default()
+                .push((self.self_id, ack));
+        } else {
+            log::warn!(
+                "party {} received an invalid row from dealer {dealer}",
+                self.self_id
+            );
+        }
+
+        self.dealings.insert(
+            dealer,
+            Dealing {
+                commitment: part.commitment,
+                row: our_row,
+                values: BTreeMap::new(),
+                acks: BTreeSet::new(),
+            },
+        );
+
+        for (sender, ack) in self.pending_acks.remove(&dealer).unwrap_or_default() {
+            self.receive_ack(sender, ack)?;
+        }
+        Ok(())
+    }
+
+    fn receive_ack(&mut self, sender: NodeId, ack: Ack) -> Result<()> {
+        let dealing = match self.dealings.get_mut(&ack.dealer) {
+            Some(dealing) => dealing,
+            None => {
+                if !self.participants.contains_key(&ack.dealer) {
+                    return Err(Error::UnknownParticipant(ack.dealer));
+                }
+                self.pending_acks
+                    .entry(ack.dealer)
+                    .or_default()
+                    .push((sender, ack));
+                return Ok(());
+            }
+        };
+        if dealing.acks.contains(&sender) {
+            return Ok(());
+        }
+
+        let value = ack
+            .values
+            .get(&self.self_id)
+            .and_then(|ct| self.secret_key.decrypt(ct))
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .and_then(|bytes| Option::<Fr>::from(Fr::from_bytes_be(&bytes)))
+            .ok_or(Error::MissingValue(sender))?;
+
+        // `f(eval_point(sender), eval_point(self_id))` is a point on our row since `f` is symmetric
+        let expected = PublicKeySet::from(dealing.commitment.row(eval_point(self.self_id)))
+            .public_key_share(sender);
+        if SecretKeyShare::from_mut(&mut value.clone()).public_key_share() != expected {
+            return Err(Error::InvalidValue {
+                sender,
+                dealer: ack.dealer,
+            });
+        }
+
+        dealing.acks.insert(sender);
+        dealing.values.insert(sender, value);
+        Ok(())
+    }
+
+    fn broadcast(&mut self, message: Message) {
+        log::debug!("party {} broadcasts {message:?}", self.self_id);
+        self.outgoings.push(Outgoing::Gossip(Bundle {
+            domain: self.domain.clone(),
+            initiator: self.self_id,
+            message,
+        }));
+    }
+}
+
+#[cfg(test)]
+#[path = "./test.rs"]
+mod test;
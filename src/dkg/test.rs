@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use blsttc::SecretKey;
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use super::error::Error;
+use super::message::{Bundle, Message, Outgoing};
+use super::{Dkg, Outcome};
+use crate::consensus::{Consensus, VoteResponse};
+use crate::mvba::tag::Domain;
+use crate::vote::{Ballot, Vote};
+use crate::{Membership, MembershipConfig, NodeId, Reconfig};
+
+const THRESHOLD: usize = 2;
+
+struct Net {
+    nodes: BTreeMap<NodeId, Dkg>,
+    queue: VecDeque<(NodeId, Bundle)>,
+}
+
+impl Net {
+    fn new(n: NodeId, rng: &mut StdRng) -> Self {
+        Self::with_ids(1..=n, rng)
+    }
+
+    fn with_ids(ids: impl IntoIterator<Item = NodeId>, rng: &mut StdRng) -> Self {
+        let secret_keys =
+            BTreeMap::from_iter(ids.into_iter().map(|id| (id, rng.gen::<SecretKey>())));
+        let participants =
+            BTreeMap::from_iter(secret_keys.iter().map(|(id, sk)| (*id, sk.public_key())));
+        let nodes = BTreeMap::from_iter(secret_keys.into_iter().map(|(id, sk)| {
+            let dkg = Dkg::new(
+                Domain::new("test-dkg", 0),
+                id,
+                sk,
+                participants.clone(),
+                THRESHOLD,
+            )
+            .unwrap();
+            (id, dkg)
+        }));
+        Self {
+            nodes,
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn enqueue(&mut self, outgoings: Vec<Outgoing>) {
+        for Outgoing::Gossip(bundle) in outgoings {
+            for id in self.nodes.keys() {
+                if *id != bundle.initiator {
+                    self.queue.push_back((*id, bundle.clone()));
+                }
+            }
+        }
+    }
+
+    fn deal(&mut self, id: NodeId, rng: &mut StdRng) {
+        let outgoings = self.nodes.get_mut(&id).unwrap().deal(rng).unwrap();
+        self.enqueue(outgoings);
+    }
+
+    fn drain_queue(&mut self) {
+        while let Some((recipient, bundle)) = self.queue.pop_front() {
+            let node = self.nodes.get_mut(&recipient).unwrap();
+            let outgoings = node.process_bundle(&bundle).unwrap();
+            self.enqueue(outgoings);
+        }
+    }
+
+    /// Agree on the dealers with the vote consensus, keyed by a trusted dealer
+    /// as the current elders would be.
+    fn agree_on_dealers(&self, rng: &mut StdRng) -> BTreeSet<NodeId> {
+        let n = self.nodes.len();
+        let elders_sk = blsttc::SecretKeySet::random(THRESHOLD, rng);
+        let mut procs = Vec::from_iter(self.nodes.keys().map(|id| {
+            Consensus::<BTreeSet<NodeId>>::from(
+                (*id, elders_sk.secret_key_share(*id)),
+                elders_sk.public_keys(),
+                n,
+            )
+        }));
+
+        let mut queue = Vec::new();
+        for (proc, dkg) in procs.iter_mut().zip(self.nodes.values()) {
+            let vote = proc
+                .sign_vote(Vote {
+                    gen: 1,
+                    ballot: Ballot::Propose(dkg.qualified_dealers()),
+                    faults: Default::default(),
+                })
+                .unwrap();
+            queue.push(proc.cast_vote(vote).unwrap());
+        }
+        while let Some(vote) = queue.pop() {
+            for proc in procs.iter_mut() {
+                if let VoteResponse::Broadcast(vote) =
+                    proc.handle_signed_vote(vote.clone()).unwrap()
+                {
+                    queue.push(vote);
+                }
+            }
+        }
+
+        let decision = procs[0].decision.clone().unwrap();
+        assert!(procs.iter().all(|p| p.decision.as_ref() == Some(&decision)));
+        decision.proposals.into_keys().next().unwrap()
+    }
+
+    fn generate(&self, dealers: &BTreeSet<NodeId>) -> Vec<Outcome> {
+        Vec::from_iter(
+            self.nodes
+                .values()
+                .map(|dkg| dkg.generate(dealers).unwrap()),
+        )
+    }
+}
+
+fn assert_keys_are_consistent(outcomes: &[Outcome]) {
+    let public_key_set = outcomes[0].public_key_set.clone();
+    assert_eq!(public_key_set.threshold(), THRESHOLD);
+
+    for outcome in outcomes {
+        assert_eq!(outcome.public_key_set, public_key_set);
+        assert_eq!(
+            outcome.secret_key_share.public_key_share(),
+            public_key_set.public_key_share(outcome.self_id)
+        );
+    }
+
+    let msg = b"signed with generated keys";
+    let shares = BTreeMap::from_iter(
+        outcomes
+            .iter()
+            .map(|o| (o.self_id, o.secret_key_share.sign(msg))),
+    );
+    let sig = public_key_set.combine_signatures(&shares).unwrap();
+    assert!(public_key_set.public_key().verify(&sig, msg));
+}
+
+#[test]
+fn test_all_honest_dealers() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::new(4, &mut rng);
+
+    for id in 1..=4 {
+        net.deal(id, &mut rng);
+    }
+    net.drain_queue();
+
+    for dkg in net.nodes.values() {
+        assert_eq!(dkg.qualified_dealers(), BTreeSet::from_iter(1..=4));
+    }
+
+    let dealers = net.agree_on_dealers(&mut rng);
+    let outcomes = net.generate(&dealers);
+    assert_keys_are_consistent(&outcomes);
+
+    // The generated keys plug straight into Membership
+    let mut procs = Vec::from_iter(outcomes.iter().map(|o| {
+        Membership::<u8>::from(
+            o.secret_key(),
+            o.public_key_set.clone(),
+            outcomes.len(),
+            MembershipConfig::default(),
+        )
+    }));
+    let mut queue = vec![procs[0].propose(Reconfig::Join(1)).unwrap()];
+    while let Some(vote) = queue.pop() {
+        for proc in procs.iter_mut() {
+            if let VoteResponse::Broadcast(vote) = proc.handle_signed_vote(vote.clone()).unwrap() {
+                queue.push(vote);
+            }
+        }
+    }
+    assert!(procs.iter().all(|p| p.gen == 1));
+}
+
+#[test]
+fn test_invalid_row_is_recovered_from_acks() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::new(4, &mut rng);
+
+    for id in 1..=4 {
+        net.deal(id, &mut rng);
+    }
+
+    // dealer 4 sends node 1 a row that does not match its commitment
+    let garbage = net.nodes[&1].participants[&1].encrypt(b"not a row");
+    for (_, bundle) in net.queue.iter_mut() {
+        if let (4, Message::Part(part)) = (bundle.initiator, &mut bundle.message) {
+            part.rows.insert(1, garbage.clone());
+        }
+    }
+    net.drain_queue();
+
+    let dealing = &net.nodes[&1].dealings[&4];
+    assert!(dealing.row.is_none());
+    assert!(!dealing.acks.contains(&1));
+    assert_eq!(
+        net.nodes[&1].qualified_dealers(),
+        BTreeSet::from_iter(1..=4)
+    );
+
+    let outcomes = net.generate(&BTreeSet::from_iter(1..=4));
+    assert_keys_are_consistent(&outcomes);
+}
+
+#[test]
+fn test_dealer_that_never_deals_is_not_qualified() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::new(4, &mut rng);
+
+    for id in 1..=3 {
+        net.deal(id, &mut rng);
+    }
+    net.drain_queue();
+
+    for dkg in net.nodes.values() {
+        assert_eq!(dkg.qualified_dealers(), BTreeSet::from_iter(1..=3));
+        assert!(matches!(
+            dkg.generate(&BTreeSet::from_iter(3..=4)),
+            Err(Error::IncompleteDealer(4))
+        ));
+        assert!(matches!(
+            dkg.generate(&BTreeSet::from_iter([1])),
+            Err(Error::NotEnoughDealers { have: 1, need: 2 })
+        ));
+    }
+
+    let outcomes = net.generate(&BTreeSet::from_iter(1..=3));
+    assert_keys_are_consistent(&outcomes);
+}
+
+#[test]
+fn test_reject_invalid_bundles() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::new(4, &mut rng);
+    net.deal(1, &mut rng);
+    let (_, bundle) = net.queue.pop_front().unwrap();
+    let dkg = net.nodes.get_mut(&2).unwrap();
+
+    let mut other_domain = bundle.clone();
+    other_domain.domain = Domain::new("test-dkg", 1);
+    assert!(matches!(
+        dkg.process_bundle(&other_domain),
+        Err(Error::WrongDomain { .. })
+    ));
+
+    let mut unknown_initiator = bundle.clone();
+    unknown_initiator.initiator = 5;
+    assert!(matches!(
+        dkg.process_bundle(&unknown_initiator),
+        Err(Error::UnknownParticipant(5))
+    ));
+
+    assert!(dkg.process_bundle(&bundle).is_ok());
+    // the same part again is ignored
+    assert!(dkg.process_bundle(&bundle).is_ok());
+
+    // but a different part from the same dealer is not
+    let mut conflicting = net.nodes.get_mut(&3).unwrap().deal(&mut rng).unwrap();
+    let Outgoing::Gossip(mut conflicting) = conflicting.remove(0);
+    conflicting.initiator = 1;
+    assert!(matches!(
+        net.nodes.get_mut(&2).unwrap().process_bundle(&conflicting),
+        Err(Error::ConflictingParts(1))
+    ));
+}
+
+#[test]
+fn test_largest_node_id() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    // the evaluation point of `NodeId::MAX` must not wrap to the secret at `0`
+    let mut net = Net::with_ids([1, 2, 3, NodeId::MAX], &mut rng);
+    for id in Vec::from_iter(net.nodes.keys().copied()) {
+        net.deal(id, &mut rng);
+    }
+    net.drain_queue();
+
+    let dealers = BTreeSet::from_iter(net.nodes.keys().copied());
+    let outcomes = net.generate(&dealers);
+    assert_keys_are_consistent(&outcomes);
+}
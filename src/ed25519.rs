@@ -1,11 +1,65 @@
+use std::collections::BTreeMap;
+
+use ed25519_dalek::{Signer, Verifier};
 use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
-use signature::{Signer, Verifier};
 
-pub type Error = signature::Error;
+use crate::crypto::{KeySet, MultiSignature, SignatureScheme};
+use crate::NodeId;
+
+pub type Error = ed25519_dalek::SignatureError;
+
+/// Ed25519 signatures, a decision is proven by the set of voter signatures.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Ed25519;
+
+impl SignatureScheme for Ed25519 {
+    type SecretKeyShare = SecretKey;
+    type PublicKeySet = KeySet<PublicKey>;
+    type PublicKey = KeySet<PublicKey>;
+    type SignatureShare = Signature;
+    type Signature = MultiSignature<Signature>;
+
+    fn sign_share(secret_key: &SecretKey, msg: &[u8]) -> Signature {
+        secret_key.sign(msg)
+    }
+
+    fn verify_share(
+        voters: &KeySet<PublicKey>,
+        voter: NodeId,
+        msg: &[u8],
+        sig: &Signature,
+    ) -> bool {
+        voters
+            .keys
+            .get(&voter)
+            .is_some_and(|key| key.verify(msg, sig).is_ok())
+    }
+
+    fn threshold(voters: &KeySet<PublicKey>) -> usize {
+        voters.threshold
+    }
+
+    fn combine_shares(
+        voters: &KeySet<PublicKey>,
+        shares: &BTreeMap<NodeId, Signature>,
+    ) -> crate::Result<MultiSignature<Signature>> {
+        MultiSignature::combine(voters, shares)
+    }
+
+    fn public_key(voters: &KeySet<PublicKey>) -> KeySet<PublicKey> {
+        voters.clone()
+    }
+
+    fn verify(voters: &KeySet<PublicKey>, msg: &[u8], sig: &MultiSignature<Signature>) -> bool {
+        sig.verify(voters, |key, sig| key.verify(msg, sig).is_ok())
+    }
+}
 
-#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub struct PublicKey(ed25519::PublicKey);
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PublicKey(ed25519_dalek::VerifyingKey);
 
 impl PublicKey {
     pub fn random(rng: impl Rng + CryptoRng) -> Self {
@@ -30,16 +84,16 @@ impl core::fmt::Display for PublicKey {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SecretKey(ed25519::Keypair);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretKey(ed25519_dalek::SigningKey);
 
 impl SecretKey {
     pub fn random(mut rng: impl Rng + CryptoRng) -> Self {
-        Self(ed25519::Keypair::generate(&mut rng))
+        Self(ed25519_dalek::SigningKey::generate(&mut rng))
     }
 
     pub fn public_key(&self) -> PublicKey {
-        PublicKey(self.0.public)
+        PublicKey(self.0.verifying_key())
     }
 
     pub fn sign(&self, msg: &[u8]) -> Signature {
@@ -48,7 +102,7 @@ impl SecretKey {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Signature(ed25519::Signature);
+pub struct Signature(ed25519_dalek::Signature);
 
 impl PartialOrd for PublicKey {
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
@@ -73,3 +127,9 @@ impl Ord for Signature {
         self.0.to_bytes().cmp(&other.0.to_bytes())
     }
 }
+
+impl std::hash::Hash for Signature {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bytes().hash(state)
+    }
+}
@@ -13,6 +13,8 @@ pub enum Error {
     NoMembers,
     #[error("We can not accept any new join requests, network member size is at capacity")]
     MembersAtCapacity,
+    #[error("The membership policy rejected the reconfig: {0}")]
+    PolicyViolation(String),
     #[error("An existing member can not request to join again")]
     JoinRequestForExistingMember,
     #[error("You must be a member to request to leave")]
@@ -37,6 +39,17 @@ pub enum Error {
     ExistingVoteIncompatibleWithNewVote,
     #[error("The super majority ballot does not actually have supermajority")]
     SuperMajorityBallotIsNotSuperMajority,
+    #[error(
+        "Generation {requested_gen} was pruned by the checkpoint at generation {checkpoint_gen}"
+    )]
+    PrunedGeneration {
+        requested_gen: Generation,
+        checkpoint_gen: Generation,
+    },
+    #[error("Checkpoint generation does not match the generation of its decision")]
+    InvalidCheckpoint,
+    #[error("Checkpoint is not signed by the elders")]
+    UnsignedCheckpoint,
     #[error("Invalid generation {0}")]
     InvalidGeneration(Generation),
     #[error("History contains an invalid vote")]
@@ -49,12 +62,35 @@ pub enum Error {
     InvalidElderSignature,
     #[error("SuperMajority signed a different set of proposals than the proposals in the vote")]
     SuperMajorityProposalsDoesNotMatchVoteProposals,
+    #[error("Not enough signature shares to build a decision proof: have {have}, need {need}")]
+    NotEnoughSignatureShares { have: usize, need: usize },
     #[error("Blsttc Error {0}")]
     Blsttc(#[from] blsttc::error::Error),
     #[error("Client attempted a faulty proposal")]
     AttemptedFaultyProposal,
+    #[error("Can not rotate elders for generation {0}, votes have already been handled for it")]
+    ElderRotationAfterVoting(Generation),
+    #[error("Can not vote on generation {0} before its elders are rotated")]
+    AwaitingElderRotation(Generation),
+    #[error("No secret key share was provided for the elder rotation at generation {0}")]
+    MissingRotatedSecretKey(Generation),
+    #[error("The decision recorded for generation {0} does not match the replayed decision")]
+    RecordedDecisionMismatch(Generation),
     #[error("Fault is not a valid fault: {0:?}")]
     FaultIsFaulty(crate::fault::FaultError),
+    #[error("Wire format version {0} is not supported")]
+    UnsupportedWireVersion(u8),
+    #[error("Unknown protocol {0} in the frame")]
+    UnknownProtocol(u8),
+    #[error("Expected a frame of {expected:?}, got {got:?}")]
+    WrongProtocol {
+        expected: crate::wire::ProtocolId,
+        got: crate::wire::ProtocolId,
+    },
+    #[error("The frame is truncated")]
+    TruncatedFrame,
+    #[error("A message of {0} bytes is too large for a frame")]
+    FrameTooLarge(usize),
 
     #[cfg(feature = "ed25519")]
     #[error("Ed25519 Error {0}")]
@@ -1,8 +1,7 @@
-use blsttc::PublicKeySet;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{NodeId, Proposition, SignedVote};
+use crate::{Bls, NodeId, Proposition, SignatureScheme, SignedVote};
 
 #[derive(Debug, Error)]
 pub enum FaultError {
@@ -16,13 +15,20 @@ pub enum FaultError {
     AccusedVoteOfInvalidFaultButAllFaultsAreValid,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub enum Fault<T: Proposition> {
-    ChangedVote { a: SignedVote<T>, b: SignedVote<T> },
-    InvalidFault { signed_vote: SignedVote<T> },
+#[serde(bound(serialize = "", deserialize = "T: Deserialize<'de>"))]
+pub enum Fault<T: Proposition, C: SignatureScheme = Bls> {
+    ChangedVote {
+        a: SignedVote<T, C>,
+        b: SignedVote<T, C>,
+    },
+    InvalidFault {
+        signed_vote: SignedVote<T, C>,
+    },
 }
 
-impl<T: Proposition> Fault<T> {
+impl<T: Proposition, C: SignatureScheme> Fault<T, C> {
     pub fn voter_at_fault(&self) -> NodeId {
         match self {
             Fault::ChangedVote { a, .. } => a.voter,
@@ -30,7 +36,7 @@ impl<T: Proposition> Fault<T> {
         }
     }
 
-    pub fn validate(&self, voters: &PublicKeySet) -> std::result::Result<(), FaultError> {
+    pub fn validate(&self, voters: &C::PublicKeySet) -> std::result::Result<(), FaultError> {
         match self {
             Self::ChangedVote { a, b } => {
                 a.validate_signature(voters)
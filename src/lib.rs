@@ -1,60 +1,78 @@
+pub mod blsttc;
 pub mod consensus;
+pub mod crypto;
 pub mod decision;
+pub mod dkg;
 pub mod fault;
+pub mod liveness;
 pub mod mvba;
+pub mod policy;
+pub mod protocol;
+pub mod signing;
 pub mod sn_handover;
 pub mod sn_membership;
+pub mod sn_membership_follower;
+pub mod storage;
 pub mod vote;
 pub mod vote_count;
+pub mod wire;
 
 #[cfg(feature = "bad_crypto")]
 pub mod bad_crypto;
-// #[cfg(feature = "blsttc")]
-// pub mod blsttc;
 #[cfg(feature = "ed25519")]
 pub mod ed25519;
+#[cfg(feature = "tokio")]
+pub mod runtime;
 
-use blsttc::{PublicKey, PublicKeySet, Signature, SignatureShare};
 use serde::Serialize;
 
+pub use crate::blsttc::Bls;
 pub use crate::consensus::{Consensus, VoteResponse};
+pub use crate::crypto::SignatureScheme;
 pub use crate::decision::Decision;
 pub use crate::fault::{Fault, FaultError};
+pub use crate::liveness::Liveness;
+pub use crate::policy::{CapacityPolicy, MembershipPolicy};
+pub use crate::protocol::{Action, Actions, Protocol};
 pub use crate::sn_handover::{Handover, UniqueSectionId};
-pub use crate::sn_membership::{Generation, Membership, Reconfig};
+pub use crate::sn_membership::{Checkpoint, Generation, Membership, MembershipConfig, Reconfig};
+pub use crate::sn_membership_follower::MembershipFollower;
+pub use crate::storage::{FileStorage, Record, Storage};
 pub use crate::vote::{Ballot, Proposition, SignedVote, Vote};
 pub use crate::vote_count::{Candidate, VoteCount};
 
-// #[cfg(feature = "bad_crypto")]
-// pub use crate::bad_crypto::{PublicKey, SecretKey, Signature};
-// #[cfg(feature = "blsttc")]
-// pub use crate::blsttc::{PublicKey, SecretKey, Signature};
-// #[cfg(feature = "ed25519")]
-// pub use crate::ed25519::{PublicKey, SecretKey, Signature};
+#[cfg(feature = "bad_crypto")]
+pub use crate::bad_crypto::BadCrypto;
+#[cfg(feature = "ed25519")]
+pub use crate::ed25519::Ed25519;
 
 pub mod error;
 pub use crate::error::Error;
 pub type Result<T> = std::result::Result<T, Error>;
-pub type NodeId = u8;
+/// Identifies a node across all protocols in this crate.
+pub type NodeId = u64;
 
-pub fn verify_sig_share<M: Serialize>(
+pub fn verify_sig_share<C: SignatureScheme, M: Serialize>(
     msg: &M,
-    sig: &SignatureShare,
+    sig: &C::SignatureShare,
     voter: NodeId,
-    voters: &PublicKeySet,
+    voters: &C::PublicKeySet,
 ) -> Result<()> {
-    let public_key = voters.public_key_share(voter as u64);
-    let msg_bytes = bincode::serialize(msg)?;
-    if public_key.verify(sig, msg_bytes) {
+    let msg_bytes = crate::signing::signing_bytes(msg)?;
+    if C::verify_share(voters, voter, &msg_bytes, sig) {
         Ok(())
     } else {
         Err(Error::InvalidElderSignature)
     }
 }
 
-pub fn verify_sig<M: Serialize>(msg: &M, sig: &Signature, public_key: &PublicKey) -> Result<()> {
-    let msg_bytes = bincode::serialize(msg)?;
-    if public_key.verify(sig, msg_bytes) {
+pub fn verify_sig<C: SignatureScheme, M: Serialize>(
+    msg: &M,
+    sig: &C::Signature,
+    public_key: &C::PublicKey,
+) -> Result<()> {
+    let msg_bytes = crate::signing::signing_bytes(msg)?;
+    if C::verify(public_key, &msg_bytes, sig) {
         Ok(())
     } else {
         Err(Error::InvalidElderSignature)
@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Generation, NodeId};
+
+/// The default number of ticks a generation may stay undecided before we
+/// re-send our votes.
+pub const DEFAULT_STALL_TIMEOUT: u64 = 4;
+
+/// A logical clock that lets [`crate::Membership`] and [`crate::Handover`]
+/// notice when a generation has stalled, e.g. because votes were dropped.
+///
+/// The clock only moves when the host calls `tick`, so the timeout is in ticks
+/// rather than wall clock time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Liveness {
+    /// Ticks a generation may stay undecided before it is considered stalled.
+    ///
+    /// Votes for a generation that we decided at least this many ticks ago are
+    /// taken as a sign that the voter is stuck on that generation.
+    pub stall_timeout: u64,
+    clock: u64,
+    undecided_since: Option<u64>,
+    decided_at: u64,
+    elders: BTreeSet<NodeId>,
+    heard_from: BTreeSet<NodeId>,
+    // the expected elders that didn't vote on the generation we decided last
+    missing: BTreeMap<NodeId, Generation>,
+    // the oldest decided generation each lagging elder is still voting on
+    lagging: BTreeMap<NodeId, Generation>,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self::new(DEFAULT_STALL_TIMEOUT)
+    }
+}
+
+impl Liveness {
+    pub fn new(stall_timeout: u64) -> Self {
+        Self {
+            stall_timeout,
+            clock: 0,
+            undecided_since: None,
+            decided_at: 0,
+            elders: BTreeSet::new(),
+            heard_from: BTreeSet::new(),
+            missing: BTreeMap::new(),
+            lagging: BTreeMap::new(),
+        }
+    }
+
+    /// The number of ticks the generation we are deciding has had votes
+    /// without reaching a decision.
+    pub fn undecided_ticks(&self) -> Option<u64> {
+        self.undecided_since.map(|since| self.clock - since)
+    }
+
+    /// Sets the elders we expect votes from.
+    ///
+    /// The elders can't be read from a threshold key set, without them only
+    /// the elders we have heard from are sent our votes. They have to be set
+    /// again once the elders are rotated.
+    pub fn set_elders(&mut self, elders: BTreeSet<NodeId>) {
+        self.elders = elders;
+    }
+
+    /// The elders we expect votes from.
+    pub fn elders(&self) -> &BTreeSet<NodeId> {
+        &self.elders
+    }
+
+    /// The elders we have received votes from.
+    pub fn heard_from(&self) -> &BTreeSet<NodeId> {
+        &self.heard_from
+    }
+
+    /// The elders we expect votes from or have heard from.
+    pub fn peers(&self) -> BTreeSet<NodeId> {
+        BTreeSet::from_iter(self.elders.union(&self.heard_from).copied())
+    }
+
+    /// Records a valid vote from `voter` for generation `gen`, `decided` is
+    /// whether we had already decided `gen` when the vote arrived.
+    pub(crate) fn observe_vote(&mut self, voter: NodeId, gen: Generation, decided: bool) {
+        self.heard_from.insert(voter);
+        if self.missing.get(&voter) == Some(&gen) {
+            self.missing.remove(&voter);
+        }
+        if decided && self.clock >= self.decided_at + self.stall_timeout {
+            let lagging_gen = self.lagging.entry(voter).or_insert(gen);
+            *lagging_gen = gen.min(*lagging_gen);
+        }
+    }
+
+    /// Records the decision of generation `gen`, that `voters` voted on.
+    pub(crate) fn observe_decision(
+        &mut self,
+        gen: Generation,
+        voters: impl IntoIterator<Item = NodeId>,
+    ) {
+        self.undecided_since = None;
+        self.decided_at = self.clock;
+
+        let voters = BTreeSet::from_iter(voters);
+        self.missing =
+            BTreeMap::from_iter(self.elders.difference(&voters).map(|elder| (*elder, gen)));
+    }
+
+    /// Forgets the elders, once they have been replaced.
+    pub(crate) fn forget_elders(&mut self) {
+        self.elders.clear();
+        self.heard_from.clear();
+        self.missing.clear();
+        self.lagging.clear();
+    }
+
+    /// Takes the elders that need help catching up, with the oldest generation
+    /// they are stuck on. The expected elders that still haven't voted on the
+    /// generation we decided last, `stall_timeout` ticks after its decision,
+    /// are stuck on it as well.
+    pub(crate) fn take_lagging(&mut self) -> BTreeMap<NodeId, Generation> {
+        if self.clock >= self.decided_at + self.stall_timeout {
+            for (elder, gen) in std::mem::take(&mut self.missing) {
+                let lagging_gen = self.lagging.entry(elder).or_insert(gen);
+                *lagging_gen = gen.min(*lagging_gen);
+            }
+        }
+        std::mem::take(&mut self.lagging)
+    }
+
+    /// Advances the clock, returns true once every `stall_timeout` ticks that
+    /// the generation we are deciding stays `undecided`.
+    pub(crate) fn tick(&mut self, undecided: bool) -> bool {
+        self.clock += 1;
+        if !undecided {
+            self.undecided_since = None;
+            return false;
+        }
+        let since = *self.undecided_since.get_or_insert(self.clock);
+        let ticks = self.clock - since;
+        ticks > 0 && ticks.is_multiple_of(self.stall_timeout.max(1))
+    }
+}
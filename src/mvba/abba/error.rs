@@ -1,3 +1,5 @@
+use crate::mvba::error::Severity;
+use crate::mvba::{tag::Tag, NodeId};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,10 +8,70 @@ pub enum Error {
     Encoding(#[from] bincode::Error),
     #[error("blsttc Error {0}")]
     Blsttc(#[from] blsttc::error::Error),
-    #[error("invalid message {0}")]
-    InvalidMessage(String),
-    #[error("generic error {0}")]
-    Generic(String),
+    #[error("invalid tag. expected: {expected}, got {got}")]
+    WrongTag { expected: Tag, got: Tag },
+    #[error("invalid round {0}, rounds start from 1")]
+    InvalidRound(usize),
+    #[error("round {round} is too far ahead of our round {current}")]
+    FutureRound { round: usize, current: usize },
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("invalid signature share from {from}")]
+    InvalidSignatureShare { from: NodeId },
+    #[error("invalid justification: {0}")]
+    InvalidJustification(Justification),
+    #[error("double {action} detected from {from}")]
+    DoubleVote { from: NodeId, action: &'static str },
+    #[error("received conflicting decision")]
+    ConflictingDecision,
+    #[error("protocol violated, {0}")]
+    ProtocolViolated(&'static str),
+}
+
+/// The check of a vote justification that failed.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Justification {
+    #[error("no-abstain justifications should come with no-abstain value")]
+    NoAbstainForAbstain,
+    #[error("abstain justifications should come with abstain value")]
+    AbstainForValue,
+    #[error("invalid main-vote justification")]
+    MainVoteSignature,
+    #[error("the first round zero justification in round {0}")]
+    FirstRoundZeroInRound(usize),
+    #[error("initial value should be zero")]
+    FirstRoundZeroForOne,
+    #[error("invalid signature for the VCBC proposal")]
+    ValiditySignature,
+    #[error("initial value should be one")]
+    ValidityForZero,
+    #[error("invalid hard-vote justification")]
+    HardVoteSignature,
+    #[error("invalid soft-vote justification")]
+    SoftVoteSignature,
+    #[error("invalid coin")]
+    CoinSignature,
+    #[error("invalid coin for round {0}")]
+    CoinForRound(usize),
+    #[error("soft pre-vote does not match the coin")]
+    SoftVoteNotCoin,
+}
+
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::WrongTag { .. } | Error::FutureRound { .. } => Severity::Drop,
+            Error::InvalidRound(_)
+            | Error::InvalidSignature
+            | Error::InvalidSignatureShare { .. }
+            | Error::InvalidJustification(_)
+            | Error::DoubleVote { .. } => Severity::Penalize,
+            Error::Encoding(_)
+            | Error::Blsttc(_)
+            | Error::ConflictingDecision
+            | Error::ProtocolViolated(_) => Severity::Fatal,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -29,8 +29,10 @@ pub enum PreVoteJustification {
     WithValidity(Hash32, Signature),
     // In Round r > 1, justification is either hard,...
     Hard(Signature),
-    // ... or soft (refer to the spec)
-    Soft(Signature),
+    // ... or soft (refer to the spec). The justification is the S-threshold signature
+    // on the abstain main-votes of round r - 1, with the common coin of round r − 1 that
+    // sets the value. The coin of the first round is fixed to one, so it has no signature.
+    Soft(Signature, Option<Signature>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -64,10 +66,18 @@ pub struct DecisionAction {
     pub sig: Signature,
 }
 
+// A share of the common coin for `round`, a signature share on `(ID, coin, r)`.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CoinShareAction {
+    pub round: usize,
+    pub sig_share: SignatureShare,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum Action {
     PreVote(PreVoteAction),
     MainVote(MainVoteAction),
+    CoinShare(CoinShareAction),
     Decision(DecisionAction),
 }
 
@@ -82,6 +92,7 @@ impl Message {
         match self.action {
             Action::PreVote(_) => "pre-vote",
             Action::MainVote(_) => "main-vote",
+            Action::CoinShare(_) => "coin-share",
             Action::Decision(_) => "decision",
         }
     }
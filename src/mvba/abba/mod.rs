@@ -2,16 +2,17 @@
 pub(crate) mod error;
 pub(crate) mod message;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use blsttc::{PublicKey, PublicKeySet, SecretKeyShare, Signature, SignatureShare};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use self::error::{Error, Result};
+use self::error::{Error, Justification, Result};
 use self::message::{
-    Action, DecisionAction, MainVoteAction, MainVoteValue, Message, PreVoteAction,
+    Action, CoinShareAction, DecisionAction, MainVoteAction, MainVoteValue, Message, PreVoteAction,
     PreVoteJustification,
 };
+use super::fault::Fault;
 use super::hash::Hash32;
 use super::tag::Tag;
 use super::NodeId;
@@ -19,8 +20,22 @@ use crate::mvba::abba::message::MainVoteJustification;
 use crate::mvba::broadcaster::Broadcaster;
 use crate::mvba::bundle;
 
-pub fn main_vote_bytes_to_sign(tag: &Tag, round: usize, v: &MainVoteValue) -> Result<Vec<u8>> {
-    Ok(bincode::serialize(&(&tag, "main-vote", round, v))?)
+// the bytes signed by the pre-votes of the ABBA instance `tag`
+pub fn pre_vote_bytes_to_sign(
+    tag: &Tag,
+    round: usize,
+    v: bool,
+) -> std::result::Result<Vec<u8>, bincode::Error> {
+    crate::signing::signing_bytes(&(&tag, "pre-vote", round, v))
+}
+
+// the bytes signed by the main-votes of the ABBA instance `tag`
+pub fn main_vote_bytes_to_sign(
+    tag: &Tag,
+    round: usize,
+    v: &MainVoteValue,
+) -> std::result::Result<Vec<u8>, bincode::Error> {
+    crate::signing::signing_bytes(&(&tag, "main-vote", round, v))
 }
 
 pub fn verify_decided_proposal(
@@ -31,12 +46,25 @@ pub fn verify_decided_proposal(
 ) -> Result<bool> {
     let sign_bytes = main_vote_bytes_to_sign(tag, round, &MainVoteValue::Value(true))?;
     if !pk.verify(sig, sign_bytes) {
-        return Err(Error::InvalidMessage("invalid signature".to_string()));
+        return Err(Error::InvalidSignature);
     }
 
     Ok(true)
 }
 
+/// The number of rounds ahead of our round that we keep the messages of.
+/// Messages for later rounds are rejected, so a peer can't make us keep the state
+/// of arbitrary rounds.
+pub(crate) const ROUND_WINDOW: usize = 16;
+
+// The kinds of messages that carry a signature share.
+#[derive(Debug, Clone, Copy)]
+enum ShareKind {
+    PreVote,
+    MainVote,
+    Coin,
+}
+
 /// The ABBA holds the information for Asynchronous Binary Byzantine Agreement protocol.
 pub(crate) struct Abba {
     tag: Tag, // Tag is a combination of Domain and proposer ID. It is unique in each ABBA instances.
@@ -47,8 +75,32 @@ pub(crate) struct Abba {
     decided_value: Option<DecisionAction>,
     pub_key_set: PublicKeySet,
     sec_key_share: SecretKeyShare,
-    round_pre_votes: Vec<HashMap<NodeId, PreVoteAction>>,
-    round_main_votes: Vec<HashMap<NodeId, MainVoteAction>>,
+    // the messages of the rounds `r - 1` to `r + ROUND_WINDOW`, keyed by round.
+    round_pre_votes: BTreeMap<usize, HashMap<NodeId, PreVoteAction>>,
+    round_main_votes: BTreeMap<usize, HashMap<NodeId, MainVoteAction>>,
+    round_coin_shares: BTreeMap<usize, HashMap<NodeId, SignatureShare>>,
+    faults: BTreeMap<NodeId, Fault>,
+    // checks the signature shares once combined, instead of one by one on arrival.
+    batch_verification: bool,
+    // the threshold signatures checked since the last round, with the bytes they sign.
+    verified_sigs: HashSet<(Vec<u8>, Signature)>,
+}
+
+/// The state of an ABBA instance, as kept in a snapshot.
+/// The keys and the cache of checked signatures are not part of it.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct State {
+    tag: Tag,
+    i: NodeId,
+    r: usize,
+    voted: bool,
+    weak_validity: Option<(Hash32, Signature)>,
+    decided_value: Option<DecisionAction>,
+    round_pre_votes: BTreeMap<usize, HashMap<NodeId, PreVoteAction>>,
+    round_main_votes: BTreeMap<usize, HashMap<NodeId, MainVoteAction>>,
+    round_coin_shares: BTreeMap<usize, HashMap<NodeId, SignatureShare>>,
+    faults: BTreeMap<NodeId, Fault>,
+    batch_verification: bool,
 }
 
 impl Abba {
@@ -67,11 +119,65 @@ impl Abba {
             decided_value: None,
             pub_key_set,
             sec_key_share,
-            round_pre_votes: Vec::new(),
-            round_main_votes: Vec::new(),
+            round_pre_votes: BTreeMap::new(),
+            round_main_votes: BTreeMap::new(),
+            round_coin_shares: BTreeMap::new(),
+            faults: BTreeMap::new(),
+            batch_verification: false,
+            verified_sigs: HashSet::new(),
         }
     }
 
+    /// restore rebuilds the instance from the `state` of its snapshot.
+    pub fn restore(state: State, pub_key_set: PublicKeySet, sec_key_share: SecretKeyShare) -> Self {
+        Self {
+            tag: state.tag,
+            i: state.i,
+            r: state.r,
+            voted: state.voted,
+            weak_validity: state.weak_validity,
+            decided_value: state.decided_value,
+            pub_key_set,
+            sec_key_share,
+            round_pre_votes: state.round_pre_votes,
+            round_main_votes: state.round_main_votes,
+            round_coin_shares: state.round_coin_shares,
+            faults: state.faults,
+            batch_verification: state.batch_verification,
+            verified_sigs: HashSet::new(),
+        }
+    }
+
+    /// snapshot returns the state of the instance, without the keys.
+    pub fn snapshot(&self) -> State {
+        State {
+            tag: self.tag.clone(),
+            i: self.i,
+            r: self.r,
+            voted: self.voted,
+            weak_validity: self.weak_validity.clone(),
+            decided_value: self.decided_value.clone(),
+            round_pre_votes: self.round_pre_votes.clone(),
+            round_main_votes: self.round_main_votes.clone(),
+            round_coin_shares: self.round_coin_shares.clone(),
+            faults: self.faults.clone(),
+            batch_verification: self.batch_verification,
+        }
+    }
+
+    /// In batch verification mode, the signature shares are not checked on
+    /// arrival but once combined, and are checked one by one only if the
+    /// combined signature is invalid. The justifications, that many parties
+    /// repeat in their votes, are checked once.
+    pub fn set_batch_verification(&mut self, enabled: bool) {
+        self.batch_verification = enabled;
+    }
+
+    /// The faults detected in this ABBA instance, keyed by the party at fault.
+    pub fn faults(&self) -> &BTreeMap<NodeId, Fault> {
+        &self.faults
+    }
+
     /// pre_vote_zero starts the abba by broadcasting a pre-vote message with value 0.
     pub fn pre_vote_zero<P: Serialize + Eq>(
         &mut self,
@@ -138,6 +244,9 @@ impl Abba {
             initiator
         );
 
+        if !self.check_round(&msg)? {
+            return Ok(());
+        }
         self.check_message(&initiator, &msg)?;
         if !self.add_message(&initiator, &msg)? {
             return Ok(());
@@ -153,7 +262,7 @@ impl Abba {
                             self.i,
                         );
 
-                        return Err(Error::Generic("received conflicting decision".into()));
+                        return Err(Error::ConflictingDecision);
                     }
                     return Ok(());
                 }
@@ -162,150 +271,14 @@ impl Abba {
                 self.broadcast(msg.action.clone(), broadcaster)?; // re-broadcast the msg in case we were the only one who received it.
             }
             Action::MainVote(action) => {
-                if action.round + 1 != self.r {
-                    return Ok(());
+                if action.round + 1 == self.r {
+                    self.pre_vote_next_round(broadcaster)?;
                 }
-                // 1. PRE-VOTE step.
-                // Note: In weaker validity mode, round 1 comes with the external justification.
-
-                // if r > 1, ...
-                if self.r > 1 {
-                    // select n − t properly justified main-votes from round r − 1
-                    let main_votes = match self.get_main_votes_by_round(self.r - 1) {
-                        Some(v) => v,
-                        None => {
-                            log::debug!(
-                                "party {} has no main-votes for this round: {}",
-                                self.i,
-                                self.r
-                            );
-                            return Ok(());
-                        }
-                    };
-                    let mut zero_votes = main_votes
-                        .iter()
-                        .filter(|(_, a)| a.value == MainVoteValue::zero());
-                    let mut one_votes = main_votes
-                        .iter()
-                        .filter(|(_, a)| a.value == MainVoteValue::one());
-                    let abstain_votes = main_votes
-                        .iter()
-                        .filter(|(_, a)| a.value == MainVoteValue::Abstain);
-
-                    // 3. CHECK FOR DECISION. Collect n −t valid and properly justified main-votes of round r .
-                    if main_votes.len() >= self.threshold() {
-                        // If these are all main-votes for b ∈ {0, 1}, then decide the value b for ID
-                        if zero_votes.clone().count() >= self.threshold() {
-                            log::info!(
-                                "party {} decided for zero. tag={}, r={}",
-                                self.i,
-                                self.tag,
-                                self.r
-                            );
-                            let sig_share: HashMap<&NodeId, &SignatureShare> =
-                                zero_votes.map(|(n, a)| (n, &a.sig_share)).collect();
-                            let sig = self.pub_key_set.combine_signatures(sig_share)?;
-                            let decision = DecisionAction {
-                                round: action.round,
-                                value: false,
-                                sig,
-                            };
-                            self.decided_value = Some(decision.clone());
-                            self.broadcast(Action::Decision(decision), broadcaster)?;
-                            return Ok(());
-                        }
-
-                        if one_votes.clone().count() >= self.threshold() {
-                            log::info!(
-                                "party {} decided for one. tag={}, r={}",
-                                self.i,
-                                self.tag,
-                                self.r
-                            );
-                            let sig_share: HashMap<&NodeId, &SignatureShare> =
-                                one_votes.map(|(n, a)| (n, &a.sig_share)).collect();
-                            let sig = self.pub_key_set.combine_signatures(sig_share)?;
-                            let decision = DecisionAction {
-                                round: action.round,
-                                value: true,
-                                sig,
-                            };
-                            self.decided_value = Some(decision.clone());
-                            self.broadcast(Action::Decision(decision), broadcaster)?;
-                            return Ok(());
-                        }
-
-                        let (value, justification) = if let Some((digest, sig)) =
-                            &self.weak_validity
-                        {
-                            if let Some(v) = self.get_pre_votes_by_round(self.r) {
-                                if v.contains_key(&self.i) {
-                                    log::debug!("party {} obtained the corresponding validating data after voting for zero", self.i);
-                                    return Ok(());
-                                }
-                            };
-
-                            // if all honest parties start with 0, they may still
-                            // decide on 1 if they obtain the corresponding validating data
-                            //  for 1 during the agreement protocol
-
-                            (
-                                true,
-                                PreVoteJustification::WithValidity(*digest, sig.clone()),
-                            )
-                        } else if let Some((_, zero_vote)) = zero_votes.next() {
-                            // if there is a main-vote for 0,
-                            let sig = match &zero_vote.justification {
-                                MainVoteJustification::NoAbstain(sig) => sig,
-                                _ => {
-                                    return Err(Error::Generic(
-                                        "protocol violated, invalid main-vote justification"
-                                            .to_string(),
-                                    ))
-                                }
-                            };
-                            // hard pre-vote for 0
-                            (false, PreVoteJustification::Hard(sig.clone()))
-                        } else if let Some((_, one_vote)) = one_votes.next() {
-                            // if there is a main-vote for 1,
-                            let sig = match &one_vote.justification {
-                                MainVoteJustification::NoAbstain(sig) => sig,
-                                _ => {
-                                    return Err(Error::Generic(
-                                        "protocol violated, invalid main-vote justification"
-                                            .to_string(),
-                                    ))
-                                }
-                            };
-                            // hard pre-vote for 1
-                            (true, PreVoteJustification::Hard(sig.clone()))
-                        } else if abstain_votes.clone().count() == main_votes.len() {
-                            // if all main-votes are abstain,
-                            let sig_share: HashMap<&NodeId, &SignatureShare> =
-                                abstain_votes.map(|(n, a)| (n, &a.sig_share)).collect();
-                            let sig = self.pub_key_set.combine_signatures(sig_share)?;
-                            // soft pre-vote for 1
-                            // Coin value bias to 1 in weaker validity mode.
-                            (true, PreVoteJustification::Soft(sig))
-                        } else {
-                            return Err(Error::Generic(
-                                "protocol violated, no pre-vote majority".to_string(),
-                            ));
-                        };
-
-                        // Produce an S-signature share on the message `(ID, pre-vote, r, b)`
-                        let sign_bytes = self.pre_vote_bytes_to_sign(self.r, value)?;
-                        let sig_share = self.sec_key_share.sign(sign_bytes);
-
-                        // Send to all parties the message `(ID, pre-vote, r, b, justification, signature share)`
-                        let action = Action::PreVote(PreVoteAction {
-                            round: self.r,
-                            value,
-                            justification,
-                            sig_share,
-                        });
-                        self.broadcast(action, broadcaster)?;
-                    }
+            }
+            Action::CoinShare(action) => {
+                // the coin may be what we are waiting for to pre-vote
+                if action.round + 1 == self.r {
+                    self.pre_vote_next_round(broadcaster)?;
                 }
             }
 
@@ -338,18 +311,34 @@ impl Abba {
                         // If there are n − t pre-votes for 0:
                         //   - value: 0
                         //   - justification: combination of all pre-votes S-Signature shares
-                        let sig_share: HashMap<&NodeId, &SignatureShare> =
-                            zero_votes.map(|(n, a)| (n, &a.sig_share)).collect();
-                        let sig = self.pub_key_set.combine_signatures(sig_share)?;
+                        let voters = Vec::from_iter(zero_votes.map(|(n, _)| *n));
+                        let sign_bytes = self.pre_vote_bytes_to_sign(self.r, false)?;
+                        let sig = match self.combine_shares(
+                            ShareKind::PreVote,
+                            self.r,
+                            voters,
+                            &sign_bytes,
+                        )? {
+                            Some(sig) => sig,
+                            None => return Ok(()),
+                        };
 
                         (MainVoteValue::zero(), MainVoteJustification::NoAbstain(sig))
                     } else if one_votes.clone().count() == pre_votes.len() {
                         // If there are n − t pre-votes for 1:
                         //   - value: 1
                         //   - justification: combination of all pre-votes S-Signature shares
-                        let sig_share: HashMap<&NodeId, &SignatureShare> =
-                            one_votes.map(|(n, a)| (n, &a.sig_share)).collect();
-                        let sig = self.pub_key_set.combine_signatures(sig_share)?;
+                        let voters = Vec::from_iter(one_votes.map(|(n, _)| *n));
+                        let sign_bytes = self.pre_vote_bytes_to_sign(self.r, true)?;
+                        let sig = match self.combine_shares(
+                            ShareKind::PreVote,
+                            self.r,
+                            voters,
+                            &sign_bytes,
+                        )? {
+                            Some(sig) => sig,
+                            None => return Ok(()),
+                        };
 
                         (MainVoteValue::one(), MainVoteJustification::NoAbstain(sig))
                     } else if let (Some(zero_vote), Some(one_vote)) = (
@@ -367,9 +356,7 @@ impl Abba {
                             MainVoteJustification::Abstain(Box::new(just_0), Box::new(just_1)),
                         )
                     } else {
-                        return Err(Error::Generic(
-                            "protocol violated, no pre-vote majority".to_string(),
-                        ));
+                        return Err(Error::ProtocolViolated("no pre-vote majority"));
                     };
 
                     // Produce an S-signature share on the message `(ID, main-vote, r, v)`
@@ -384,8 +371,210 @@ impl Abba {
                         sig_share,
                     });
                     self.broadcast(action, broadcaster)?;
+                    let round = self.r;
                     self.r += 1;
+                    self.prune_rounds();
+
+                    // 3. COIN. Release our share of the coin for this round, now that we
+                    // have main-voted. The coin of the first round is fixed to one.
+                    if round > 1 {
+                        let sign_bytes = self.coin_bytes_to_sign(round)?;
+                        let sig_share = self.sec_key_share.sign(sign_bytes);
+                        let action = Action::CoinShare(CoinShareAction { round, sig_share });
+                        self.broadcast(action, broadcaster)?;
+                    }
+                    self.pre_vote_next_round(broadcaster)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // pre_vote_next_round runs the pre-vote step of round `r > 1`, once we have
+    // the main-votes of round `r - 1`.
+    fn pre_vote_next_round<P: Serialize + Eq>(
+        &mut self,
+        broadcaster: &mut Broadcaster<P>,
+    ) -> Result<()> {
+        if self.decided_value.is_some() {
+            return Ok(());
+        }
+
+        // 1. PRE-VOTE step.
+        // Note: In weaker validity mode, round 1 comes with the external justification.
+
+        // if r > 1, ...
+        if self.r > 1 {
+            // select n − t properly justified main-votes from round r − 1
+            let main_votes = match self.get_main_votes_by_round(self.r - 1) {
+                Some(v) => v,
+                None => {
+                    log::debug!(
+                        "party {} has no main-votes for this round: {}",
+                        self.i,
+                        self.r
+                    );
+                    return Ok(());
                 }
+            };
+            let mut zero_votes = main_votes
+                .iter()
+                .filter(|(_, a)| a.value == MainVoteValue::zero());
+            let mut one_votes = main_votes
+                .iter()
+                .filter(|(_, a)| a.value == MainVoteValue::one());
+            let abstain_votes = main_votes
+                .iter()
+                .filter(|(_, a)| a.value == MainVoteValue::Abstain);
+
+            // 3. CHECK FOR DECISION. Collect n −t valid and properly justified main-votes of round r .
+            if main_votes.len() >= self.threshold() {
+                // If these are all main-votes for b ∈ {0, 1}, then decide the value b for ID
+                if zero_votes.clone().count() >= self.threshold() {
+                    log::info!(
+                        "party {} decided for zero. tag={}, r={}",
+                        self.i,
+                        self.tag,
+                        self.r
+                    );
+                    let voters = Vec::from_iter(zero_votes.map(|(n, _)| *n));
+                    let sign_bytes =
+                        self.main_vote_bytes_to_sign(self.r - 1, &MainVoteValue::Value(false))?;
+                    let sig = match self.combine_shares(
+                        ShareKind::MainVote,
+                        self.r - 1,
+                        voters,
+                        &sign_bytes,
+                    )? {
+                        Some(sig) => sig,
+                        None => return Ok(()),
+                    };
+                    let decision = DecisionAction {
+                        round: self.r - 1,
+                        value: false,
+                        sig,
+                    };
+                    self.decided_value = Some(decision.clone());
+                    self.broadcast(Action::Decision(decision), broadcaster)?;
+                    return Ok(());
+                }
+
+                if one_votes.clone().count() >= self.threshold() {
+                    log::info!(
+                        "party {} decided for one. tag={}, r={}",
+                        self.i,
+                        self.tag,
+                        self.r
+                    );
+                    let voters = Vec::from_iter(one_votes.map(|(n, _)| *n));
+                    let sign_bytes =
+                        self.main_vote_bytes_to_sign(self.r - 1, &MainVoteValue::Value(true))?;
+                    let sig = match self.combine_shares(
+                        ShareKind::MainVote,
+                        self.r - 1,
+                        voters,
+                        &sign_bytes,
+                    )? {
+                        Some(sig) => sig,
+                        None => return Ok(()),
+                    };
+                    let decision = DecisionAction {
+                        round: self.r - 1,
+                        value: true,
+                        sig,
+                    };
+                    self.decided_value = Some(decision.clone());
+                    self.broadcast(Action::Decision(decision), broadcaster)?;
+                    return Ok(());
+                }
+
+                if let Some(v) = self.get_pre_votes_by_round(self.r) {
+                    if v.contains_key(&self.i) {
+                        log::debug!("party {} has pre-voted in round {}", self.i, self.r);
+                        return Ok(());
+                    }
+                };
+
+                let (value, justification) = if let Some((digest, sig)) = &self.weak_validity {
+                    // if all honest parties start with 0, they may still
+                    // decide on 1 if they obtain the corresponding validating data
+                    //  for 1 during the agreement protocol
+
+                    (
+                        true,
+                        PreVoteJustification::WithValidity(*digest, sig.clone()),
+                    )
+                } else if let Some((_, zero_vote)) = zero_votes.next() {
+                    // if there is a main-vote for 0,
+                    let sig = match &zero_vote.justification {
+                        MainVoteJustification::NoAbstain(sig) => sig,
+                        _ => {
+                            return Err(Error::ProtocolViolated("invalid main-vote justification"))
+                        }
+                    };
+                    // hard pre-vote for 0
+                    (false, PreVoteJustification::Hard(sig.clone()))
+                } else if let Some((_, one_vote)) = one_votes.next() {
+                    // if there is a main-vote for 1,
+                    let sig = match &one_vote.justification {
+                        MainVoteJustification::NoAbstain(sig) => sig,
+                        _ => {
+                            return Err(Error::ProtocolViolated("invalid main-vote justification"))
+                        }
+                    };
+                    // hard pre-vote for 1
+                    (true, PreVoteJustification::Hard(sig.clone()))
+                } else if abstain_votes.clone().count() == main_votes.len() {
+                    // if all main-votes are abstain,
+                    let voters = Vec::from_iter(abstain_votes.map(|(n, _)| *n));
+                    let sign_bytes =
+                        self.main_vote_bytes_to_sign(self.r - 1, &MainVoteValue::Abstain)?;
+                    let sig = match self.combine_shares(
+                        ShareKind::MainVote,
+                        self.r - 1,
+                        voters,
+                        &sign_bytes,
+                    )? {
+                        Some(sig) => sig,
+                        None => return Ok(()),
+                    };
+                    // soft pre-vote for the value of the common coin of round r - 1.
+                    // The coin of the first round is fixed to one, this biases the
+                    // agreement towards one in weaker validity mode.
+                    if self.r - 1 == 1 {
+                        (true, PreVoteJustification::Soft(sig, None))
+                    } else {
+                        match self.coin(self.r - 1)? {
+                            Some(coin) => {
+                                (coin.parity(), PreVoteJustification::Soft(sig, Some(coin)))
+                            }
+                            None => {
+                                log::debug!(
+                                    "party {} is waiting for the coin of round {}",
+                                    self.i,
+                                    self.r - 1
+                                );
+                                return Ok(());
+                            }
+                        }
+                    }
+                } else {
+                    return Err(Error::ProtocolViolated("no pre-vote majority"));
+                };
+
+                // Produce an S-signature share on the message `(ID, pre-vote, r, b)`
+                let sign_bytes = self.pre_vote_bytes_to_sign(self.r, value)?;
+                let sig_share = self.sec_key_share.sign(sign_bytes);
+
+                // Send to all parties the message `(ID, pre-vote, r, b, justification, signature share)`
+                let action = Action::PreVote(PreVoteAction {
+                    round: self.r,
+                    value,
+                    justification,
+                    sig_share,
+                });
+                self.broadcast(action, broadcaster)?;
             }
         }
 
@@ -415,120 +604,207 @@ impl Abba {
     fn add_message(&mut self, initiator: &NodeId, msg: &Message) -> Result<bool> {
         match &msg.action {
             Action::PreVote(action) => {
-                let pre_votes = self.get_mut_pre_votes_by_round(action.round)?;
-                if let Some(exist) = pre_votes.get(initiator) {
+                if let Some(exist) = self.get_mut_pre_votes_by_round(action.round).get(initiator) {
+                    let exist = exist.clone();
+                    let exist_bytes = self.pre_vote_bytes_to_sign(action.round, exist.value)?;
+                    if &exist != action
+                        && self.is_invalid_unchecked(initiator, &exist.sig_share, &exist_bytes)
+                    {
+                        self.get_mut_pre_votes_by_round(action.round)
+                            .remove(initiator);
+                    }
+                }
+                if let Some(exist) = self.get_mut_pre_votes_by_round(action.round).get(initiator) {
                     if exist != action {
-                        return Err(Error::InvalidMessage(format!(
-                            "double pre-vote detected from {initiator:?}"
-                        )));
+                        let exist = exist.clone();
+                        if exist.value != action.value
+                            && self.is_valid_evidence(
+                                initiator,
+                                [
+                                    (
+                                        &exist.sig_share,
+                                        self.pre_vote_bytes_to_sign(action.round, exist.value)?,
+                                    ),
+                                    (
+                                        &action.sig_share,
+                                        self.pre_vote_bytes_to_sign(action.round, action.value)?,
+                                    ),
+                                ],
+                            )
+                        {
+                            let (zero, one) = match action.value {
+                                true => (exist.sig_share.clone(), action.sig_share.clone()),
+                                false => (action.sig_share.clone(), exist.sig_share.clone()),
+                            };
+                            self.add_fault(Fault::ChangedPreVote {
+                                tag: self.tag.clone(),
+                                voter: *initiator,
+                                round: action.round,
+                                zero,
+                                one,
+                            });
+                        }
+                        return Err(Error::DoubleVote {
+                            from: *initiator,
+                            action: "pre-vote",
+                        });
                     }
                     return Ok(false);
                 }
 
-                pre_votes.insert(*initiator, action.clone());
+                self.get_mut_pre_votes_by_round(action.round)
+                    .insert(*initiator, action.clone());
             }
             Action::MainVote(action) => {
-                let main_votes = self.get_mut_main_votes_by_round(action.round)?;
-                if let Some(exist) = main_votes.get(initiator) {
+                if let Some(exist) = self
+                    .get_mut_main_votes_by_round(action.round)
+                    .get(initiator)
+                {
+                    let exist = exist.clone();
+                    let exist_bytes = self.main_vote_bytes_to_sign(action.round, &exist.value)?;
+                    if &exist != action
+                        && self.is_invalid_unchecked(initiator, &exist.sig_share, &exist_bytes)
+                    {
+                        self.get_mut_main_votes_by_round(action.round)
+                            .remove(initiator);
+                    }
+                }
+                if let Some(exist) = self
+                    .get_mut_main_votes_by_round(action.round)
+                    .get(initiator)
+                {
                     if exist != action {
-                        return Err(Error::InvalidMessage(format!(
-                            "double main-vote detected from {initiator:?}"
-                        )));
+                        let exist = exist.clone();
+                        if exist.value != action.value
+                            && self.is_valid_evidence(
+                                initiator,
+                                [
+                                    (
+                                        &exist.sig_share,
+                                        self.main_vote_bytes_to_sign(action.round, &exist.value)?,
+                                    ),
+                                    (
+                                        &action.sig_share,
+                                        self.main_vote_bytes_to_sign(action.round, &action.value)?,
+                                    ),
+                                ],
+                            )
+                        {
+                            let a = (exist.value, exist.sig_share.clone());
+                            self.add_fault(Fault::ChangedMainVote {
+                                tag: self.tag.clone(),
+                                voter: *initiator,
+                                round: action.round,
+                                a,
+                                b: (action.value, action.sig_share.clone()),
+                            });
+                        }
+                        return Err(Error::DoubleVote {
+                            from: *initiator,
+                            action: "main-vote",
+                        });
                     }
                     return Ok(false);
                 }
 
-                main_votes.insert(*initiator, action.clone());
+                self.get_mut_main_votes_by_round(action.round)
+                    .insert(*initiator, action.clone());
+            }
+            Action::CoinShare(action) => {
+                if let Some(exist) = self
+                    .get_mut_coin_shares_by_round(action.round)
+                    .get(initiator)
+                {
+                    let exist = exist.clone();
+                    let exist_bytes = self.coin_bytes_to_sign(action.round)?;
+                    if exist != action.sig_share
+                        && self.is_invalid_unchecked(initiator, &exist, &exist_bytes)
+                    {
+                        self.get_mut_coin_shares_by_round(action.round)
+                            .remove(initiator);
+                    }
+                }
+                let coin_shares = self.get_mut_coin_shares_by_round(action.round);
+                if let Some(exist) = coin_shares.get(initiator) {
+                    if exist != &action.sig_share {
+                        return Err(Error::DoubleVote {
+                            from: *initiator,
+                            action: "coin-share",
+                        });
+                    }
+                    return Ok(false);
+                }
+
+                coin_shares.insert(*initiator, action.sig_share.clone());
             }
             Action::Decision(_action) => (),
         }
         Ok(true)
     }
 
-    fn check_message(&self, initiator: &NodeId, msg: &Message) -> Result<()> {
+    // check_round keeps the messages we store bounded to a window around our round.
+    // It returns false for the messages of the rounds we don't need anymore.
+    fn check_round(&self, msg: &Message) -> Result<bool> {
+        let round = match &msg.action {
+            Action::PreVote(action) => action.round,
+            Action::MainVote(action) => action.round,
+            Action::CoinShare(action) => action.round,
+            // a decision is final, whatever round it was made in
+            Action::Decision(_) => return Ok(true),
+        };
+
+        if round == 0 {
+            return Err(Error::InvalidRound(round));
+        }
+        if round > self.r + ROUND_WINDOW {
+            return Err(Error::FutureRound {
+                round,
+                current: self.r,
+            });
+        }
+        if round + 1 < self.r {
+            log::trace!(
+                "party {} ignores a message of the old round {round}",
+                self.i
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn add_fault(&mut self, fault: Fault) {
+        let voter = fault.voter_at_fault();
+        log::warn!("party {} detected a fault from {voter}: {fault:?}", self.i);
+        self.faults.entry(voter).or_insert(fault);
+    }
+
+    fn check_message(&mut self, initiator: &NodeId, msg: &Message) -> Result<()> {
         if msg.tag != self.tag {
-            return Err(Error::InvalidMessage(format!(
-                "invalid tag. expected: {}, got {}",
-                self.tag, msg.tag
-            )));
+            return Err(Error::WrongTag {
+                expected: self.tag.clone(),
+                got: msg.tag.clone(),
+            });
         }
 
         match &msg.action {
             Action::PreVote(action) => {
                 // check the validity of the S-signature share on message (ID, pre-vote, r, b)
                 let sign_bytes = self.pre_vote_bytes_to_sign(action.round, action.value)?;
-                if !self
-                    .pub_key_set
-                    .public_key_share(initiator)
-                    .verify(&action.sig_share, sign_bytes)
-                {
-                    return Err(Error::InvalidMessage("invalid signature share".to_string()));
+                if !self.check_share(initiator, &action.sig_share, &sign_bytes) {
+                    return Err(Error::InvalidSignatureShare { from: *initiator });
                 }
 
-                match &action.justification {
-                    PreVoteJustification::FirstRoundZero => {
-                        if action.round != 1 {
-                            return Err(Error::InvalidMessage(format!(
-                                "invalid round. expected 1, got {}",
-                                action.round
-                            )));
-                        }
-
-                        if action.value {
-                            return Err(Error::InvalidMessage(
-                                "initial value should be zero".to_string(),
-                            ));
-                        }
-                    }
-                    PreVoteJustification::WithValidity(digest, sig) => {
-                        let sign_bytes =
-                            crate::mvba::vcbc::c_ready_bytes_to_sign(&self.tag, digest)?;
-
-                        if !self.pub_key_set.public_key().verify(sig, sign_bytes) {
-                            return Err(Error::InvalidMessage(
-                                "invalid signature for the VCBC proposal".to_string(),
-                            ));
-                        }
-
-                        // A weaker validity: an honest party may only decide on a value
-                        // for which it has the accompanying validating data.
-                        if !action.value {
-                            return Err(Error::InvalidMessage(
-                                "initial value should be one".to_string(),
-                            ));
-                        }
-                    }
-                    PreVoteJustification::Hard(sig) => {
-                        // Hard pre-vote justification is the S-threshold signature for `(ID, pre-vote, r − 1, b)`
-                        let sign_bytes =
-                            self.pre_vote_bytes_to_sign(action.round - 1, action.value)?;
-                        if !self.pub_key_set.public_key().verify(sig, sign_bytes) {
-                            return Err(Error::InvalidMessage(
-                                "invalid hard-vote justification".to_string(),
-                            ));
-                        }
-                    }
-                    PreVoteJustification::Soft(sig) => {
-                        // Soft pre-vote justification is the S-threshold signature for `(ID, main-vote, r − 1, abstain)`
-                        let sign_bytes = self
-                            .main_vote_bytes_to_sign(action.round - 1, &MainVoteValue::Abstain)?;
-                        if !self.pub_key_set.public_key().verify(sig, sign_bytes) {
-                            return Err(Error::InvalidMessage(
-                                "invalid soft-vote justification".to_string(),
-                            ));
-                        }
-                    }
-                }
+                self.check_pre_vote_justification(
+                    action.round,
+                    action.value,
+                    &action.justification,
+                )?;
             }
             Action::MainVote(action) => {
                 // check the validity of the S-signature share
                 let sign_bytes = self.main_vote_bytes_to_sign(action.round, &action.value)?;
-                if !self
-                    .pub_key_set
-                    .public_key_share(initiator)
-                    .verify(&action.sig_share, sign_bytes)
-                {
-                    return Err(Error::InvalidMessage("invalid signature share".to_string()));
+                if !self.check_share(initiator, &action.sig_share, &sign_bytes) {
+                    return Err(Error::InvalidSignatureShare { from: *initiator });
                 }
 
                 match &action.justification {
@@ -536,75 +812,44 @@ impl Abba {
                         let pre_vote_value = if let MainVoteValue::Value(value) = action.value {
                             value
                         } else {
-                            return Err(Error::InvalidMessage(
-                                "no-abstain justifications should come with no-abstain value"
-                                    .to_string(),
+                            return Err(Error::InvalidJustification(
+                                Justification::NoAbstainForAbstain,
                             ));
                         };
                         // valid S-signature share on the message `(ID, pre-vote, r, b)`
                         let sign_bytes =
                             self.pre_vote_bytes_to_sign(action.round, pre_vote_value)?;
-                        if !self.pub_key_set.public_key().verify(sig, sign_bytes) {
-                            return Err(Error::InvalidMessage(
-                                "invalid main-vote justification".to_string(),
+                        if !self.check_sig(sig, sign_bytes) {
+                            return Err(Error::InvalidJustification(
+                                Justification::MainVoteSignature,
                             ));
                         }
                     }
                     MainVoteJustification::Abstain(just_0, just_1) => {
                         if action.value != MainVoteValue::Abstain {
-                            return Err(Error::InvalidMessage(format!(
-                                "abstain justifications should come with abstain value: {:?}",
-                                action.value
-                            )));
-                        }
-                        match just_0.as_ref() {
-                            PreVoteJustification::FirstRoundZero => {}
-                            PreVoteJustification::Hard(sig) => {
-                                let sign_bytes =
-                                    self.pre_vote_bytes_to_sign(action.round - 1, false)?;
-                                if !self.pub_key_set.public_key().verify(sig, sign_bytes) {
-                                    return Err(Error::InvalidMessage(
-                                        "invalid abstain justification for value zero".to_string(),
-                                    ));
-                                }
-                            }
-                            _ => {
-                                return Err(Error::InvalidMessage(format!(
-                                    "invalid justification for value 0 in round 1: {just_0:?}"
-                                )));
-                            }
-                        }
-
-                        match just_1.as_ref() {
-                            PreVoteJustification::WithValidity(digest, sig) => {
-                                let sign_bytes =
-                                    crate::mvba::vcbc::c_ready_bytes_to_sign(&self.tag, digest)?;
-
-                                if !self.pub_key_set.public_key().verify(sig, sign_bytes) {
-                                    return Err(Error::InvalidMessage(
-                                        "invalid signature for the VCBC proposal".to_string(),
-                                    ));
-                                }
-                            }
-                            _ => {
-                                return Err(Error::InvalidMessage(format!(
-                                    "invalid justification for value 1 in round 1: {just_1:?}"
-                                )));
-                            }
+                            return Err(Error::InvalidJustification(
+                                Justification::AbstainForValue,
+                            ));
                         }
+                        // the two conflicting pre-votes of this round
+                        self.check_pre_vote_justification(action.round, false, just_0)?;
+                        self.check_pre_vote_justification(action.round, true, just_1)?;
                     }
                 }
             }
+            Action::CoinShare(action) => {
+                // check the validity of the S-signature share on message (ID, coin, r)
+                let sign_bytes = self.coin_bytes_to_sign(action.round)?;
+                if !self.check_share(initiator, &action.sig_share, &sign_bytes) {
+                    return Err(Error::InvalidSignatureShare { from: *initiator });
+                }
+            }
             Action::Decision(action) => {
                 // check the validity of the signature
                 let sign_bytes = self
                     .main_vote_bytes_to_sign(action.round, &MainVoteValue::Value(action.value))?;
-                if !self
-                    .pub_key_set
-                    .public_key()
-                    .verify(&action.sig, sign_bytes)
-                {
-                    return Err(Error::InvalidMessage("invalid signature".to_string()));
+                if !self.check_sig(&action.sig, sign_bytes) {
+                    return Err(Error::InvalidSignature);
                 }
             }
         }
@@ -612,6 +857,216 @@ impl Abba {
         Ok(())
     }
 
+    // check_pre_vote_justification checks the justification of a pre-vote for
+    // `value` in `round`.
+    fn check_pre_vote_justification(
+        &mut self,
+        round: usize,
+        value: bool,
+        justification: &PreVoteJustification,
+    ) -> Result<()> {
+        match justification {
+            PreVoteJustification::FirstRoundZero => {
+                if round != 1 {
+                    return Err(Error::InvalidJustification(
+                        Justification::FirstRoundZeroInRound(round),
+                    ));
+                }
+
+                if value {
+                    return Err(Error::InvalidJustification(
+                        Justification::FirstRoundZeroForOne,
+                    ));
+                }
+            }
+            PreVoteJustification::WithValidity(digest, sig) => {
+                let sign_bytes = crate::mvba::vcbc::c_ready_bytes_to_sign(&self.tag, digest)?;
+
+                if !self.check_sig(sig, sign_bytes) {
+                    return Err(Error::InvalidJustification(
+                        Justification::ValiditySignature,
+                    ));
+                }
+
+                // A weaker validity: an honest party may only decide on a value
+                // for which it has the accompanying validating data.
+                if !value {
+                    return Err(Error::InvalidJustification(Justification::ValidityForZero));
+                }
+            }
+            PreVoteJustification::Hard(sig) => {
+                // Hard pre-vote justification is the S-threshold signature for `(ID, pre-vote, r − 1, b)`
+                let sign_bytes = self.pre_vote_bytes_to_sign(round - 1, value)?;
+                if !self.check_sig(sig, sign_bytes) {
+                    return Err(Error::InvalidJustification(
+                        Justification::HardVoteSignature,
+                    ));
+                }
+            }
+            PreVoteJustification::Soft(sig, coin) => {
+                // Soft pre-vote justification is the S-threshold signature for `(ID, main-vote, r − 1, abstain)`
+                let sign_bytes =
+                    self.main_vote_bytes_to_sign(round - 1, &MainVoteValue::Abstain)?;
+                if !self.check_sig(sig, sign_bytes) {
+                    return Err(Error::InvalidJustification(
+                        Justification::SoftVoteSignature,
+                    ));
+                }
+
+                // and the value must be the coin of round r − 1
+                let coin_value = match (round - 1, coin) {
+                    (1, None) => true,
+                    (coin_round, Some(coin)) if coin_round > 1 => {
+                        let sign_bytes = self.coin_bytes_to_sign(coin_round)?;
+                        if !self.check_sig(coin, sign_bytes) {
+                            return Err(Error::InvalidJustification(Justification::CoinSignature));
+                        }
+                        coin.parity()
+                    }
+                    _ => {
+                        return Err(Error::InvalidJustification(Justification::CoinForRound(
+                            round,
+                        )))
+                    }
+                };
+                if value != coin_value {
+                    return Err(Error::InvalidJustification(Justification::SoftVoteNotCoin));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // coin returns the common coin for `round`, once we have enough coin shares.
+    // The value of the coin is the parity of the signature.
+    fn coin(&mut self, round: usize) -> Result<Option<Signature>> {
+        let voters = match self.get_coin_shares_by_round(round) {
+            Some(shares) if shares.len() >= self.threshold() => {
+                Vec::from_iter(shares.keys().copied())
+            }
+            _ => return Ok(None),
+        };
+        let sign_bytes = self.coin_bytes_to_sign(round)?;
+        self.combine_shares(ShareKind::Coin, round, voters, &sign_bytes)
+    }
+
+    // check_share checks a signature share on arrival. In batch verification
+    // mode the shares are checked once combined, see `combine_shares`.
+    fn check_share(&self, from: &NodeId, share: &SignatureShare, sign_bytes: &[u8]) -> bool {
+        self.batch_verification
+            || self
+                .pub_key_set
+                .public_key_share(from)
+                .verify(share, sign_bytes)
+    }
+
+    // is_invalid_unchecked tells if the share we kept from `voter` is invalid,
+    // when another message of the voter arrives. In batch verification mode the
+    // kept share was not checked on arrival, if it is invalid it gives way to
+    // the new message instead of rejecting it as a double vote.
+    fn is_invalid_unchecked(
+        &self,
+        voter: &NodeId,
+        share: &SignatureShare,
+        sign_bytes: &[u8],
+    ) -> bool {
+        self.batch_verification
+            && !self
+                .pub_key_set
+                .public_key_share(voter)
+                .verify(share, sign_bytes)
+    }
+
+    // is_valid_evidence checks the shares of an equivocation before it is
+    // reported, as in batch verification mode they were not checked on arrival.
+    fn is_valid_evidence(&self, voter: &NodeId, shares: [(&SignatureShare, Vec<u8>); 2]) -> bool {
+        !self.batch_verification
+            || shares.iter().all(|(share, sign_bytes)| {
+                self.pub_key_set
+                    .public_key_share(voter)
+                    .verify(share, sign_bytes)
+            })
+    }
+
+    // check_sig checks a threshold signature of the parties. In batch
+    // verification mode the valid signatures are remembered until the next
+    // round, as the same justification comes with the votes of many parties.
+    fn check_sig(&mut self, sig: &Signature, sign_bytes: Vec<u8>) -> bool {
+        if !self.batch_verification {
+            return self.pub_key_set.public_key().verify(sig, sign_bytes);
+        }
+        let key = (sign_bytes, sig.clone());
+        if self.verified_sigs.contains(&key) {
+            return true;
+        }
+        let valid = self.pub_key_set.public_key().verify(sig, &key.0);
+        if valid {
+            self.verified_sigs.insert(key);
+        }
+        valid
+    }
+
+    // combine_shares combines the signature shares of `voters` for the messages
+    // of `kind` in `round`. In batch verification mode, the combined signature
+    // is checked. If it is invalid, each share is checked and the messages with
+    // an invalid share are dropped, then None is returned until there are
+    // enough valid shares.
+    fn combine_shares(
+        &mut self,
+        kind: ShareKind,
+        round: usize,
+        voters: Vec<NodeId>,
+        sign_bytes: &[u8],
+    ) -> Result<Option<Signature>> {
+        let shares = Vec::from_iter(voters.into_iter().filter_map(|voter| {
+            let share = match kind {
+                ShareKind::PreVote => self
+                    .get_pre_votes_by_round(round)?
+                    .get(&voter)
+                    .map(|a| &a.sig_share),
+                ShareKind::MainVote => self
+                    .get_main_votes_by_round(round)?
+                    .get(&voter)
+                    .map(|a| &a.sig_share),
+                ShareKind::Coin => self.get_coin_shares_by_round(round)?.get(&voter),
+            };
+            share.map(|share| (voter, share.clone()))
+        }));
+        let sig = self
+            .pub_key_set
+            .combine_signatures(shares.iter().map(|(voter, share)| (voter, share)))?;
+        if !self.batch_verification || self.pub_key_set.public_key().verify(&sig, sign_bytes) {
+            return Ok(Some(sig));
+        }
+
+        for (voter, share) in shares {
+            if self
+                .pub_key_set
+                .public_key_share(voter)
+                .verify(&share, sign_bytes)
+            {
+                continue;
+            }
+            log::warn!(
+                "party {} dropped the {kind:?} of {voter} with an invalid signature share",
+                self.i
+            );
+            match kind {
+                ShareKind::PreVote => {
+                    self.get_mut_pre_votes_by_round(round).remove(&voter);
+                }
+                ShareKind::MainVote => {
+                    self.get_mut_main_votes_by_round(round).remove(&voter);
+                }
+                ShareKind::Coin => {
+                    self.get_mut_coin_shares_by_round(round).remove(&voter);
+                }
+            }
+        }
+        Ok(None)
+    }
+
     // broadcast sends the message `msg` to all other peers in the network.
     // It adds the message to our messages log.
     fn broadcast<P: Serialize + Eq>(
@@ -633,13 +1088,19 @@ impl Abba {
     // pre_vote_bytes_to_sign generates bytes for Pre-Vote signature share.
     // pre_vote_bytes_to_sign is same as serialized of $(ID, pre-vote, r, b)$ in spec.
     fn pre_vote_bytes_to_sign(&self, round: usize, v: bool) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(&(&self.tag, "pre-vote", round, v))?)
+        Ok(pre_vote_bytes_to_sign(&self.tag, round, v)?)
     }
 
     // main_vote_bytes_to_sign generates bytes for Main-Vote signature share.
     // main_vote_bytes_to_sign is same as serialized of $(ID, main-vote, r, v)$ in spec.
     fn main_vote_bytes_to_sign(&self, round: usize, v: &MainVoteValue) -> Result<Vec<u8>> {
-        main_vote_bytes_to_sign(&self.tag, round, v)
+        Ok(main_vote_bytes_to_sign(&self.tag, round, v)?)
+    }
+
+    // coin_bytes_to_sign generates bytes for the coin signature share.
+    // coin_bytes_to_sign is same as serialized of $(ID, coin, r)$ in spec.
+    fn coin_bytes_to_sign(&self, round: usize) -> Result<Vec<u8>> {
+        Ok(crate::signing::signing_bytes(&(&self.tag, "coin", round))?)
     }
 
     // threshold return the threshold of the public key set.
@@ -651,53 +1112,53 @@ impl Abba {
     /// returns the pre votes for the given `round`.
     /// If there is not votes for the `round`, it returns None.
     fn get_pre_votes_by_round(&self, round: usize) -> Option<&HashMap<NodeId, PreVoteAction>> {
-        self.round_pre_votes.get(round - 1) // rounds start from 1 based on spec
+        self.round_pre_votes.get(&round)
     }
 
     /// returns the main votes for the given `round`.
     /// If there is not votes for the `round`, it returns None.
     fn get_main_votes_by_round(&self, round: usize) -> Option<&HashMap<NodeId, MainVoteAction>> {
-        self.round_main_votes.get(round - 1) // rounds start from 1 based on spec
+        self.round_main_votes.get(&round)
     }
 
-    /// returns the pre votes for the given `round`.
-    /// If there is not votes for the `round`, it expand the `round_pre_votes`.
-    fn get_mut_pre_votes_by_round(
-        &mut self,
-        round: usize,
-    ) -> Result<&mut HashMap<NodeId, PreVoteAction>> {
-        // make sure we have the round messages
-        while self.round_pre_votes.len() < round {
-            self.round_pre_votes.push(HashMap::new());
-        }
+    /// returns the coin shares for the given `round`.
+    /// If there is no shares for the `round`, it returns None.
+    fn get_coin_shares_by_round(&self, round: usize) -> Option<&HashMap<NodeId, SignatureShare>> {
+        self.round_coin_shares.get(&round)
+    }
 
-        // rounds start from 1 based on spec
-        match self.round_pre_votes.get_mut(round - 1) {
-            Some(v) => Ok(v),
-            None => Err(Error::Generic(format!(
-                "round_pre_votes is not initialized for round {round}"
-            ))),
-        }
+    /// returns the pre votes for the given `round`.
+    /// If there is not votes for the `round`, it adds an empty set of votes.
+    fn get_mut_pre_votes_by_round(&mut self, round: usize) -> &mut HashMap<NodeId, PreVoteAction> {
+        self.round_pre_votes.entry(round).or_default()
     }
 
     /// returns the main votes for the given `round`.
-    /// If there is not votes for the `round`, it expand the `round_main_votes`.
+    /// If there is not votes for the `round`, it adds an empty set of votes.
     fn get_mut_main_votes_by_round(
         &mut self,
         round: usize,
-    ) -> Result<&mut HashMap<NodeId, MainVoteAction>> {
-        // make sure we have the round messages
-        while self.round_main_votes.len() < round {
-            self.round_main_votes.push(HashMap::new());
-        }
+    ) -> &mut HashMap<NodeId, MainVoteAction> {
+        self.round_main_votes.entry(round).or_default()
+    }
 
-        // rounds start from 1 based on spec
-        match self.round_main_votes.get_mut(round - 1) {
-            Some(v) => Ok(v),
-            None => Err(Error::Generic(format!(
-                "round_main_votes is not initialized for round {round}"
-            ))),
-        }
+    /// returns the coin shares for the given `round`.
+    /// If there is no shares for the `round`, it adds an empty set of shares.
+    fn get_mut_coin_shares_by_round(
+        &mut self,
+        round: usize,
+    ) -> &mut HashMap<NodeId, SignatureShare> {
+        self.round_coin_shares.entry(round).or_default()
+    }
+
+    /// drops the messages of the rounds before `r - 1`.
+    /// Moving to round `r` only needs the main-votes and the coin of round `r - 1`.
+    fn prune_rounds(&mut self) {
+        let oldest = self.r - 1;
+        self.round_pre_votes.retain(|round, _| *round >= oldest);
+        self.round_main_votes.retain(|round, _| *round >= oldest);
+        self.round_coin_shares.retain(|round, _| *round >= oldest);
+        self.verified_sigs.clear();
     }
 }
 
@@ -29,7 +29,7 @@ impl Net {
         let public_key_set = secret_key_set.public_keys();
         let tag = Tag::new(Domain::new("test-domain", 0), proposer);
 
-        let nodes = BTreeMap::from_iter((1..=n).map(|node_id| {
+        let nodes = BTreeMap::from_iter((1..=n as NodeId).map(|node_id| {
             let key_share = secret_key_set.secret_key_share(node_id);
             let broadcaster = Broadcaster::new(node_id);
 
@@ -2,14 +2,15 @@ use blsttc::{SecretKey, SecretKeySet, Signature};
 use rand::thread_rng;
 
 use super::{
-    error::Error,
+    error::{Error, Justification},
     message::{
-        Action, DecisionAction, MainVoteAction, MainVoteJustification, MainVoteValue, Message,
-        PreVoteAction, PreVoteJustification,
+        Action, CoinShareAction, DecisionAction, MainVoteAction, MainVoteJustification,
+        MainVoteValue, Message, PreVoteAction, PreVoteJustification,
     },
-    Abba,
+    Abba, ROUND_WINDOW,
 };
 
+use crate::mvba::fault::{Fault, FaultError};
 use crate::mvba::tag::{Domain, Tag};
 use crate::mvba::{broadcaster::Broadcaster, NodeId};
 use crate::mvba::{bundle, hash::Hash32};
@@ -100,6 +101,29 @@ impl TestNet {
         }
     }
 
+    pub fn make_coin_share_msg(&self, round: usize, peer_id: &NodeId) -> Message {
+        let sign_bytes = self.abba.coin_bytes_to_sign(round).unwrap();
+        let sig_share = self.sec_key_set.secret_key_share(peer_id).sign(sign_bytes);
+        Message {
+            tag: self.abba.tag.clone(),
+            action: Action::CoinShare(CoinShareAction { round, sig_share }),
+        }
+    }
+
+    // the coin for `round`, as the parties would combine it from their shares
+    pub fn coin(&self, round: usize) -> Signature {
+        let sign_bytes = self.abba.coin_bytes_to_sign(round).unwrap();
+        self.sec_key_set.secret_key().sign(sign_bytes)
+    }
+
+    pub fn abstain_sig(&self, round: usize) -> Signature {
+        let sign_bytes = self
+            .abba
+            .main_vote_bytes_to_sign(round, &MainVoteValue::Abstain)
+            .unwrap();
+        self.sec_key_set.secret_key().sign(sign_bytes)
+    }
+
     pub fn is_broadcasted(&self, msg: &Message) -> bool {
         self.broadcaster
             .has_gossip_message(&bundle::Message::Abba(msg.clone()))
@@ -189,9 +213,12 @@ fn test_ignore_messages_with_wrong_domain() {
         .abba
         .receive_message(TestNet::PARTY_B, pre_vote_x, &mut t.broadcaster);
     match result {
-        Err(Error::InvalidMessage(msg)) => assert_eq!(
-            msg,
-            format!("invalid tag. expected: test-domain[0].{j}, got another-domain[0].{j}"),
+        Err(Error::WrongTag { expected, got }) => assert_eq!(
+            (expected.to_string(), got.to_string()),
+            (
+                format!("test-domain[0].{j}"),
+                format!("another-domain[0].{j}")
+            ),
         ),
         other => panic!("Expected invalid message, got: {other:?}"),
     }
@@ -211,10 +238,10 @@ fn test_ignore_messages_with_wrong_proposer() {
         .abba
         .receive_message(TestNet::PARTY_B, pre_vote_x, &mut t.broadcaster);
     match result {
-        Err(Error::InvalidMessage(msg)) => assert_eq!(
-            msg,
-            "invalid tag. expected: test-domain[0].0, got test-domain[0].2"
-        ),
+        Err(Error::WrongTag { expected, got }) => {
+            assert_eq!(expected.proposer, TestNet::PARTY_X);
+            assert_eq!(got.proposer, TestNet::PARTY_B);
+        }
         res => panic!("Should not have accepted the message: {res:?}"),
     }
 }
@@ -238,8 +265,12 @@ fn test_absent_main_vote_round_one_invalid_justification() {
     let result = t
         .abba
         .receive_message(TestNet::PARTY_B, main_vote_b, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == "invalid signature for the VCBC proposal"));
+    assert!(matches!(
+        result,
+        Err(Error::InvalidJustification(
+            Justification::ValiditySignature
+        ))
+    ));
 }
 
 #[test]
@@ -266,8 +297,8 @@ fn test_pre_vote_invalid_sig_share() {
     let result = t
         .abba
         .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == "invalid signature share"));
+    assert!(matches!(result, Err(Error::InvalidSignatureShare { from })
+        if from == TestNet::PARTY_B));
 }
 
 #[test]
@@ -294,9 +325,70 @@ fn test_double_pre_vote() {
     let result = t
         .abba
         .receive_message(TestNet::PARTY_B, pre_vote_2, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == format!(
-            "double pre-vote detected from {:?}", &TestNet::PARTY_B)));
+    assert!(
+        matches!(result, Err(Error::DoubleVote { from, action: "pre-vote" })
+        if from == TestNet::PARTY_B)
+    );
+
+    let fault = t.abba.faults().get(&TestNet::PARTY_B).unwrap();
+    assert!(matches!(fault, Fault::ChangedPreVote { round: 1, .. }));
+    assert!(fault.validate(&t.sec_key_set.public_keys()).is_ok());
+}
+
+#[test]
+fn test_double_main_vote() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    let sign_bytes = t.abba.pre_vote_bytes_to_sign(1, false).unwrap();
+    let just_0 = MainVoteJustification::NoAbstain(t.sec_key_set.secret_key().sign(sign_bytes));
+    let main_vote_1 = t.make_main_vote_msg(1, MainVoteValue::zero(), &just_0, &TestNet::PARTY_B);
+
+    let abstain_just = MainVoteJustification::Abstain(
+        Box::new(PreVoteJustification::FirstRoundZero),
+        Box::new(PreVoteJustification::WithValidity(
+            t.proposal_digest,
+            t.proposal_sig.clone(),
+        )),
+    );
+    let main_vote_2 =
+        t.make_main_vote_msg(1, MainVoteValue::Abstain, &abstain_just, &TestNet::PARTY_B);
+
+    t.abba
+        .receive_message(TestNet::PARTY_B, main_vote_1, &mut t.broadcaster)
+        .unwrap();
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, main_vote_2, &mut t.broadcaster);
+    assert!(
+        matches!(result, Err(Error::DoubleVote { from, action: "main-vote" })
+        if from == TestNet::PARTY_B)
+    );
+
+    let fault = t.abba.faults().get(&TestNet::PARTY_B).unwrap();
+    assert!(matches!(fault, Fault::ChangedMainVote { round: 1, .. }));
+    assert!(fault.validate(&t.sec_key_set.public_keys()).is_ok());
+
+    // a fault must accuse the party that signed the messages
+    let mut wrong_voter = fault.clone();
+    if let Fault::ChangedMainVote { voter, .. } = &mut wrong_voter {
+        *voter = TestNet::PARTY_Y;
+    }
+    assert!(matches!(
+        wrong_voter.validate(&t.sec_key_set.public_keys()),
+        Err(FaultError::AccusedAnImproperlySignedMessage)
+    ));
+
+    // and the messages must conflict
+    let mut not_changed = fault.clone();
+    if let Fault::ChangedMainVote { a, b, .. } = &mut not_changed {
+        *b = a.clone();
+    }
+    assert!(matches!(
+        not_changed.validate(&t.sec_key_set.public_keys()),
+        Err(FaultError::NotActuallyChanged)
+    ));
 }
 
 #[test]
@@ -315,8 +407,12 @@ fn test_pre_vote_round_1_invalid_c_final_signature() {
     let result = t
         .abba
         .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-    if msg == *"invalid signature for the VCBC proposal"));
+    assert!(matches!(
+        result,
+        Err(Error::InvalidJustification(
+            Justification::ValiditySignature
+        ))
+    ));
 }
 
 #[test]
@@ -331,8 +427,10 @@ fn test_pre_vote_round_1_invalid_value_one() {
     let result = t
         .abba
         .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-    if msg == "initial value should be one"));
+    assert!(matches!(
+        result,
+        Err(Error::InvalidJustification(Justification::ValidityForZero))
+    ));
 }
 
 #[test]
@@ -347,8 +445,12 @@ fn test_pre_vote_round_1_invalid_value_zero() {
     let result = t
         .abba
         .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-    if msg == "initial value should be zero"));
+    assert!(matches!(
+        result,
+        Err(Error::InvalidJustification(
+            Justification::FirstRoundZeroForOne
+        ))
+    ));
 }
 
 #[test]
@@ -363,8 +465,7 @@ fn test_invalid_decision() {
     let result = t
         .abba
         .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-    if msg == "invalid signature"));
+    assert!(matches!(result, Err(Error::InvalidSignature)));
 }
 
 #[test]
@@ -622,7 +723,7 @@ fn test_two_rounds() {
         .unwrap();
 
     let round_1_main_vote_just = MainVoteJustification::Abstain(
-        Box::new(round_1_just_0),
+        Box::new(round_1_just_0.clone()),
         Box::new(weak_validity_just.clone()),
     );
     let round_1_main_vote_x = t.make_main_vote_msg(
@@ -767,7 +868,7 @@ fn test_three_rounds() {
         1,
         MainVoteValue::Abstain,
         &MainVoteJustification::Abstain(
-            Box::new(round_1_just_0),
+            Box::new(round_1_just_0.clone()),
             Box::new(weak_validity_just.clone()),
         ),
         &TestNet::PARTY_X,
@@ -891,3 +992,384 @@ fn test_three_rounds() {
 
     assert!(t.abba.decided_value.unwrap().value);
 }
+
+// All main-votes of round 2 abstain, so the pre-vote of round 3 is set by the common coin.
+//
+// PARTY_X:
+// PreVoteAction  { round: 1, value: Zero,       justification: FirstRoundZero }
+// MainVoteAction { round: 1, value: Value(Zero),justification: NoAbstain(...) }
+// PreVoteAction  { round: 2, value: Zero,       justification: Hard(...) }
+// MainVoteAction { round: 2, value: Abstain,    justification: Abstain(Hard(...), Hard(...)) }
+// CoinShareAction{ round: 2 }
+// PreVoteAction  { round: 3, value: coin,       justification: Soft(..., coin) }
+//
+// PARTY_Y and PARTY_S main-vote abstain in rounds 1 and 2.
+#[test]
+fn test_all_abstain_pre_votes_the_coin() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    // -- round 1
+    t.abba.pre_vote_zero(&mut t.broadcaster).unwrap();
+
+    let round_1_just_0 = PreVoteJustification::FirstRoundZero;
+    for peer in [TestNet::PARTY_Y, TestNet::PARTY_S] {
+        let pre_vote = t.make_pre_vote_msg(1, false, &round_1_just_0, &peer);
+        t.abba
+            .receive_message(peer, pre_vote, &mut t.broadcaster)
+            .unwrap();
+    }
+
+    let weak_validity_just =
+        PreVoteJustification::WithValidity(t.proposal_digest, t.proposal_sig.clone());
+    let round_1_main_vote_just = MainVoteJustification::Abstain(
+        Box::new(round_1_just_0.clone()),
+        Box::new(weak_validity_just),
+    );
+    for peer in [TestNet::PARTY_Y, TestNet::PARTY_S] {
+        let main_vote =
+            t.make_main_vote_msg(1, MainVoteValue::Abstain, &round_1_main_vote_just, &peer);
+        t.abba
+            .receive_message(peer, main_vote, &mut t.broadcaster)
+            .unwrap();
+    }
+
+    // -- round 2
+    let sign_bytes = t.abba.pre_vote_bytes_to_sign(1, false).unwrap();
+    let hard_0 = PreVoteJustification::Hard(t.sec_key_set.secret_key().sign(sign_bytes));
+    let sign_bytes = t.abba.pre_vote_bytes_to_sign(1, true).unwrap();
+    let hard_1 = PreVoteJustification::Hard(t.sec_key_set.secret_key().sign(sign_bytes));
+
+    let round_2_pre_vote_x = t.make_pre_vote_msg(2, false, &hard_0, &TestNet::PARTY_X);
+    assert!(t.is_broadcasted(&round_2_pre_vote_x));
+
+    let round_2_pre_vote_y = t.make_pre_vote_msg(2, true, &hard_1, &TestNet::PARTY_Y);
+    let round_2_pre_vote_s = t.make_pre_vote_msg(2, false, &hard_0, &TestNet::PARTY_S);
+    t.abba
+        .receive_message(TestNet::PARTY_Y, round_2_pre_vote_y, &mut t.broadcaster)
+        .unwrap();
+    t.abba
+        .receive_message(TestNet::PARTY_S, round_2_pre_vote_s, &mut t.broadcaster)
+        .unwrap();
+
+    let round_2_main_vote_just = MainVoteJustification::Abstain(Box::new(hard_0), Box::new(hard_1));
+    let round_2_main_vote_x = t.make_main_vote_msg(
+        2,
+        MainVoteValue::Abstain,
+        &round_2_main_vote_just,
+        &TestNet::PARTY_X,
+    );
+    assert!(t.is_broadcasted(&round_2_main_vote_x));
+    assert!(t.is_broadcasted(&t.make_coin_share_msg(2, &TestNet::PARTY_X)));
+
+    for peer in [TestNet::PARTY_Y, TestNet::PARTY_S] {
+        let main_vote =
+            t.make_main_vote_msg(2, MainVoteValue::Abstain, &round_2_main_vote_just, &peer);
+        t.abba
+            .receive_message(peer, main_vote, &mut t.broadcaster)
+            .unwrap();
+    }
+
+    // -- round 3
+    // all main-votes are abstain, we can't pre-vote before the coin is revealed
+    assert!(t.abba.get_pre_votes_by_round(3).is_none());
+
+    let coin_share_y = t.make_coin_share_msg(2, &TestNet::PARTY_Y);
+    t.abba
+        .receive_message(TestNet::PARTY_Y, coin_share_y, &mut t.broadcaster)
+        .unwrap();
+    assert!(t.abba.get_pre_votes_by_round(3).is_none());
+
+    let coin_share_s = t.make_coin_share_msg(2, &TestNet::PARTY_S);
+    t.abba
+        .receive_message(TestNet::PARTY_S, coin_share_s, &mut t.broadcaster)
+        .unwrap();
+
+    let coin = t.coin(2);
+    let round_3_pre_vote_x = t.make_pre_vote_msg(
+        3,
+        coin.parity(),
+        &PreVoteJustification::Soft(t.abstain_sig(2), Some(coin)),
+        &TestNet::PARTY_X,
+    );
+    assert!(t.is_broadcasted(&round_3_pre_vote_x));
+
+    // the messages of round 1 are not needed anymore
+    assert!(t.abba.get_pre_votes_by_round(1).is_none());
+    assert!(t.abba.get_main_votes_by_round(1).is_none());
+    assert!(t.abba.get_main_votes_by_round(2).is_some());
+
+    let old_pre_vote = t.make_pre_vote_msg(1, false, &round_1_just_0, &TestNet::PARTY_B);
+    t.abba
+        .receive_message(TestNet::PARTY_B, old_pre_vote, &mut t.broadcaster)
+        .unwrap();
+    assert!(t.abba.get_pre_votes_by_round(1).is_none());
+}
+
+#[test]
+fn test_soft_pre_vote_must_follow_the_coin() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    let coin = t.coin(2);
+    let just = PreVoteJustification::Soft(t.abstain_sig(2), Some(coin.clone()));
+    let msg = t.make_pre_vote_msg(3, !coin.parity(), &just, &TestNet::PARTY_B);
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
+    assert!(matches!(
+        result,
+        Err(Error::InvalidJustification(Justification::SoftVoteNotCoin))
+    ));
+
+    // a coin for another round
+    let just = PreVoteJustification::Soft(t.abstain_sig(2), Some(t.coin(3)));
+    let msg = t.make_pre_vote_msg(3, t.coin(3).parity(), &just, &TestNet::PARTY_B);
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
+    assert!(matches!(
+        result,
+        Err(Error::InvalidJustification(Justification::CoinSignature))
+    ));
+
+    // the coin of the first round is fixed to one
+    let just = PreVoteJustification::Soft(t.abstain_sig(1), None);
+    let msg = t.make_pre_vote_msg(2, false, &just, &TestNet::PARTY_B);
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
+    assert!(matches!(
+        result,
+        Err(Error::InvalidJustification(Justification::SoftVoteNotCoin))
+    ));
+
+    let just = PreVoteJustification::Soft(t.abstain_sig(2), Some(coin.clone()));
+    let msg = t.make_pre_vote_msg(3, coin.parity(), &just, &TestNet::PARTY_B);
+    assert!(t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster)
+        .is_ok());
+}
+
+#[test]
+fn test_invalid_coin_share() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    let mut msg = t.make_coin_share_msg(2, &TestNet::PARTY_Y);
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg.clone(), &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::InvalidSignatureShare { from })
+        if from == TestNet::PARTY_B));
+
+    msg.action = Action::CoinShare(CoinShareAction {
+        round: 3,
+        sig_share: t
+            .sec_key_set
+            .secret_key_share(TestNet::PARTY_Y)
+            .sign(t.abba.coin_bytes_to_sign(2).unwrap()),
+    });
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_Y, msg, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::InvalidSignatureShare { from })
+        if from == TestNet::PARTY_Y));
+}
+
+#[test]
+fn test_reject_round_zero() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    let msg = t.make_pre_vote_msg(
+        0,
+        false,
+        &PreVoteJustification::FirstRoundZero,
+        &TestNet::PARTY_B,
+    );
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::InvalidRound(0))));
+}
+
+#[test]
+fn test_reject_far_future_round() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    let round = 1_000_000_000;
+    let sign_bytes = t.abba.pre_vote_bytes_to_sign(round - 1, true).unwrap();
+    let just = PreVoteJustification::Hard(t.sec_key_set.secret_key().sign(sign_bytes));
+    let msg = t.make_pre_vote_msg(round, true, &just, &TestNet::PARTY_B);
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::FutureRound { round: r, current: 1 }) if r == round));
+    assert!(t.abba.round_pre_votes.is_empty());
+
+    let main_vote = t.make_main_vote_msg(
+        round,
+        MainVoteValue::Abstain,
+        &MainVoteJustification::Abstain(Box::new(just.clone()), Box::new(just)),
+        &TestNet::PARTY_B,
+    );
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, main_vote, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::FutureRound { .. })));
+    assert!(t.abba.round_main_votes.is_empty());
+
+    let coin_share = t.make_coin_share_msg(round, &TestNet::PARTY_B);
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, coin_share, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::FutureRound { .. })));
+    assert!(t.abba.round_coin_shares.is_empty());
+}
+
+#[test]
+fn test_buffer_near_future_round() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    let last_round = 1 + ROUND_WINDOW;
+    for round in [2, last_round] {
+        let sign_bytes = t.abba.pre_vote_bytes_to_sign(round - 1, true).unwrap();
+        let just = PreVoteJustification::Hard(t.sec_key_set.secret_key().sign(sign_bytes));
+        let msg = t.make_pre_vote_msg(round, true, &just, &TestNet::PARTY_B);
+        t.abba
+            .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster)
+            .unwrap();
+        assert!(t
+            .abba
+            .get_pre_votes_by_round(round)
+            .unwrap()
+            .contains_key(&TestNet::PARTY_B));
+    }
+
+    let round = last_round + 1;
+    let sign_bytes = t.abba.pre_vote_bytes_to_sign(round - 1, true).unwrap();
+    let just = PreVoteJustification::Hard(t.sec_key_set.secret_key().sign(sign_bytes));
+    let msg = t.make_pre_vote_msg(round, true, &just, &TestNet::PARTY_B);
+    let result = t
+        .abba
+        .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::FutureRound { round: r, current: 1 }) if r == round));
+}
+
+#[test]
+fn test_batch_verification_drops_invalid_pre_vote() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+    t.abba.set_batch_verification(true);
+
+    t.abba
+        .pre_vote_one(
+            t.proposal_digest,
+            t.proposal_sig.clone(),
+            &mut t.broadcaster,
+        )
+        .unwrap();
+
+    let just = PreVoteJustification::WithValidity(t.proposal_digest, t.proposal_sig.clone());
+    let invalid_pre_vote_b = Message {
+        tag: t.abba.tag.clone(),
+        action: Action::PreVote(PreVoteAction {
+            round: 1,
+            justification: just.clone(),
+            value: true,
+            sig_share: t
+                .sec_key_set
+                .secret_key_share(TestNet::PARTY_B)
+                .sign("invalid-msg"),
+        }),
+    };
+    let pre_vote_y = t.make_pre_vote_msg(1, true, &just, &TestNet::PARTY_Y);
+    let pre_vote_s = t.make_pre_vote_msg(1, true, &just, &TestNet::PARTY_S);
+
+    // the invalid share is only found once the shares are combined
+    t.abba
+        .receive_message(TestNet::PARTY_B, invalid_pre_vote_b, &mut t.broadcaster)
+        .unwrap();
+    t.abba
+        .receive_message(TestNet::PARTY_Y, pre_vote_y, &mut t.broadcaster)
+        .unwrap();
+    assert!(!t
+        .abba
+        .get_pre_votes_by_round(1)
+        .unwrap()
+        .contains_key(&TestNet::PARTY_B));
+    assert_eq!(t.abba.r, 1);
+
+    t.abba
+        .receive_message(TestNet::PARTY_S, pre_vote_s, &mut t.broadcaster)
+        .unwrap();
+
+    let sign_bytes = t.abba.pre_vote_bytes_to_sign(1, true).unwrap();
+    let sig = t.sec_key_set.secret_key().sign(sign_bytes);
+    let main_vote_just = MainVoteJustification::NoAbstain(sig);
+    let main_vote_x =
+        t.make_main_vote_msg(1, MainVoteValue::one(), &main_vote_just, &TestNet::PARTY_X);
+    assert!(t.is_broadcasted(&main_vote_x));
+}
+
+#[test]
+fn test_batch_verification_invalid_pre_vote_before_valid_one() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+    t.abba.set_batch_verification(true);
+
+    t.abba
+        .pre_vote_one(
+            t.proposal_digest,
+            t.proposal_sig.clone(),
+            &mut t.broadcaster,
+        )
+        .unwrap();
+
+    let just = PreVoteJustification::WithValidity(t.proposal_digest, t.proposal_sig.clone());
+    let invalid_pre_vote_b = Message {
+        tag: t.abba.tag.clone(),
+        action: Action::PreVote(PreVoteAction {
+            round: 1,
+            justification: just.clone(),
+            value: true,
+            sig_share: t
+                .sec_key_set
+                .secret_key_share(TestNet::PARTY_B)
+                .sign("invalid-msg"),
+        }),
+    };
+    let pre_vote_b = t.make_pre_vote_msg(1, true, &just, &TestNet::PARTY_B);
+    let pre_vote_y = t.make_pre_vote_msg(1, true, &just, &TestNet::PARTY_Y);
+
+    // the valid pre-vote of B takes the place of its invalid one
+    t.abba
+        .receive_message(TestNet::PARTY_B, invalid_pre_vote_b, &mut t.broadcaster)
+        .unwrap();
+    t.abba
+        .receive_message(TestNet::PARTY_B, pre_vote_b, &mut t.broadcaster)
+        .unwrap();
+    t.abba
+        .receive_message(TestNet::PARTY_Y, pre_vote_y, &mut t.broadcaster)
+        .unwrap();
+    assert!(t.abba.faults.is_empty());
+
+    let sign_bytes = t.abba.pre_vote_bytes_to_sign(1, true).unwrap();
+    let sig = t.sec_key_set.secret_key().sign(sign_bytes);
+    let main_vote_just = MainVoteJustification::NoAbstain(sig);
+    let main_vote_x =
+        t.make_main_vote_msg(1, MainVoteValue::one(), &main_vote_just, &TestNet::PARTY_X);
+    assert!(t.is_broadcasted(&main_vote_x));
+}
@@ -0,0 +1,545 @@
+//! Atomic broadcast on top of consecutive MVBA instances.
+//!
+//! The instances run one after the other on the domains `id[0]`, `id[1]`, ...
+//! Each of them decides a batch of the transactions submitted by the parties,
+//! and the decided batches form a totally ordered log. Every entry of the log
+//! carries the [`Proof`](super::Proof) of its instance, so it can be checked
+//! with the public key of the parties alone.
+
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+
+use blsttc::{PublicKeySet, SecretKeyShare};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    bundle::{Bundle, Outgoing},
+    consensus::{Consensus, Dissemination},
+    error::{Error, Result, Severity},
+    tag::Domain,
+    Decision, MessageValidity, NodeId,
+};
+
+const DEFAULT_BATCH_SIZE: usize = 64;
+const DEFAULT_FUTURE_WINDOW: usize = 8;
+const DEFAULT_MAX_FUTURE_BUNDLES: usize = 1024;
+const DEFAULT_DECIDED_GRACE: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct AtomicBroadcastConfig {
+    /// The most transactions we put in one proposal.
+    pub batch_size: usize,
+    /// How many instances past the current one we buffer bundles for.
+    /// Bundles further ahead are dropped.
+    pub future_window: usize,
+    /// The most bundles we keep for the instances after the current one.
+    pub max_future_bundles: usize,
+    /// How many decided instances we keep, so that they still answer the
+    /// parties that are late.
+    pub decided_grace: usize,
+    /// See [`Consensus::set_batch_verification`].
+    pub batch_verification: bool,
+    /// See [`Consensus::set_dissemination`].
+    pub dissemination: Dissemination,
+}
+
+impl Default for AtomicBroadcastConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            future_window: DEFAULT_FUTURE_WINDOW,
+            max_future_bundles: DEFAULT_MAX_FUTURE_BUNDLES,
+            decided_grace: DEFAULT_DECIDED_GRACE,
+            batch_verification: false,
+            dissemination: Dissemination::Full,
+        }
+    }
+}
+
+pub struct AtomicBroadcast<T: Debug + Clone + Serialize + Eq> {
+    id: String,
+    self_id: NodeId,
+    sec_key_share: SecretKeyShare,
+    pub_key_set: PublicKeySet,
+    parties: Vec<NodeId>,
+    message_validity: Arc<dyn MessageValidity<Vec<T>>>,
+    config: AtomicBroadcastConfig,
+    // the instance deciding the next entry of the log.
+    consensus: Consensus<Vec<T>>,
+    proposed: bool,
+    // the submitted transactions that are not delivered yet.
+    pending: Vec<T>,
+    // the last decided instances, keyed by their sequence number.
+    decided: BTreeMap<usize, Consensus<Vec<T>>>,
+    // the bundles received for the instances after the current one.
+    future: BTreeMap<usize, Vec<Bundle<Vec<T>>>>,
+    log: BTreeMap<usize, Decision<Vec<T>>>,
+}
+
+impl<T: Debug + Clone + Serialize + DeserializeOwned + Eq> AtomicBroadcast<T> {
+    pub fn init(
+        id: impl Into<String>,
+        self_id: NodeId,
+        sec_key_share: SecretKeyShare,
+        pub_key_set: PublicKeySet,
+        parties: Vec<NodeId>,
+        message_validity: impl MessageValidity<Vec<T>> + 'static,
+        config: AtomicBroadcastConfig,
+    ) -> Self {
+        let id = id.into();
+        let message_validity: Arc<dyn MessageValidity<Vec<T>>> = Arc::new(message_validity);
+        let mut consensus = Consensus::init_shared(
+            Domain::new(id.clone(), 0),
+            self_id,
+            sec_key_share.clone(),
+            pub_key_set.clone(),
+            parties.clone(),
+            message_validity.clone(),
+        );
+        consensus.set_batch_verification(config.batch_verification);
+        consensus.set_dissemination(config.dissemination);
+
+        Self {
+            id,
+            self_id,
+            sec_key_share,
+            pub_key_set,
+            parties,
+            message_validity,
+            config,
+            consensus,
+            proposed: false,
+            pending: Vec::new(),
+            decided: BTreeMap::new(),
+            future: BTreeMap::new(),
+            log: BTreeMap::new(),
+        }
+    }
+
+    pub fn self_id(&self) -> NodeId {
+        self.self_id
+    }
+
+    pub fn pub_key_set(&self) -> PublicKeySet {
+        self.pub_key_set.clone()
+    }
+
+    /// The sequence number of the instance that decides the next entry.
+    pub fn seq(&self) -> usize {
+        self.consensus.domain().seq
+    }
+
+    /// The submitted transactions that are not delivered yet.
+    pub fn pending(&self) -> &[T] {
+        &self.pending
+    }
+
+    /// The decided entries of the log that are not pruned, in order.
+    pub fn log(&self) -> impl Iterator<Item = &Decision<Vec<T>>> {
+        self.log.values()
+    }
+
+    pub fn entry(&self, seq: usize) -> Option<&Decision<Vec<T>>> {
+        self.log.get(&seq)
+    }
+
+    /// Forgets the entries of the log before `seq`.
+    pub fn prune(&mut self, seq: usize) {
+        self.log = self.log.split_off(&seq);
+    }
+
+    /// Queues a transaction to be ordered, it is proposed right away if we
+    /// have not proposed in the current instance yet.
+    pub fn submit(&mut self, tx: T) -> Result<Vec<Outgoing<Vec<T>>>> {
+        if !self.pending.contains(&tx) {
+            self.pending.push(tx);
+        }
+        if self.proposed {
+            return Ok(vec![]);
+        }
+        self.propose_batch()
+    }
+
+    pub fn process_bundle(&mut self, bundle: &Bundle<Vec<T>>) -> Result<Vec<Outgoing<Vec<T>>>> {
+        let seq = self.check_domain(bundle.domain())?;
+        if seq < self.seq() {
+            // the decided instances still answer the requests for their
+            // proposal and collect the coin shares
+            if let Some(consensus) = self.decided.get_mut(&seq) {
+                return consensus.process_bundle(bundle);
+            }
+            log::debug!(
+                "party {} dropped a bundle of the finished instance {}",
+                self.self_id,
+                bundle.domain()
+            );
+            return Ok(vec![]);
+        }
+        if seq > self.seq() {
+            let future_bundles: usize = self.future.values().map(Vec::len).sum();
+            if future_bundles >= self.config.max_future_bundles {
+                return Err(Error::OutOfWindow(bundle.domain().clone()));
+            }
+            self.future.entry(seq).or_default().push(bundle.clone());
+            return Ok(vec![]);
+        }
+
+        let mut outgoings = Vec::new();
+        if !self.proposed {
+            // the others have started this instance, we join it even if we
+            // have nothing to propose.
+            outgoings.extend(self.propose_batch()?);
+        }
+        outgoings.extend(self.consensus.process_bundle(bundle)?);
+        outgoings.extend(self.advance()?);
+        Ok(outgoings)
+    }
+
+    /// Checks the proposals of the current instance that the validity
+    /// predicate could not judge before.
+    pub fn revalidate_pending(&mut self) -> Result<Vec<Outgoing<Vec<T>>>> {
+        let mut outgoings = self.consensus.revalidate_pending()?;
+        outgoings.extend(self.advance()?);
+        Ok(outgoings)
+    }
+
+    /// Appends a decision received from another party, to catch up with the
+    /// instances we missed.
+    ///
+    /// Decisions are only accepted in order, the ones for other instances than
+    /// the current one are ignored.
+    pub fn process_decision(
+        &mut self,
+        decision: Decision<Vec<T>>,
+    ) -> Result<Vec<Outgoing<Vec<T>>>> {
+        if self.check_domain(&decision.domain)? != self.seq() {
+            return Ok(vec![]);
+        }
+        if !decision.validate(&self.pub_key_set.public_key())? {
+            return Err(Error::InvalidDecision(decision.domain));
+        }
+
+        self.append(decision);
+        let mut outgoings = self.start_next()?;
+        outgoings.extend(self.advance()?);
+        Ok(outgoings)
+    }
+
+    // check_domain returns the sequence number of the `domain`, if it is ours
+    // and not too far ahead.
+    fn check_domain(&self, domain: &Domain) -> Result<usize> {
+        if domain.id != self.id || domain.seq > self.seq() + self.config.future_window {
+            return Err(Error::WrongDomain {
+                expected: self.consensus.domain().clone(),
+                got: domain.clone(),
+            });
+        }
+        Ok(domain.seq)
+    }
+
+    fn propose_batch(&mut self) -> Result<Vec<Outgoing<Vec<T>>>> {
+        let batch = Vec::from_iter(self.pending.iter().take(self.config.batch_size).cloned());
+        self.proposed = true;
+        self.consensus.propose(batch)
+    }
+
+    // advance moves to the next instances as long as the current one is decided.
+    fn advance(&mut self) -> Result<Vec<Outgoing<Vec<T>>>> {
+        let mut outgoings = Vec::new();
+        while let Some(decision) = self.consensus.decided_proposal() {
+            self.append(decision);
+            outgoings.extend(self.start_next()?);
+        }
+        Ok(outgoings)
+    }
+
+    fn append(&mut self, decision: Decision<Vec<T>>) {
+        let seq = decision.domain.seq;
+        log::info!(
+            "party {} delivered {} transactions at {}",
+            self.self_id,
+            decision.proposal.len(),
+            decision.domain
+        );
+        self.pending.retain(|tx| !decision.proposal.contains(tx));
+        self.log.insert(seq, decision);
+
+        // the finished instance is kept for a while if it decided, the
+        // bundles buffered for it are dropped
+        let next = Consensus::init_shared(
+            Domain::new(self.id.clone(), seq + 1),
+            self.self_id,
+            self.sec_key_share.clone(),
+            self.pub_key_set.clone(),
+            self.parties.clone(),
+            self.message_validity.clone(),
+        );
+        let finished = std::mem::replace(&mut self.consensus, next);
+        if finished.decided_proposal().is_some() {
+            self.decided.insert(seq, finished);
+        }
+        self.decided = self
+            .decided
+            .split_off(&(seq + 1).saturating_sub(self.config.decided_grace));
+        self.consensus
+            .set_batch_verification(self.config.batch_verification);
+        self.consensus.set_dissemination(self.config.dissemination);
+        self.proposed = false;
+        self.future = self.future.split_off(&(seq + 1));
+    }
+
+    // start_next proposes in the new instance if there is anything to order,
+    // and replays the bundles we buffered for it.
+    fn start_next(&mut self) -> Result<Vec<Outgoing<Vec<T>>>> {
+        let mut outgoings = Vec::new();
+        let bundles = self.future.remove(&self.seq()).unwrap_or_default();
+        if !self.pending.is_empty() || !bundles.is_empty() {
+            outgoings.extend(self.propose_batch()?);
+        }
+        for bundle in bundles {
+            match self.consensus.process_bundle(&bundle) {
+                Ok(bundle_outgoings) => outgoings.extend(bundle_outgoings),
+                Err(err) if err.severity() == Severity::Fatal => return Err(err),
+                Err(err) => log::debug!(
+                    "party {} dropped a buffered bundle from {}: {err}",
+                    self.self_id,
+                    bundle.initiator
+                ),
+            }
+        }
+        Ok(outgoings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blsttc::SecretKeySet;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{AtomicBroadcast, AtomicBroadcastConfig};
+    use crate::mvba::{
+        bundle::{Message, Outgoing},
+        consensus::Dissemination,
+        error::{Error, Severity},
+        NodeId,
+    };
+
+    fn valid_batch(_id: NodeId, _: &Vec<u32>) -> bool {
+        true
+    }
+
+    struct TestNet {
+        abcs: Vec<AtomicBroadcast<u32>>,
+        buffer: Vec<Outgoing<Vec<u32>>>,
+        sks: SecretKeySet,
+    }
+
+    impl TestNet {
+        fn new(rng: &mut StdRng, config: AtomicBroadcastConfig) -> Self {
+            let sks = SecretKeySet::random(2, rng);
+            let parties = Vec::from_iter(0..4);
+            let abcs = Vec::from_iter(parties.iter().map(|p| {
+                AtomicBroadcast::init(
+                    "test-log",
+                    *p,
+                    sks.secret_key_share(p),
+                    sks.public_keys(),
+                    parties.clone(),
+                    valid_batch,
+                    config.clone(),
+                )
+            }));
+
+            Self {
+                abcs,
+                buffer: Vec::new(),
+                sks,
+            }
+        }
+
+        // delivers the outgoings in random order, returns the ones for the
+        // parties that are not in the net.
+        fn run(&mut self, rng: &mut StdRng) -> Vec<Outgoing<Vec<u32>>> {
+            let mut missed = Vec::new();
+            while !self.buffer.is_empty() {
+                let outgoing = self.buffer.remove(rng.gen_range(0..self.buffer.len()));
+                for abc in &mut self.abcs {
+                    match &outgoing {
+                        Outgoing::Direct(id, bundle) if id == &abc.self_id() => {
+                            self.buffer.append(&mut abc.process_bundle(bundle).unwrap())
+                        }
+                        Outgoing::Direct(..) => {}
+                        Outgoing::Gossip(bundle) => {
+                            self.buffer.append(&mut abc.process_bundle(bundle).unwrap())
+                        }
+                    }
+                }
+                let delivered = match &outgoing {
+                    Outgoing::Direct(id, _) => self.abcs.iter().any(|abc| abc.self_id() == *id),
+                    Outgoing::Gossip(_) => false,
+                };
+                if !delivered {
+                    missed.push(outgoing);
+                }
+            }
+            missed
+        }
+    }
+
+    #[test]
+    fn test_transactions_are_delivered_in_the_same_order() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let config = AtomicBroadcastConfig {
+            batch_size: 2,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+
+        for tx in 0..12 {
+            let party = rng.gen_range(0..net.abcs.len());
+            net.buffer.append(&mut net.abcs[party].submit(tx).unwrap());
+        }
+        net.run(&mut rng);
+
+        let pk = net.sks.public_keys().public_key();
+        let log = Vec::from_iter(net.abcs[0].log().cloned());
+        for (seq, entry) in log.iter().enumerate() {
+            assert_eq!(entry.domain.seq, seq);
+            assert!(entry.validate(&pk).unwrap());
+            assert!(entry.proposal.len() <= 2);
+        }
+        for abc in &net.abcs {
+            assert!(abc.pending().is_empty());
+            assert_eq!(Vec::from_iter(abc.log().cloned()), log);
+            assert_eq!(abc.seq(), log.len());
+        }
+
+        let mut delivered = Vec::from_iter(log.iter().flat_map(|e| e.proposal.clone()));
+        delivered.sort();
+        assert_eq!(delivered, Vec::from_iter(0..12));
+    }
+
+    #[test]
+    fn test_catch_up_with_decisions() {
+        let mut rng = StdRng::from_seed([1u8; 32]);
+        let mut net = TestNet::new(&mut rng, AtomicBroadcastConfig::default());
+        let mut late = net.abcs.pop().unwrap();
+
+        // three parties are enough to order the transactions
+        for tx in 0..3 {
+            net.buffer.append(&mut net.abcs[0].submit(tx).unwrap());
+            net.run(&mut rng);
+        }
+        assert!(net.abcs[0].pending().is_empty());
+
+        // the instances that decided on an empty batch are in the log too
+        let log = Vec::from_iter(net.abcs[0].log().cloned());
+        assert!(log.len() >= 3);
+        // decisions that are not the next one are ignored
+        assert!(late.process_decision(log[1].clone()).unwrap().is_empty());
+        assert_eq!(late.seq(), 0);
+
+        let mut forged = log[0].clone();
+        forged.proposal = vec![42];
+        let err = late.process_decision(forged).unwrap_err();
+        assert!(matches!(err, Error::InvalidDecision(_)));
+        assert_eq!(err.severity(), Severity::Penalize);
+
+        for entry in log.iter().cloned() {
+            late.process_decision(entry).unwrap();
+        }
+        assert_eq!(late.seq(), log.len());
+        assert_eq!(Vec::from_iter(late.log().cloned()), log);
+
+        late.prune(2);
+        assert!(late.entry(1).is_none());
+        assert_eq!(late.entry(2), log.get(2));
+    }
+
+    #[test]
+    fn test_decided_instances_answer_late_parties() {
+        let mut rng = StdRng::from_seed([4u8; 32]);
+        let config = AtomicBroadcastConfig {
+            dissemination: Dissemination::Reference,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let mut late = net.abcs.pop().unwrap();
+
+        net.buffer.append(&mut net.abcs[0].submit(7).unwrap());
+        let missed = net.run(&mut rng);
+        assert!(net.abcs[0].pending().is_empty());
+
+        // the fragments are lost, the late party decides on the digest alone
+        // and has to fetch the proposal from the others
+        for outgoing in missed {
+            let bundle = match outgoing {
+                Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+            };
+            if !matches!(bundle.message, Message::Avid(_)) {
+                net.buffer
+                    .append(&mut late.process_bundle(&bundle).unwrap());
+            }
+        }
+        while !net.buffer.is_empty() {
+            for outgoing in net.run(&mut rng) {
+                let bundle = match outgoing {
+                    Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+                };
+                net.buffer
+                    .append(&mut late.process_bundle(&bundle).unwrap());
+            }
+        }
+
+        assert_eq!(late.seq(), net.abcs[0].seq());
+        assert!(late.log().eq(net.abcs[0].log()));
+    }
+
+    #[test]
+    fn test_drop_bundles_of_other_logs_and_far_instances() {
+        let mut rng = StdRng::from_seed([2u8; 32]);
+        let mut net = TestNet::new(&mut rng, AtomicBroadcastConfig::default());
+        let mut bundle = match net.abcs[0].submit(7).unwrap().remove(0) {
+            Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+        };
+
+        let mut far = bundle.clone();
+        if let Message::Vcbc(msg) = &mut far.message {
+            msg.tag.domain.seq = 9;
+        }
+        let err = net.abcs[1].process_bundle(&far).unwrap_err();
+        assert_eq!(err.severity(), Severity::Drop);
+
+        if let Message::Vcbc(msg) = &mut bundle.message {
+            msg.tag.domain.id = "another-log".to_string();
+        }
+        let err = net.abcs[1].process_bundle(&bundle).unwrap_err();
+        assert!(matches!(err, Error::WrongDomain { .. }));
+    }
+
+    #[test]
+    fn test_future_bundles_are_bounded() {
+        let mut rng = StdRng::from_seed([3u8; 32]);
+        let config = AtomicBroadcastConfig {
+            max_future_bundles: 1,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let bundle = match net.abcs[0].submit(7).unwrap().remove(0) {
+            Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+        };
+
+        let mut futures = Vec::from_iter((1..3).map(|seq| {
+            let mut future = bundle.clone();
+            if let Message::Vcbc(msg) = &mut future.message {
+                msg.tag.domain.seq = seq;
+            }
+            future
+        }));
+        assert!(net.abcs[1].process_bundle(&futures[0]).unwrap().is_empty());
+        let err = net.abcs[1]
+            .process_bundle(&futures.pop().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, Error::OutOfWindow(_)));
+        assert_eq!(err.severity(), Severity::Drop);
+    }
+}
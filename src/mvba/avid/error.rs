@@ -0,0 +1,29 @@
+use crate::mvba::error::Severity;
+use crate::mvba::{hash::Hash32, tag::Tag, NodeId};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("encoding/decoding error {0:?}")]
+    Encoding(#[from] bincode::Error),
+    #[error("erasure coding error {0}")]
+    Coding(#[from] reed_solomon_erasure::Error),
+    #[error("invalid tag. expected {expected}, got {got}")]
+    WrongTag { expected: Tag, got: Tag },
+    #[error("invalid fragment from {0}")]
+    InvalidFragment(NodeId),
+    #[error("the fragments of {0:?} don't rebuild the same proposal")]
+    InconsistentFragments(Hash32),
+}
+
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::Encoding(_) | Error::Coding(_) => Severity::Fatal,
+            Error::WrongTag { .. } => Severity::Drop,
+            Error::InvalidFragment(_) | Error::InconsistentFragments(_) => Severity::Penalize,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
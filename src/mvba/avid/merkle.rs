@@ -0,0 +1,82 @@
+use crate::mvba::hash::Hash32;
+
+// The leaves are padded to a power of two with this hash.
+const EMPTY: [u8; 32] = [0; 32];
+
+fn leaf_hash(data: &[u8]) -> Result<Hash32, bincode::Error> {
+    Hash32::calculate(("avid-leaf", data))
+}
+
+fn node_hash(left: &Hash32, right: &Hash32) -> Result<Hash32, bincode::Error> {
+    Hash32::calculate((left, right))
+}
+
+// depth is the number of levels above the leaves for `n` leaves.
+fn depth(n: usize) -> usize {
+    n.next_power_of_two().trailing_zeros() as usize
+}
+
+/// A Merkle tree over the fragments of a proposal.
+pub(crate) struct MerkleTree {
+    // the levels of the tree, from the leaves to the root
+    levels: Vec<Vec<Hash32>>,
+}
+
+impl MerkleTree {
+    pub fn new<T: AsRef<[u8]>>(leaves: &[T]) -> Result<Self, bincode::Error> {
+        let mut level = Vec::with_capacity(leaves.len().next_power_of_two());
+        for leaf in leaves {
+            level.push(leaf_hash(leaf.as_ref())?);
+        }
+        level.resize(leaves.len().next_power_of_two(), Hash32::from(EMPTY));
+
+        let mut levels = vec![level];
+        while levels[levels.len() - 1].len() > 1 {
+            let last = &levels[levels.len() - 1];
+            let mut next = Vec::with_capacity(last.len() / 2);
+            for pair in last.chunks(2) {
+                next.push(node_hash(&pair[0], &pair[1])?);
+            }
+            levels.push(next);
+        }
+        Ok(Self { levels })
+    }
+
+    pub fn root(&self) -> Hash32 {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// The sibling hashes from the leaf at `index` up to the root.
+    pub fn proof(&self, mut index: usize) -> Vec<Hash32> {
+        let mut proof = Vec::with_capacity(self.levels.len() - 1);
+        for level in &self.levels[..self.levels.len() - 1] {
+            proof.push(level[index ^ 1]);
+            index /= 2;
+        }
+        proof
+    }
+}
+
+/// Checks that `data` is the leaf at `index` of the tree of `n` leaves with this `root`.
+pub(crate) fn verify(
+    root: &Hash32,
+    n: usize,
+    index: usize,
+    data: &[u8],
+    proof: &[Hash32],
+) -> Result<bool, bincode::Error> {
+    if index >= n || proof.len() != depth(n) {
+        return Ok(false);
+    }
+    let mut hash = leaf_hash(data)?;
+    let mut index = index;
+    for sibling in proof {
+        hash = if index.is_multiple_of(2) {
+            node_hash(&hash, sibling)?
+        } else {
+            node_hash(sibling, &hash)?
+        };
+        index /= 2;
+    }
+    Ok(&hash == root)
+}
@@ -0,0 +1,32 @@
+use crate::mvba::{hash::Hash32, tag::Tag};
+
+/// The fragment of a proposal for the party at `index`, with the proof that it
+/// is part of the fragments committed by `root`.
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Fragment {
+    pub root: Hash32,
+    pub index: usize,
+    pub data: Vec<u8>,
+    pub proof: Vec<Hash32>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Action {
+    Disperse(Fragment), // the proposer sends each party its fragment
+    Echo(Fragment),     // each party sends its fragment to all parties
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Message {
+    pub tag: Tag,
+    pub action: Action,
+}
+
+impl Message {
+    pub fn action_str(&self) -> &str {
+        match self.action {
+            Action::Disperse(_) => "avid-disperse",
+            Action::Echo(_) => "avid-echo",
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Erasure-coded dissemination of the proposals, as in the AVID broadcast of
+//! Cachin and Tessaro.
+//!
+//! The proposer encodes its proposal into one fragment per party, any `t + 1`
+//! of which rebuild it, and sends each party its fragment with a Merkle proof
+//! against the root of all the fragments. Each party echoes its fragment to
+//! the others, so all the parties can rebuild the proposal while the proposer
+//! sends about `n / (t + 1)` times its size, instead of `n` times.
+//!
+//! The rebuilt proposal is certified by VCBC, as if it was c-sent.
+
+pub(crate) mod error;
+pub(crate) mod merkle;
+pub(crate) mod message;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::marker::PhantomData;
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use self::error::{Error, Result};
+use self::merkle::MerkleTree;
+use self::message::{Action, Fragment, Message};
+use super::hash::Hash32;
+use super::tag::Tag;
+use super::{bundle, NodeId};
+use crate::mvba::broadcaster::Broadcaster;
+
+// encode splits the serialized proposal into `total` fragments, any
+// `data_shards` of which rebuild it.
+// The serialized proposal is prefixed with its length and padded to fill the
+// data fragments.
+pub(crate) fn encode<P: Serialize>(
+    proposal: &P,
+    data_shards: usize,
+    total: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let bytes = bincode::serialize(proposal)?;
+    let mut data = Vec::from((bytes.len() as u64).to_le_bytes());
+    data.extend(bytes);
+
+    let shard_len = data.len().div_ceil(data_shards);
+    data.resize(shard_len * data_shards, 0);
+
+    let mut shards = Vec::from_iter(data.chunks(shard_len).map(<[u8]>::to_vec));
+    shards.resize(total, vec![0; shard_len]);
+    if total > data_shards {
+        ReedSolomon::new(data_shards, total - data_shards)?.encode(&mut shards)?;
+    }
+    Ok(shards)
+}
+
+// decode rebuilds the proposal from the `fragments` keyed by their index.
+// It returns None if the fragments don't hold a proposal.
+pub(crate) fn decode<P: DeserializeOwned>(
+    fragments: &BTreeMap<usize, Vec<u8>>,
+    data_shards: usize,
+    total: usize,
+) -> Result<Option<P>> {
+    let mut shards = vec![None; total];
+    for (index, data) in fragments {
+        shards[*index] = Some(data.clone());
+    }
+    if total > data_shards {
+        ReedSolomon::new(data_shards, total - data_shards)?.reconstruct(&mut shards)?;
+    }
+    let shards = match shards.into_iter().collect::<Option<Vec<_>>>() {
+        Some(shards) => shards,
+        None => return Ok(None),
+    };
+
+    let data = shards[..data_shards].concat();
+    let len = match data.get(..8) {
+        Some(len) => u64::from_le_bytes(len.try_into().expect("8 bytes")) as usize,
+        None => return Ok(None),
+    };
+    // the length is set by the proposer, it can be anything
+    match data.get(8..).and_then(|data| data.get(..len)) {
+        Some(bytes) => Ok(bincode::deserialize(bytes).ok()),
+        None => Ok(None),
+    }
+}
+
+// Protocol AVID for the dispersal of a proposal.
+pub(crate) struct Avid<P> {
+    tag: Tag, // Tag is a combination of Domain and proposer ID. It is unique in each AVID instances.
+    i: NodeId, // represents our unique identifier
+    parties: Vec<NodeId>, // the fragment of each party is at its index in `parties`
+    data_shards: usize, // the number of fragments that rebuild the proposal
+    echoed: bool,
+    // the fragments echoed by the parties, grouped by their root.
+    fragments: HashMap<Hash32, BTreeMap<usize, Vec<u8>>>,
+    echoes: HashSet<NodeId>,
+    // set once the proposal is rebuilt, or failed to be.
+    done: bool,
+    _proposal: PhantomData<P>,
+}
+
+/// The state of an AVID instance, as kept in a snapshot.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct State {
+    tag: Tag,
+    i: NodeId,
+    parties: Vec<NodeId>,
+    data_shards: usize,
+    echoed: bool,
+    fragments: HashMap<Hash32, BTreeMap<usize, Vec<u8>>>,
+    echoes: HashSet<NodeId>,
+    done: bool,
+}
+
+impl<P: Serialize + DeserializeOwned + Eq> Avid<P> {
+    pub fn new(tag: Tag, self_id: NodeId, mut parties: Vec<NodeId>, threshold: usize) -> Self {
+        parties.sort();
+        Self {
+            tag,
+            i: self_id,
+            parties,
+            data_shards: threshold,
+            echoed: false,
+            fragments: HashMap::new(),
+            echoes: HashSet::new(),
+            done: false,
+            _proposal: PhantomData,
+        }
+    }
+
+    /// restore rebuilds the instance from the `state` of its snapshot.
+    pub fn restore(state: State) -> Self {
+        Self {
+            tag: state.tag,
+            i: state.i,
+            parties: state.parties,
+            data_shards: state.data_shards,
+            echoed: state.echoed,
+            fragments: state.fragments,
+            echoes: state.echoes,
+            done: state.done,
+            _proposal: PhantomData,
+        }
+    }
+
+    /// snapshot returns the state of the instance.
+    pub fn snapshot(&self) -> State {
+        State {
+            tag: self.tag.clone(),
+            i: self.i,
+            parties: self.parties.clone(),
+            data_shards: self.data_shards,
+            echoed: self.echoed,
+            fragments: self.fragments.clone(),
+            echoes: self.echoes.clone(),
+            done: self.done,
+        }
+    }
+
+    /// disperse sends each party its fragment of the proposal `m`.
+    pub fn disperse(&mut self, m: &P, broadcaster: &mut Broadcaster<P>) -> Result<()> {
+        debug_assert_eq!(self.i, self.tag.proposer);
+
+        let shards = encode(m, self.data_shards, self.parties.len())?;
+        let tree = MerkleTree::new(&shards)?;
+        // we know the proposal already
+        self.done = true;
+
+        for (index, (party, data)) in self.parties.clone().into_iter().zip(shards).enumerate() {
+            let fragment = Fragment {
+                root: tree.root(),
+                index,
+                data,
+                proof: tree.proof(index),
+            };
+            let msg = Message {
+                tag: self.tag.clone(),
+                action: Action::Disperse(fragment),
+            };
+            if party == self.i {
+                self.receive_message(self.i, msg, broadcaster)?;
+            } else {
+                log::debug!("party {} sends {} to {party}", self.i, msg.action_str());
+                broadcaster.send_to(Some(self.tag.proposer), bundle::Message::Avid(msg), party);
+            }
+        }
+        Ok(())
+    }
+
+    /// receive_message process the received message 'msg` from `initiator`.
+    /// It returns the proposal once it is rebuilt.
+    pub fn receive_message(
+        &mut self,
+        initiator: NodeId,
+        msg: Message,
+        broadcaster: &mut Broadcaster<P>,
+    ) -> Result<Option<P>> {
+        log::trace!(
+            "party {} received {} message from {}",
+            self.i,
+            msg.action_str(),
+            initiator
+        );
+
+        if msg.tag != self.tag {
+            return Err(Error::WrongTag {
+                expected: self.tag.clone(),
+                got: msg.tag,
+            });
+        }
+
+        match msg.action {
+            Action::Disperse(fragment) => {
+                if initiator != self.tag.proposer || self.echoed {
+                    return Ok(None);
+                }
+                if !self.is_valid(self.i, &fragment)? {
+                    return Err(Error::InvalidFragment(initiator));
+                }
+                self.echoed = true;
+
+                // send our fragment to all parties
+                let echo_msg = Message {
+                    tag: self.tag.clone(),
+                    action: Action::Echo(fragment),
+                };
+                log::debug!("party {} broadcasts {}", self.i, echo_msg.action_str());
+                broadcaster.broadcast(
+                    Some(self.tag.proposer),
+                    bundle::Message::Avid(echo_msg.clone()),
+                );
+                self.receive_message(self.i, echo_msg, broadcaster)
+            }
+            Action::Echo(fragment) => {
+                if self.echoes.contains(&initiator) {
+                    return Ok(None);
+                }
+                if !self.is_valid(initiator, &fragment)? {
+                    return Err(Error::InvalidFragment(initiator));
+                }
+                self.echoes.insert(initiator);
+
+                let root = fragment.root;
+                let fragments = self.fragments.entry(root).or_default();
+                fragments.insert(fragment.index, fragment.data);
+                if self.done || fragments.len() < self.data_shards {
+                    return Ok(None);
+                }
+                self.rebuild(root)
+            }
+        }
+    }
+
+    // is_valid checks that `fragment` is the fragment of `party`.
+    fn is_valid(&self, party: NodeId, fragment: &Fragment) -> Result<bool> {
+        if self.parties.binary_search(&party) != Ok(fragment.index) {
+            return Ok(false);
+        }
+        Ok(merkle::verify(
+            &fragment.root,
+            self.parties.len(),
+            fragment.index,
+            &fragment.data,
+            &fragment.proof,
+        )?)
+    }
+
+    // rebuild decodes the proposal from the fragments of `root`. The proposal
+    // is encoded again, to check that any other fragments of `root` would
+    // rebuild it too.
+    fn rebuild(&mut self, root: Hash32) -> Result<Option<P>> {
+        self.done = true;
+        let fragments = &self.fragments[&root];
+
+        let shard_len = fragments.values().next().map(Vec::len).unwrap_or_default();
+        if shard_len == 0 || fragments.values().any(|data| data.len() != shard_len) {
+            return Err(Error::InconsistentFragments(root));
+        }
+
+        let proposal = match decode::<P>(fragments, self.data_shards, self.parties.len())? {
+            Some(proposal) => proposal,
+            None => return Err(Error::InconsistentFragments(root)),
+        };
+        let shards = encode(&proposal, self.data_shards, self.parties.len())?;
+        if MerkleTree::new(&shards)?.root() != root {
+            return Err(Error::InconsistentFragments(root));
+        }
+
+        log::debug!(
+            "party {} rebuilt the proposal of {}",
+            self.i,
+            self.tag.proposer
+        );
+        Ok(Some(proposal))
+    }
+}
+
+#[cfg(test)]
+#[path = "./test.rs"]
+mod test;
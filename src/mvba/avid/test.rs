@@ -0,0 +1,214 @@
+use super::error::Error;
+use super::merkle::{self, MerkleTree};
+use super::message::{Action, Fragment, Message};
+use super::{decode, encode, Avid, NodeId};
+use crate::mvba::broadcaster::Broadcaster;
+use crate::mvba::bundle;
+use crate::mvba::tag::{Domain, Tag};
+
+use rand::{thread_rng, Rng};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use std::collections::BTreeMap;
+
+type Party = (Avid<Vec<u8>>, Broadcaster<Vec<u8>>);
+
+struct TestNet {
+    parties: BTreeMap<NodeId, Party>,
+    m: Vec<u8>,
+}
+
+impl TestNet {
+    const PROPOSER: NodeId = 0;
+
+    // There are `n` parties, any `t + 1` fragments rebuild the proposal.
+    pub fn new(n: usize, t: usize) -> Self {
+        let tag = Tag::new(Domain::new("test-domain", 0), Self::PROPOSER);
+        let ids = Vec::from_iter(0..n as NodeId);
+        let parties = BTreeMap::from_iter(ids.iter().map(|id| {
+            let avid = Avid::new(tag.clone(), *id, ids.clone(), t + 1);
+            (*id, (avid, Broadcaster::new(*id)))
+        }));
+
+        // Creating a random proposal
+        let mut rng = thread_rng();
+        let m = Vec::from_iter((0..rng.gen_range(1..1000)).map(|_| rng.gen()));
+
+        Self { parties, m }
+    }
+
+    pub fn tag(&self) -> Tag {
+        Tag::new(Domain::new("test-domain", 0), Self::PROPOSER)
+    }
+
+    // deliver routes the messages until there are no more, and returns the
+    // proposals rebuilt by each party.
+    pub fn deliver(&mut self) -> BTreeMap<NodeId, Vec<u8>> {
+        let mut rebuilt = BTreeMap::new();
+        loop {
+            let ids = Vec::from_iter(self.parties.keys().copied());
+            let mut bundles = Vec::new();
+            for (id, (_, broadcaster)) in self.parties.iter_mut() {
+                let (gossips, directs) = broadcaster.take_bundles();
+                for bundle in gossips {
+                    bundles.extend(
+                        ids.iter()
+                            .filter(|to| *to != id)
+                            .map(|to| (*to, bundle.clone())),
+                    );
+                }
+                bundles.extend(directs);
+            }
+            if bundles.is_empty() {
+                return rebuilt;
+            }
+            for (to, bundle) in bundles {
+                let msg = match bundle.message {
+                    bundle::Message::Avid(msg) => msg,
+                    msg => panic!("unexpected message {msg:?}"),
+                };
+                let (avid, broadcaster) = self.parties.get_mut(&to).unwrap();
+                if let Some(m) = avid
+                    .receive_message(bundle.initiator, msg, broadcaster)
+                    .unwrap()
+                {
+                    rebuilt.insert(to, m);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rebuild_from_any_fragments() {
+    let (n, k) = (7, 3);
+    let m = "a proposal that is longer than a few fragments".to_string();
+    let shards = encode(&m, k, n).unwrap();
+    assert_eq!(shards.len(), n);
+
+    for subset in [[0, 1, 2], [4, 5, 6], [0, 3, 6], [1, 4, 5]] {
+        let fragments = BTreeMap::from_iter(subset.iter().map(|i| (*i, shards[*i].clone())));
+        assert_eq!(decode::<String>(&fragments, k, n).unwrap(), Some(m.clone()));
+    }
+}
+
+#[test]
+fn test_malicious_length_prefix() {
+    let (n, k) = (4, 2);
+    for len in [u64::MAX, u64::MAX - 7, 1 << 40] {
+        // the length prefix doesn't match the data that follows it
+        let mut data = Vec::from(len.to_le_bytes());
+        data.extend([1; 8]);
+        let mut shards = Vec::from_iter(data.chunks(8).map(<[u8]>::to_vec));
+        shards.resize(n, vec![0; 8]);
+        ReedSolomon::new(k, n - k)
+            .unwrap()
+            .encode(&mut shards)
+            .unwrap();
+
+        let fragments = BTreeMap::from_iter(shards.into_iter().enumerate().take(k));
+        assert_eq!(decode::<Vec<u8>>(&fragments, k, n).unwrap(), None);
+    }
+}
+
+#[test]
+fn test_merkle_proof() {
+    let leaves = Vec::from_iter((0..5u8).map(|i| vec![i; 10]));
+    let tree = MerkleTree::new(&leaves).unwrap();
+
+    for (index, leaf) in leaves.iter().enumerate() {
+        let proof = tree.proof(index);
+        assert!(merkle::verify(&tree.root(), 5, index, leaf, &proof).unwrap());
+        // a leaf is only valid at its index
+        assert!(!merkle::verify(&tree.root(), 5, (index + 1) % 5, leaf, &proof).unwrap());
+        assert!(!merkle::verify(&tree.root(), 5, index, &[index as u8; 9], &proof).unwrap());
+    }
+}
+
+#[test]
+fn test_all_parties_rebuild_the_proposal() {
+    let mut t = TestNet::new(7, 2);
+    let m = t.m.clone();
+    let (proposer, broadcaster) = t.parties.get_mut(&TestNet::PROPOSER).unwrap();
+    proposer.disperse(&m, broadcaster).unwrap();
+
+    // the proposer sends each party its fragment, about a third of the proposal
+    let (_, directs) = broadcaster.take_bundles();
+    assert_eq!(directs.len(), 6);
+    for (to, bundle) in directs {
+        match &bundle.message {
+            bundle::Message::Avid(Message {
+                action: Action::Disperse(fragment),
+                ..
+            }) => {
+                assert_eq!(fragment.index as NodeId, to);
+                assert!(fragment.data.len() <= (m.len() + 16) / 3 + 1);
+            }
+            msg => panic!("unexpected message {msg:?}"),
+        }
+        broadcaster.send_to(bundle.target, bundle.message, to);
+    }
+
+    let rebuilt = t.deliver();
+    assert_eq!(rebuilt.len(), 6);
+    assert!(rebuilt.values().all(|p| p == &m));
+}
+
+#[test]
+fn test_invalid_fragment() {
+    let mut t = TestNet::new(4, 1);
+    let shards = encode(&t.m, 2, 4).unwrap();
+    let tree = MerkleTree::new(&shards).unwrap();
+    let tag = t.tag();
+
+    let mut fragment = Fragment {
+        root: tree.root(),
+        index: 1,
+        data: shards[1].clone(),
+        proof: tree.proof(1),
+    };
+    fragment.data[0] ^= 1;
+
+    let (avid, broadcaster) = t.parties.get_mut(&1).unwrap();
+    let msg = Message {
+        tag: tag.clone(),
+        action: Action::Disperse(fragment.clone()),
+    };
+    let result = avid.receive_message(TestNet::PROPOSER, msg, broadcaster);
+    assert!(matches!(result, Err(Error::InvalidFragment(0))));
+
+    // a party can only echo its own fragment
+    fragment.data = shards[1].clone();
+    let msg = Message {
+        tag,
+        action: Action::Echo(fragment),
+    };
+    let result = avid.receive_message(2, msg, broadcaster);
+    assert!(matches!(result, Err(Error::InvalidFragment(2))));
+}
+
+#[test]
+fn test_inconsistent_fragments() {
+    let mut t = TestNet::new(4, 1);
+    let tag = t.tag();
+
+    // the fragments of the proposer are not encoded from one proposal
+    let mut shards = encode(&t.m, 2, 4).unwrap();
+    shards[3][0] ^= 1;
+    let tree = MerkleTree::new(&shards).unwrap();
+
+    let (avid, broadcaster) = t.parties.get_mut(&1).unwrap();
+    let mut result = Ok(None);
+    for index in [0, 3] {
+        let msg = Message {
+            tag: tag.clone(),
+            action: Action::Echo(Fragment {
+                root: tree.root(),
+                index,
+                data: shards[index].clone(),
+                proof: tree.proof(index),
+            }),
+        };
+        result = avid.receive_message(index as NodeId, msg, broadcaster);
+    }
+    assert!(matches!(result, Err(Error::InconsistentFragments(root)) if root == tree.root()));
+}
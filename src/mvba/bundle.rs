@@ -1,4 +1,4 @@
-use super::{abba, mvba, tag::Domain, vcbc, NodeId};
+use super::{abba, avid, mvba, tag::Domain, vcbc, NodeId};
 use serde::{Deserialize, Serialize};
 
 /// Bundle is a wrapper around the actual message
@@ -16,8 +16,9 @@ impl<P> Bundle<P> {
     pub fn domain(&self) -> &Domain {
         match &self.message {
             Message::Vcbc(msg) => &msg.tag.domain,
-            Message::Mvba(msg) => &msg.vote.tag.domain,
+            Message::Mvba(msg) => msg.domain(),
             Message::Abba(msg) => &msg.tag.domain,
+            Message::Avid(msg) => &msg.tag.domain,
         }
     }
 }
@@ -27,6 +28,7 @@ pub enum Message<P> {
     Vcbc(vcbc::message::Message<P>),
     Abba(abba::message::Message),
     Mvba(mvba::message::Message),
+    Avid(avid::message::Message),
 }
 
 /// Ongoing messages definition
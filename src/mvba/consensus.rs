@@ -1,42 +1,108 @@
 use super::{
-    abba::Abba,
+    abba::{self, Abba},
+    avid::{self, Avid},
     bundle::Message::*,
     bundle::{Bundle, Outgoing},
-    error::Error,
-    error::Result,
-    hash::Hash32,
-    mvba::Mvba,
+    error::{Error, Result, Severity},
+    fault::Fault,
+    mvba::{self, Mvba},
     tag::{Domain, Tag},
     vcbc, Proof,
 };
 use crate::mvba::{broadcaster::Broadcaster, vcbc::Vcbc, Decision, MessageValidity, NodeId};
-use blsttc::{PublicKeySet, SecretKeyShare};
-use serde::Serialize;
-use std::{collections::HashMap, fmt::Debug};
+use blsttc::{PublicKey, PublicKeySet, SecretKeyShare};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    sync::Arc,
+};
+
+/// The version of the snapshots written by `Consensus::snapshot`.
+/// It is bumped whenever the state that is kept changes.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// How the proposals are sent to the parties.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dissemination {
+    /// Each proposer sends its proposal to all the parties.
+    #[default]
+    Full,
+    /// Each proposer sends the digest of its proposal, and disperses the
+    /// proposal in fragments as in `ErasureCoded`.
+    ///
+    /// A party certifies the digest only once it rebuilt a valid proposal
+    /// for it, so a proposer that withholds its proposal is not certified.
+    /// The parties that learn the decided digest without the proposal fetch
+    /// it, and check it with the validity predicate.
+    Reference,
+    /// Each proposer sends each party a fragment of its proposal, and the
+    /// parties rebuild it from the fragments they echo to each other.
+    ErasureCoded,
+}
+
+// The state of the consensus, as kept in a snapshot.
+#[derive(Serialize, Deserialize)]
+struct State<P> {
+    domain: Domain,
+    self_id: NodeId,
+    // the snapshot can only be restored with the keys it was taken with
+    public_key: PublicKey,
+    abba_map: BTreeMap<NodeId, abba::State>,
+    vcbc_map: BTreeMap<NodeId, vcbc::State<P>>,
+    avid_map: BTreeMap<NodeId, avid::State>,
+    dissemination: Dissemination,
+    mvba: mvba::State,
+    decided_proposer: Option<NodeId>,
+    decided_proposal: Option<P>,
+}
 
 pub struct Consensus<P: Debug + Clone + Serialize + Eq> {
     domain: Domain,
     self_id: NodeId,
     abba_map: HashMap<NodeId, Abba>,
     vcbc_map: HashMap<NodeId, Vcbc<P>>,
+    avid_map: HashMap<NodeId, Avid<P>>,
+    dissemination: Dissemination,
     mvba: Mvba<P>,
     decided_proposer: Option<NodeId>,
     decided_proposal: Option<P>,
     broadcaster: Broadcaster<P>,
 }
 
-impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
+impl<P: Debug + Clone + Serialize + DeserializeOwned + Eq> Consensus<P> {
     pub fn init(
         domain: Domain,
         self_id: NodeId,
         sec_key_share: SecretKeyShare,
         pub_key_set: PublicKeySet,
         parties: Vec<NodeId>,
-        message_validity: MessageValidity<P>,
+        message_validity: impl MessageValidity<P> + 'static,
+    ) -> Self {
+        Self::init_shared(
+            domain,
+            self_id,
+            sec_key_share,
+            pub_key_set,
+            parties,
+            Arc::new(message_validity),
+        )
+    }
+
+    // init_shared is the same as `init`, with a validity predicate that is
+    // shared with other consensus instances.
+    pub(crate) fn init_shared(
+        domain: Domain,
+        self_id: NodeId,
+        sec_key_share: SecretKeyShare,
+        pub_key_set: PublicKeySet,
+        parties: Vec<NodeId>,
+        message_validity: Arc<dyn MessageValidity<P>>,
     ) -> Self {
         let broadcaster = Broadcaster::new(self_id);
         let mut abba_map = HashMap::new();
         let mut vcbc_map = HashMap::new();
+        let mut avid_map = HashMap::new();
 
         for party in &parties {
             let tag = Tag::new(domain.clone(), *party);
@@ -45,10 +111,18 @@ impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
                 self_id,
                 pub_key_set.clone(),
                 sec_key_share.clone(),
-                message_validity,
+                message_validity.clone(),
             );
             vcbc_map.insert(*party, vcbc);
 
+            let avid = Avid::new(
+                tag.clone(),
+                self_id,
+                parties.clone(),
+                pub_key_set.threshold() + 1,
+            );
+            avid_map.insert(*party, avid);
+
             let abba = Abba::new(tag, self_id, pub_key_set.clone(), sec_key_share.clone());
             abba_map.insert(*party, abba);
         }
@@ -60,6 +134,8 @@ impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
             self_id,
             vcbc_map,
             abba_map,
+            avid_map,
+            dissemination: Dissemination::default(),
             mvba,
             decided_proposer: None,
             decided_proposal: None,
@@ -67,6 +143,122 @@ impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
         }
     }
 
+    /// Restores the consensus from a `snapshot` taken by [`Consensus::snapshot`].
+    /// The keys and the validity predicate have to be given again, as they
+    /// are not part of the snapshot.
+    pub fn restore(
+        snapshot: &[u8],
+        sec_key_share: SecretKeyShare,
+        pub_key_set: PublicKeySet,
+        message_validity: impl MessageValidity<P> + 'static,
+    ) -> Result<Self> {
+        let version: u32 = bincode::deserialize(snapshot)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedSnapshot(version));
+        }
+        let (_, state): (u32, State<P>) = bincode::deserialize(snapshot)?;
+
+        if state.public_key != pub_key_set.public_key()
+            || pub_key_set.public_key_share(state.self_id) != sec_key_share.public_key_share()
+        {
+            return Err(Error::SnapshotKeys);
+        }
+
+        let message_validity: Arc<dyn MessageValidity<P>> = Arc::new(message_validity);
+        let vcbc_map = HashMap::from_iter(state.vcbc_map.into_iter().map(|(party, vcbc)| {
+            let vcbc = Vcbc::restore(
+                vcbc,
+                pub_key_set.clone(),
+                sec_key_share.clone(),
+                message_validity.clone(),
+            );
+            (party, vcbc)
+        }));
+        let abba_map = HashMap::from_iter(state.abba_map.into_iter().map(|(party, abba)| {
+            let abba = Abba::restore(abba, pub_key_set.clone(), sec_key_share.clone());
+            (party, abba)
+        }));
+        let avid_map = HashMap::from_iter(
+            state
+                .avid_map
+                .into_iter()
+                .map(|(party, avid)| (party, Avid::restore(avid))),
+        );
+        let mvba = Mvba::restore(state.mvba, sec_key_share, pub_key_set);
+
+        Ok(Consensus {
+            domain: state.domain,
+            self_id: state.self_id,
+            vcbc_map,
+            abba_map,
+            avid_map,
+            dissemination: state.dissemination,
+            mvba,
+            decided_proposer: state.decided_proposer,
+            decided_proposal: state.decided_proposal,
+            broadcaster: Broadcaster::new(state.self_id),
+        })
+    }
+
+    /// Returns a snapshot of the consensus state, that [`Consensus::restore`]
+    /// resumes from after a restart. The keys are not part of the snapshot.
+    ///
+    /// The snapshot should be persisted after each call that changes the
+    /// state, before its messages are sent. Otherwise a restarted party can
+    /// sign messages that conflict with the ones it sent before.
+    pub fn snapshot(&self) -> Result<Vec<u8>> {
+        let state = State {
+            domain: self.domain.clone(),
+            self_id: self.self_id,
+            public_key: self.mvba.pub_key_set().public_key(),
+            abba_map: BTreeMap::from_iter(
+                self.abba_map
+                    .iter()
+                    .map(|(party, abba)| (*party, abba.snapshot())),
+            ),
+            vcbc_map: BTreeMap::from_iter(
+                self.vcbc_map
+                    .iter()
+                    .map(|(party, vcbc)| (*party, vcbc.snapshot())),
+            ),
+            avid_map: BTreeMap::from_iter(
+                self.avid_map
+                    .iter()
+                    .map(|(party, avid)| (*party, avid.snapshot())),
+            ),
+            dissemination: self.dissemination,
+            mvba: self.mvba.snapshot(),
+            decided_proposer: self.decided_proposer,
+            decided_proposal: self.decided_proposal.clone(),
+        };
+        Ok(bincode::serialize(&(SNAPSHOT_VERSION, &state))?)
+    }
+
+    /// Checks the signature shares of the VCBC and ABBA messages once combined,
+    /// instead of one by one on arrival. The shares are only checked one by one
+    /// to find the invalid ones when the combined signature is invalid.
+    ///
+    /// It saves most of the pairings with honest parties, and costs a few more
+    /// when a party sends invalid shares.
+    pub fn set_batch_verification(&mut self, enabled: bool) {
+        for vcbc in self.vcbc_map.values_mut() {
+            vcbc.set_batch_verification(enabled);
+        }
+        for abba in self.abba_map.values_mut() {
+            abba.set_batch_verification(enabled);
+        }
+    }
+
+    /// Sets how the proposals are sent to the parties. The default is to
+    /// send them in full, the other ways save bandwidth when the proposals are
+    /// large. All the parties have to use the same way.
+    pub fn set_dissemination(&mut self, dissemination: Dissemination) {
+        self.dissemination = dissemination;
+        for vcbc in self.vcbc_map.values_mut() {
+            vcbc.set_reference_mode(dissemination == Dissemination::Reference);
+        }
+    }
+
     pub fn domain(&self) -> &Domain {
         &self.domain
     }
@@ -82,51 +274,205 @@ impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
     /// starts the consensus by proposing the `proposal`.
     pub fn propose(&mut self, proposal: P) -> Result<Vec<Outgoing<P>>> {
         match self.vcbc_map.get_mut(&self.self_id) {
+            Some(vcbc) if self.dissemination == Dissemination::ErasureCoded => {
+                let avid = self
+                    .avid_map
+                    .get_mut(&self.self_id)
+                    .expect("avid_map is not initialized");
+                avid.disperse(&proposal, &mut self.broadcaster)?;
+                vcbc.deliver_dispersed(proposal, &mut self.broadcaster)?;
+            }
+            Some(vcbc) if self.dissemination == Dissemination::Reference => {
+                // the parties certify the digest once they rebuilt the proposal
+                let avid = self
+                    .avid_map
+                    .get_mut(&self.self_id)
+                    .expect("avid_map is not initialized");
+                avid.disperse(&proposal, &mut self.broadcaster)?;
+                vcbc.c_broadcast(proposal, &mut self.broadcaster)?;
+            }
             Some(vcbc) => {
                 // verifiably authenticatedly c-broadcast message (v-echo, w, π) tagged with ID|vcbc.i.0
                 vcbc.c_broadcast(proposal, &mut self.broadcaster)?;
             }
             None => {
-                log::warn!("this node is an observer node")
+                log::warn!(
+                    "party {} is not a proposer, an `Observer` can follow the consensus",
+                    self.self_id
+                )
             }
         }
         Ok(self.broadcaster.take_outgoings())
     }
 
     pub fn process_bundle(&mut self, bundle: &Bundle<P>) -> Result<Vec<Outgoing<P>>> {
+        if self.decided_proposal.is_some() {
+            match (&bundle.message, bundle.target) {
+                // the coin is part of the proof, we keep collecting its shares if
+                // we decided before it was revealed.
+                (Mvba(msg @ mvba::message::Message::CoinShare(_)), _)
+                    if self.mvba.coin().is_none() =>
+                {
+                    self.mvba
+                        .receive_message(msg.clone(), &mut self.broadcaster)?;
+                }
+                // in reference mode the others may still need the proposal
+                (Vcbc(msg), Some(target)) if msg.action == vcbc::message::Action::Request => {
+                    if let Some(vcbc) = self.vcbc_map.get_mut(&target) {
+                        vcbc.receive_message(bundle.initiator, msg.clone(), &mut self.broadcaster)?;
+                    }
+                }
+                _ => {}
+            }
+            return Ok(self.broadcaster.take_outgoings());
+        }
+
+        if let Err(err) = self.receive_bundle(bundle) {
+            if err.severity() != Severity::Drop {
+                return Err(err);
+            }
+            log::debug!(
+                "party {} dropped a message from {}: {err}",
+                self.self_id,
+                bundle.initiator
+            );
+            return Ok(self.broadcaster.take_outgoings());
+        }
+
+        self.check_completed_vote()?;
+        Ok(self.broadcaster.take_outgoings())
+    }
+
+    /// Checks the proposals that the validity predicate could not judge
+    /// before, once the application has what it needs to judge them.
+    ///
+    /// A proposal that is rejected now is dropped.
+    pub fn revalidate_pending(&mut self) -> Result<Vec<Outgoing<P>>> {
         if self.decided_proposal.is_some() {
             return Ok(vec![]);
         }
 
+        let mut proposers = Vec::from_iter(
+            self.vcbc_map
+                .iter()
+                .filter(|(_, vcbc)| vcbc.has_pending())
+                .map(|(proposer, _)| *proposer),
+        );
+        proposers.sort();
+
+        for proposer in proposers {
+            let vcbc = self
+                .vcbc_map
+                .get_mut(&proposer)
+                .expect("vcbc_map is not initialized");
+            if let Err(err) = vcbc.revalidate_pending(&mut self.broadcaster) {
+                let err = Error::from(err);
+                if err.severity() == Severity::Fatal {
+                    return Err(err);
+                }
+                log::warn!(
+                    "party {} rejected the proposal of {proposer}: {err}",
+                    self.self_id
+                );
+                continue;
+            }
+            self.check_delivered(proposer)?;
+        }
+
+        self.check_completed_vote()?;
+        Ok(self.broadcaster.take_outgoings())
+    }
+
+    // check_completed_vote starts the binary agreement on the current
+    // proposer, once its v-votes are completed.
+    fn check_completed_vote(&mut self) -> Result<()> {
+        if let Some(completed_vote) = self.mvba.completed_vote() {
+            let abba = self
+                .abba_map
+                .get_mut(&self.mvba.current_proposer()?)
+                .expect("mvba is not initialized");
+
+            if completed_vote {
+                // The proposal is c-delivered and we have proof for that.
+                // Let's start binary agreement by voting 1
+                if let Some((digest, sig)) = self.mvba.completed_vote_value()? {
+                    abba.pre_vote_one(*digest, sig.clone(), &mut self.broadcaster)?;
+                }
+            } else {
+                // The proposal is NOT c-delivered.
+                // Let's start binary agreement by voting 0,
+                abba.pre_vote_zero(&mut self.broadcaster)?;
+            }
+        }
+        Ok(())
+    }
+
+    // check_delivered passes the proposal of `target` to the MVBA, once it is c-delivered.
+    fn check_delivered(&mut self, target: NodeId) -> Result<()> {
+        let vcbc = self
+            .vcbc_map
+            .get(&target)
+            .expect("vcbc_map is not initialized");
+        // Check if we have agreed on this proposal before.
+        //    There might be a situation that we receive the agreement
+        //    before receiving the actual proposal.
+        match self.decided_proposer {
+            Some(decided) if decided == target => {
+                if let Some((proposal, _)) = vcbc.read_delivered() {
+                    // We re done! We have both proposal and agreement
+                    log::info!("halted. proposer: {target}");
+                    self.decided_proposal = Some(proposal);
+                }
+            }
+            Some(_) => {}
+            None => {
+                // the MVBA only needs the digest, the proposal may not be
+                // known yet in reference mode.
+                if let Some((digest, sig)) = vcbc.read_delivered_digest() {
+                    self.mvba
+                        .set_proposal(target, digest, sig, &mut self.broadcaster)?;
+                    // our coin share may have revealed the order of the proposers
+                    self.skip_rejected_proposers()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // receive_bundle passes the message of the `bundle` to its protocol instance.
+    fn receive_bundle(&mut self, bundle: &Bundle<P>) -> Result<()> {
         match &bundle.message {
             Vcbc(msg) => match bundle.target {
                 Some(target) => match self.vcbc_map.get_mut(&target) {
                     Some(vcbc) => {
                         vcbc.receive_message(bundle.initiator, msg.clone(), &mut self.broadcaster)?;
-                        if let Some((proposal, sig)) = vcbc.read_delivered() {
-                            // Check if we have agreed on this proposal before.
-                            //    There might be a situation that we receive the agreement
-                            //    before receiving the actual proposal.
-
-                            if self.decided_proposer.is_some() {
-                                // We re done! We have both proposal and agreement
-                                log::info!("halted. proposer: {target}");
-                                self.decided_proposal = Some(proposal);
-                            } else {
-                                self.mvba.set_proposal(
-                                    target,
-                                    proposal,
-                                    sig,
-                                    &mut self.broadcaster,
-                                )?;
-                            }
-                        }
+                        self.check_delivered(target)?;
                     }
-                    None => {
-                        return Err(Error::InvalidMessage(format!("target {target} not found")))
+                    None => return Err(Error::UnknownTarget(target)),
+                },
+                None => return Err(Error::MissingTarget),
+            },
+
+            Avid(msg) => match bundle.target {
+                Some(target) => match self.avid_map.get_mut(&target) {
+                    Some(avid) => {
+                        let rebuilt = avid.receive_message(
+                            bundle.initiator,
+                            msg.clone(),
+                            &mut self.broadcaster,
+                        )?;
+                        if let Some(proposal) = rebuilt {
+                            let vcbc = self
+                                .vcbc_map
+                                .get_mut(&target)
+                                .expect("vcbc_map is not initialized");
+                            vcbc.deliver_dispersed(proposal, &mut self.broadcaster)?;
+                            self.check_delivered(target)?;
+                        }
                     }
+                    None => return Err(Error::UnknownTarget(target)),
                 },
-                None => return Err(Error::InvalidMessage("no target is defined".to_string())),
+                None => return Err(Error::MissingTarget),
             },
 
             Abba(msg) => match bundle.target {
@@ -153,46 +499,62 @@ impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
 
                                     self.broadcaster.broadcast(Some(target), bundle_message);
                                 }
-                            } else if self.mvba.current_proposer()? == target
-                                && !self.mvba.move_to_next_proposal(&mut self.broadcaster)?
-                            {
-                                log::warn!("party {} has no more proposal", self.self_id);
+                            } else if self.mvba.is_current_proposer(target) {
+                                self.skip_rejected_proposers()?;
                             }
                         }
                     }
-                    None => {
-                        return Err(Error::InvalidMessage(format!("target {target} not found")))
-                    }
+                    None => return Err(Error::UnknownTarget(target)),
                 },
-                None => return Err(Error::InvalidMessage("no target is defined".to_string())),
+                None => return Err(Error::MissingTarget),
             },
             Mvba(msg) => {
                 self.mvba
                     .receive_message(msg.clone(), &mut self.broadcaster)?;
+                // the binary agreement on the first proposers may have
+                // decided before the coin revealed their order
+                self.skip_rejected_proposers()?;
             }
         };
+        Ok(())
+    }
 
-        if let Some(completed_vote) = self.mvba.completed_vote() {
+    // skip_rejected_proposers moves to the next proposer as long as the
+    // binary agreement on the current one decided 0. It won't get any more
+    // messages that would move us.
+    fn skip_rejected_proposers(&mut self) -> Result<()> {
+        while let Ok(proposer) = self.mvba.current_proposer() {
             let abba = self
                 .abba_map
-                .get_mut(&self.mvba.current_proposer()?)
-                .expect("mvba is not initialized");
-
-            if completed_vote {
-                // The proposal is c-delivered and we have proof for that.
-                // Let's start binary agreement by voting 1
-                if let Some((proposal, sig)) = self.mvba.completed_vote_value()? {
-                    let digest = Hash32::calculate(proposal)?;
-                    abba.pre_vote_one(digest, sig.clone(), &mut self.broadcaster)?;
-                }
-            } else {
-                // The proposal is NOT c-delivered.
-                // Let's start binary agreement by voting 0,
-                abba.pre_vote_zero(&mut self.broadcaster)?;
+                .get(&proposer)
+                .expect("abba_map is not initialized");
+            if abba.is_decided() != Some(false) {
+                break;
+            }
+            if !self.mvba.move_to_next_proposal(&mut self.broadcaster)? {
+                log::warn!("party {} has no more proposal", self.self_id);
+                break;
             }
         }
+        Ok(())
+    }
 
-        Ok(self.broadcaster.take_outgoings())
+    /// The faults detected so far, with the evidence to prove them.
+    pub fn faults(&self) -> Vec<Fault> {
+        let mut proposers = Vec::from_iter(self.vcbc_map.keys().copied());
+        proposers.sort();
+
+        let mut faults = Vec::new();
+        for proposer in proposers {
+            if let Some(vcbc) = self.vcbc_map.get(&proposer) {
+                faults.extend(vcbc.faults().values().cloned());
+            }
+            if let Some(abba) = self.abba_map.get(&proposer) {
+                faults.extend(abba.faults().values().cloned());
+            }
+        }
+        faults.extend(self.mvba.faults().values().cloned());
+        faults
     }
 
     pub fn decided_proposal(&self) -> Option<Decision<P>> {
@@ -201,12 +563,14 @@ impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
         let vcbc = self.vcbc_map.get(proposer)?;
         let (proposal, vcbc_sig) = vcbc.read_delivered()?;
         let (value, abba_sig, round) = abba.decided_value()?;
+        let permutation_coin = self.mvba.coin()?.clone();
         if value {
             let proof = Proof {
                 proposer: *proposer,
                 abba_round: round,
                 abba_signature: abba_sig.clone(),
                 vcbc_signature: vcbc_sig,
+                permutation_coin,
             };
             Some(Decision {
                 domain: self.domain.clone(),
@@ -222,9 +586,18 @@ impl<P: Debug + Clone + Serialize + Eq> Consensus<P> {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-
-    use super::Consensus;
-    use crate::mvba::{bundle::Outgoing, tag::Domain, *};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::{Consensus, Dissemination, SNAPSHOT_VERSION};
+    use crate::mvba::{
+        bundle::Outgoing,
+        error::{Error, Severity},
+        tag::Domain,
+        *,
+    };
 
     use blsttc::SecretKeySet;
     use quickcheck_macros::quickcheck;
@@ -251,7 +624,7 @@ mod tests {
             let mut cons = Vec::new();
 
             for index in 0..n {
-                parties.push(index as usize)
+                parties.push(index)
             }
 
             for p in &parties {
@@ -273,6 +646,42 @@ mod tests {
                 buffer: Vec::new(),
             }
         }
+
+        // deliver_one delivers a buffered message picked at random, and returns it.
+        fn deliver_one(&mut self, rng: &mut impl Rng) -> Outgoing<char> {
+            let outgoing = self.buffer.remove(rng.gen_range(0..self.buffer.len()));
+            for c in &mut self.cons {
+                let bundle = match &outgoing {
+                    Outgoing::Direct(id, bundle) if *id == c.self_id => bundle,
+                    Outgoing::Direct(..) => continue,
+                    Outgoing::Gossip(bundle) => bundle,
+                };
+                self.buffer.append(&mut c.process_bundle(bundle).unwrap());
+            }
+            outgoing
+        }
+
+        // deliver_all delivers the buffered messages in a random order, until
+        // there is nothing left to deliver.
+        fn deliver_all(&mut self, rng: &mut impl Rng) {
+            while !self.buffer.is_empty() {
+                self.deliver_one(rng);
+            }
+        }
+
+        // restart_all restarts the parties from their snapshots.
+        fn restart_all(&mut self) {
+            for c in &mut self.cons {
+                let snapshot = c.snapshot().unwrap();
+                *c = Consensus::restore(
+                    &snapshot,
+                    self.sks.secret_key_share(c.self_id),
+                    self.sks.public_keys(),
+                    valid_proposal,
+                )
+                .unwrap();
+            }
+        }
     }
 
     #[test]
@@ -313,20 +722,19 @@ mod tests {
 
         let mut decisions = HashMap::new();
         for c in &mut net.cons {
-            if c.decided_proposer.is_some() {
+            if let Some(decided_proposer) = c.decided_proposer {
                 let value = c
                     .abba_map
-                    .get(&c.decided_proposer.unwrap())
+                    .get(&decided_proposer)
                     .unwrap()
                     .is_decided()
                     .unwrap();
 
                 log::debug!(
-                    "test for consensus {} finished on proposal {} with {value}",
+                    "test for consensus {} finished on proposal {decided_proposer} with {value}",
                     c.self_id,
-                    c.decided_proposer.unwrap(),
                 );
-                decisions.insert(c.self_id, (c.decided_proposer.unwrap(), value));
+                decisions.insert(c.self_id, (decided_proposer, value));
             }
         }
 
@@ -380,20 +788,19 @@ mod tests {
 
         let mut decisions = HashMap::new();
         for c in &mut net.cons {
-            if c.decided_proposer.is_some() {
+            if let Some(decided_proposer) = c.decided_proposer {
                 let value = c
                     .abba_map
-                    .get(&c.decided_proposer.unwrap())
+                    .get(&decided_proposer)
                     .unwrap()
                     .is_decided()
                     .unwrap();
 
                 log::debug!(
-                    "test for consensus {} finished on proposal {} with {value}",
+                    "test for consensus {} finished on proposal {decided_proposer} with {value}",
                     c.self_id,
-                    c.decided_proposer.unwrap(),
                 );
-                decisions.insert(c.self_id, (c.decided_proposer.unwrap(), value));
+                decisions.insert(c.self_id, (decided_proposer, value));
             }
         }
 
@@ -404,6 +811,69 @@ mod tests {
         assert!(decisions.iter().all(|(_, item)| item == first));
     }
 
+    #[test]
+    fn test_abba_decided_before_the_coin() {
+        let mut rng = rand::rngs::StdRng::from_seed([5u8; 32]);
+        let late_id = 3;
+        // the late party is the first proposer in the order of the coin
+        let mut net = loop {
+            let net = TestNet::new();
+            let coin_bytes = mvba::coin_bytes_to_sign(net.cons[0].domain()).unwrap();
+            let coin = net.sks.secret_key().sign(coin_bytes);
+            if mvba::proposer_permutation(&coin, &[0, 1, 2, 3]).unwrap()[0] == late_id {
+                break net;
+            }
+        };
+        let mut late = net.cons.pop().unwrap();
+        for c in &mut net.cons {
+            let mut msgs = c.propose(rng.gen()).unwrap();
+            net.buffer.append(&mut msgs);
+        }
+        let mut missed = Vec::new();
+        while !net.buffer.is_empty() {
+            match net.deliver_one(&mut rng) {
+                Outgoing::Direct(id, bundle) if id == late_id => missed.push(bundle),
+                Outgoing::Direct(..) => {}
+                Outgoing::Gossip(bundle) => missed.push(bundle),
+            }
+        }
+        // the others rejected the late party, it didn't propose
+        let decision = net.cons[0].decided_proposal().unwrap();
+        assert_ne!(decision.proof.proposer, late_id);
+
+        // the proposals, then the binary agreement on the late party
+        for bundle in &missed {
+            if matches!(bundle.message, bundle::Message::Vcbc(_)) {
+                late.process_bundle(bundle).unwrap();
+            }
+        }
+        for bundle in &missed {
+            if matches!(bundle.message, bundle::Message::Abba(_)) && bundle.target == Some(late_id)
+            {
+                late.process_bundle(bundle).unwrap();
+            }
+        }
+        assert_eq!(late.abba_map[&late_id].is_decided(), Some(false));
+        assert!(late.mvba.coin().is_none());
+
+        // the coin puts the rejected proposer first, we move past it
+        for bundle in &missed {
+            if matches!(
+                bundle.message,
+                bundle::Message::Mvba(mvba::message::Message::CoinShare(_))
+            ) {
+                late.process_bundle(bundle).unwrap();
+            }
+        }
+        assert!(late.mvba.coin().is_some());
+        assert_ne!(late.mvba.current_proposer().unwrap(), late_id);
+
+        for bundle in &missed {
+            late.process_bundle(bundle).unwrap();
+        }
+        assert_eq!(late.decided_proposal(), Some(decision));
+    }
+
     #[test]
     fn test_proof() {
         let mut rng = rand::rngs::StdRng::from_seed([0u8; 32]);
@@ -437,6 +907,7 @@ mod tests {
         }
 
         for c in &mut net.cons {
+            assert!(c.faults().is_empty());
             if let Some(decision) = c.decided_proposal() {
                 assert!(decision
                     .validate(&net.sks.public_keys().public_key())
@@ -445,6 +916,377 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_batch_verification() {
+        let mut rng = rand::rngs::StdRng::from_seed([1u8; 32]);
+        let mut net = TestNet::new();
+
+        for c in &mut net.cons {
+            c.set_batch_verification(true);
+            let proposal = rng.gen();
+            let mut msgs = c.propose(proposal).unwrap();
+            net.buffer.append(&mut msgs);
+        }
+
+        while !net.buffer.is_empty() {
+            let rand_index = rng.gen_range(0..net.buffer.len());
+            let rand_msg = &net.buffer.remove(rand_index);
+            let mut msgs = Vec::new();
+
+            for c in &mut net.cons {
+                msgs.append(&mut match rand_msg {
+                    Outgoing::Direct(id, bundle) => {
+                        if id == &c.self_id {
+                            c.process_bundle(bundle).unwrap()
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    Outgoing::Gossip(bundle) => c.process_bundle(bundle).unwrap(),
+                });
+            }
+
+            net.buffer.append(&mut msgs);
+        }
+
+        let decisions = Vec::from_iter(net.cons.iter().map(|c| c.decided_proposal().unwrap()));
+        for decision in &decisions {
+            assert_eq!(decision, &decisions[0]);
+            assert!(decision
+                .validate(&net.sks.public_keys().public_key())
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn test_reference_mode() {
+        let mut rng = rand::rngs::StdRng::from_seed([3u8; 32]);
+        let mut net = TestNet::new();
+
+        for c in &mut net.cons {
+            c.set_dissemination(Dissemination::Reference);
+            let proposal = rng.gen();
+            let mut msgs = c.propose(proposal).unwrap();
+            net.buffer.append(&mut msgs);
+        }
+
+        let mut digests = 0;
+        while !net.buffer.is_empty() {
+            let outgoing = net.deliver_one(&mut rng);
+            let bundle = match &outgoing {
+                Outgoing::Gossip(bundle) => bundle,
+                Outgoing::Direct(_, bundle) => bundle,
+            };
+            // the proposals are not c-sent, they are rebuilt from their fragments
+            if let bundle::Message::Vcbc(msg) = &bundle.message {
+                match msg.action {
+                    vcbc::message::Action::Send(_) => panic!("proposal is c-sent"),
+                    vcbc::message::Action::SendDigest(_) => digests += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        assert_eq!(digests, net.cons.len());
+        let decisions = Vec::from_iter(net.cons.iter().map(|c| c.decided_proposal().unwrap()));
+        for decision in &decisions {
+            assert_eq!(decision, &decisions[0]);
+            assert!(decision
+                .validate(&net.sks.public_keys().public_key())
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn test_reference_mode_withholding_proposer() {
+        let mut rng = rand::rngs::StdRng::from_seed([3u8; 32]);
+        let mut net = TestNet::new();
+
+        for c in &mut net.cons {
+            c.set_dissemination(Dissemination::Reference);
+            let proposal = rng.gen();
+            let mut msgs = c.propose(proposal).unwrap();
+            if c.self_id == 0 {
+                // the first proposer sends its digest, but not its proposal
+                msgs.retain(|outgoing| {
+                    let bundle = match outgoing {
+                        Outgoing::Gossip(bundle) => bundle,
+                        Outgoing::Direct(_, bundle) => bundle,
+                    };
+                    !matches!(bundle.message, bundle::Message::Avid(_))
+                });
+            }
+            net.buffer.append(&mut msgs);
+        }
+
+        while !net.buffer.is_empty() {
+            let outgoing = net.deliver_one(&mut rng);
+            let bundle = match &outgoing {
+                Outgoing::Gossip(bundle) => bundle,
+                Outgoing::Direct(_, bundle) => bundle,
+            };
+            // nobody certifies a digest it has no proposal for
+            if let bundle::Message::Vcbc(msg) = &bundle.message {
+                if let vcbc::message::Action::Ready(..) = msg.action {
+                    assert_ne!(msg.tag.proposer, 0);
+                }
+            }
+        }
+
+        // the withholding proposer doesn't stall the consensus
+        let decisions = Vec::from_iter(net.cons.iter().map(|c| c.decided_proposal().unwrap()));
+        for decision in &decisions {
+            assert_eq!(decision, &decisions[0]);
+            assert_ne!(decision.proof.proposer, 0);
+            assert!(decision
+                .validate(&net.sks.public_keys().public_key())
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn test_erasure_coded() {
+        let mut rng = rand::rngs::StdRng::from_seed([4u8; 32]);
+        let mut net = TestNet::new();
+
+        for c in &mut net.cons {
+            c.set_dissemination(Dissemination::ErasureCoded);
+            let proposal = rng.gen();
+            let mut msgs = c.propose(proposal).unwrap();
+            net.buffer.append(&mut msgs);
+        }
+
+        let mut dispersed = 0;
+        while !net.buffer.is_empty() {
+            let outgoing = net.buffer.remove(rng.gen_range(0..net.buffer.len()));
+            let bundle = match &outgoing {
+                Outgoing::Gossip(bundle) => bundle,
+                Outgoing::Direct(_, bundle) => bundle,
+            };
+            match &bundle.message {
+                // the proposals are rebuilt from their fragments
+                bundle::Message::Vcbc(msg) => {
+                    assert!(!matches!(msg.action, vcbc::message::Action::Send(_)))
+                }
+                bundle::Message::Avid(_) => dispersed += 1,
+                _ => {}
+            }
+            for c in &mut net.cons {
+                let mut msgs = match &outgoing {
+                    Outgoing::Direct(id, bundle) if *id == c.self_id => {
+                        c.process_bundle(bundle).unwrap()
+                    }
+                    Outgoing::Direct(..) => continue,
+                    Outgoing::Gossip(bundle) => c.process_bundle(bundle).unwrap(),
+                };
+                net.buffer.append(&mut msgs);
+            }
+        }
+
+        assert!(dispersed > 0);
+        let decisions = Vec::from_iter(net.cons.iter().map(|c| c.decided_proposal().unwrap()));
+        for decision in &decisions {
+            assert_eq!(decision, &decisions[0]);
+            assert!(decision
+                .validate(&net.sks.public_keys().public_key())
+                .unwrap());
+        }
+    }
+
+    // Lookup leaves the proposals pending until the lookup is done.
+    struct Lookup(Arc<AtomicBool>);
+
+    impl MessageValidity<char> for Lookup {
+        fn validate(&self, _: NodeId, _: &char) -> Validity {
+            if self.0.load(Ordering::SeqCst) {
+                Validity::Valid
+            } else {
+                Validity::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn test_revalidate_pending() {
+        let mut rng = rand::rngs::StdRng::from_seed([2u8; 32]);
+        let mut net = TestNet::new();
+        let looked_up = Arc::new(AtomicBool::new(false));
+        let parties = Vec::from_iter(net.cons.iter().map(|c| c.self_id));
+        for c in &mut net.cons {
+            *c = Consensus::init(
+                c.domain.clone(),
+                c.self_id,
+                net.sks.secret_key_share(c.self_id),
+                net.sks.public_keys(),
+                parties.clone(),
+                Lookup(looked_up.clone()),
+            );
+        }
+
+        for c in &mut net.cons {
+            let proposal = rng.gen();
+            net.buffer.append(&mut c.propose(proposal).unwrap());
+        }
+        net.deliver_all(&mut rng);
+        assert!(net.cons.iter().all(|c| c.decided_proposal().is_none()));
+
+        looked_up.store(true, Ordering::SeqCst);
+        for c in &mut net.cons {
+            net.buffer.append(&mut c.revalidate_pending().unwrap());
+        }
+        net.deliver_all(&mut rng);
+
+        let decisions = Vec::from_iter(net.cons.iter().map(|c| c.decided_proposal().unwrap()));
+        for decision in &decisions {
+            assert_eq!(decision, &decisions[0]);
+        }
+    }
+
+    #[test]
+    fn test_reject_with_reason() {
+        let mut net = TestNet::new();
+        let parties = Vec::from_iter(net.cons.iter().map(|c| c.self_id));
+        let allowed = ['a', 'b'];
+        net.cons[1] = Consensus::init(
+            net.cons[1].domain.clone(),
+            1,
+            net.sks.secret_key_share(1),
+            net.sks.public_keys(),
+            parties,
+            move |_, p: &char| allowed.contains(p),
+        );
+
+        let outgoings = net.cons[0].propose('z').unwrap();
+        let bundle = match &outgoings[0] {
+            Outgoing::Gossip(bundle) => bundle.clone(),
+            Outgoing::Direct(_, bundle) => bundle.clone(),
+        };
+        let err = net.cons[1].process_bundle(&bundle).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Vcbc(vcbc::error::Error::InvalidProposal(_))
+        ));
+        assert_eq!(err.severity(), Severity::Penalize);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut rng = rand::rngs::StdRng::from_seed([5u8; 32]);
+        let mut net = TestNet::new();
+
+        for c in &mut net.cons {
+            let proposal = rng.gen();
+            net.buffer.append(&mut c.propose(proposal).unwrap());
+        }
+
+        // the parties restart after every message they process
+        while !net.buffer.is_empty() {
+            net.deliver_one(&mut rng);
+            net.restart_all();
+        }
+
+        let decisions = Vec::from_iter(net.cons.iter().map(|c| c.decided_proposal().unwrap()));
+        for (c, decision) in net.cons.iter().zip(&decisions) {
+            assert!(c.faults().is_empty());
+            assert_eq!(decision, &decisions[0]);
+            assert!(decision
+                .validate(&net.sks.public_keys().public_key())
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn test_restore_during_dispersal() {
+        let mut rng = rand::rngs::StdRng::from_seed([6u8; 32]);
+        let mut net = TestNet::new();
+
+        for c in &mut net.cons {
+            c.set_dissemination(Dissemination::ErasureCoded);
+            let proposal = rng.gen();
+            net.buffer.append(&mut c.propose(proposal).unwrap());
+        }
+
+        // the parties restart after every fragment they receive, until the
+        // proposals are dispersed
+        let is_avid = |outgoing: &Outgoing<char>| {
+            let bundle = match outgoing {
+                Outgoing::Gossip(bundle) => bundle,
+                Outgoing::Direct(_, bundle) => bundle,
+            };
+            matches!(bundle.message, bundle::Message::Avid(_))
+        };
+        while net.buffer.iter().any(is_avid) {
+            if is_avid(&net.deliver_one(&mut rng)) {
+                net.restart_all();
+            }
+        }
+        net.deliver_all(&mut rng);
+
+        let decisions = Vec::from_iter(net.cons.iter().map(|c| c.decided_proposal().unwrap()));
+        for (c, decision) in net.cons.iter().zip(&decisions) {
+            assert!(c.faults().is_empty());
+            assert_eq!(decision, &decisions[0]);
+            assert!(decision
+                .validate(&net.sks.public_keys().public_key())
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn test_restore_checks_the_snapshot() {
+        let mut net = TestNet::new();
+        net.cons[0].propose('a').unwrap();
+        let snapshot = net.cons[0].snapshot().unwrap();
+
+        // the keys of another party
+        let result = Consensus::restore(
+            &snapshot,
+            net.sks.secret_key_share(1),
+            net.sks.public_keys(),
+            valid_proposal,
+        );
+        assert!(matches!(result, Err(Error::SnapshotKeys)));
+
+        // a snapshot of a later version
+        let mut later = snapshot;
+        later[..4].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        let result = Consensus::restore(
+            &later,
+            net.sks.secret_key_share(0),
+            net.sks.public_keys(),
+            valid_proposal,
+        );
+        assert!(matches!(result, Err(Error::UnsupportedSnapshot(v)) if v == SNAPSHOT_VERSION + 1));
+        assert_eq!(result.err().unwrap().severity(), Severity::Fatal);
+    }
+
+    #[test]
+    fn test_drop_routine_errors() {
+        let mut net = TestNet::new();
+        let outgoings = net.cons[0].propose('a').unwrap();
+        let bundle = match &outgoings[0] {
+            Outgoing::Gossip(bundle) => bundle.clone(),
+            Outgoing::Direct(_, bundle) => bundle.clone(),
+        };
+
+        // a message of another consensus instance is dropped
+        let mut other_domain = bundle.clone();
+        if let bundle::Message::Vcbc(msg) = &mut other_domain.message {
+            msg.tag.domain = Domain::new("another-domain", 0);
+        }
+        assert!(net.cons[1]
+            .process_bundle(&other_domain)
+            .unwrap()
+            .is_empty());
+
+        // but a message for an unknown proposer is not
+        let mut unknown_target = bundle;
+        unknown_target.target = Some(net.cons.len() as NodeId);
+        let err = net.cons[1].process_bundle(&unknown_target).unwrap_err();
+        assert!(matches!(err, Error::UnknownTarget(_)));
+        assert_eq!(err.severity(), Severity::Penalize);
+    }
+
     #[quickcheck]
     fn prop_random_msg_delivery(seed: u128) {
         let _ = env_logger::builder()
@@ -487,20 +1329,19 @@ mod tests {
 
         let mut decisions = HashMap::new();
         for c in &mut net.cons {
-            if c.decided_proposer.is_some() {
+            if let Some(decided_proposer) = c.decided_proposer {
                 let value = c
                     .abba_map
-                    .get(&c.decided_proposer.unwrap())
+                    .get(&decided_proposer)
                     .unwrap()
                     .is_decided()
                     .unwrap();
 
                 log::debug!(
-                    "test for consensus {} finished on proposal {} with {value}",
+                    "test for consensus {} finished on proposal {decided_proposer} with {value}",
                     c.self_id,
-                    c.decided_proposer.unwrap(),
                 );
-                decisions.insert(c.self_id, (c.decided_proposer.unwrap(), value));
+                decisions.insert(c.self_id, (decided_proposer, value));
             }
         }
 
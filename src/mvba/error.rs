@@ -1,22 +1,70 @@
 use core::fmt::Debug;
 use thiserror::Error;
 
-use super::{abba, mvba, vcbc};
+use super::{abba, avid, mvba, tag::Domain, vcbc, NodeId};
+
+/// How the host should treat a message that failed to be processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A late, duplicated or out of order message that honest parties can send.
+    /// It can be ignored.
+    Drop,
+    /// A message that no honest party would send. The sender can be penalized.
+    Penalize,
+    /// Our own state is broken, we can't continue.
+    Fatal,
+}
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("encoding/decoding error {0:?}")]
     Encoding(#[from] bincode::Error),
-    #[error("vcbc error {0:?}")]
+    #[error("vcbc error {0}")]
     Vcbc(#[from] vcbc::error::Error),
-    #[error("vcbc error {0:?}")]
+    #[error("abba error {0}")]
     Abba(#[from] abba::error::Error),
-    #[error("abba {0}")]
-    Mvba(#[from] mvba::error::Error),
     #[error("mvba error {0}")]
-    InvalidMessage(String),
-    #[error("generic error {0}")]
-    Generic(String),
+    Mvba(#[from] mvba::error::Error),
+    #[error("avid error {0}")]
+    Avid(#[from] avid::error::Error),
+    #[error("no target is defined")]
+    MissingTarget,
+    #[error("target {0} not found")]
+    UnknownTarget(NodeId),
+    #[error("bundle initiated by {initiator} was sent by {sender}")]
+    WrongSender { sender: NodeId, initiator: NodeId },
+    #[error("invalid domain. expected: {expected}, got {got}")]
+    WrongDomain { expected: Domain, got: Domain },
+    #[error("invalid threshold signature in the message from {0}")]
+    InvalidSignature(NodeId),
+    #[error("decision of {0} with an invalid proof")]
+    InvalidDecision(Domain),
+    #[error("domain {0} is out of the window")]
+    OutOfWindow(Domain),
+    #[error("snapshot version {0} is not supported")]
+    UnsupportedSnapshot(u32),
+    #[error("the snapshot was taken with other keys")]
+    SnapshotKeys,
+}
+
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::Encoding(_) | Error::UnsupportedSnapshot(_) | Error::SnapshotKeys => {
+                Severity::Fatal
+            }
+            Error::Vcbc(err) => err.severity(),
+            Error::Abba(err) => err.severity(),
+            Error::Mvba(err) => err.severity(),
+            Error::Avid(err) => err.severity(),
+            Error::WrongDomain { .. } | Error::OutOfWindow(_) => Severity::Drop,
+            Error::MissingTarget
+            | Error::UnknownTarget(_)
+            | Error::WrongSender { .. }
+            | Error::InvalidSignature(_)
+            | Error::InvalidDecision(_) => Severity::Penalize,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,136 @@
+use blsttc::{PublicKeySet, SignatureShare};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{abba, hash::Hash32, mvba, tag::Tag, vcbc, NodeId};
+
+pub use super::abba::message::MainVoteValue;
+pub use super::mvba::message::Vote;
+
+#[derive(Debug, Error)]
+pub enum FaultError {
+    #[error("The fault accuses a message that was improperly signed")]
+    AccusedAnImproperlySignedMessage,
+    #[error("The messages of the fault are not actually conflicting")]
+    NotActuallyChanged,
+    #[error("encoding/decoding error {0:?}")]
+    Encoding(#[from] bincode::Error),
+}
+
+/// Evidence of a party signing two conflicting messages.
+///
+/// A fault can be verified by anyone that knows the public key set of the
+/// parties, so it can be passed on to justify punishing the party at fault.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Fault {
+    /// The party sent c-ready for two different proposals of the same proposer.
+    ChangedReady {
+        tag: Tag,
+        voter: NodeId,
+        a: (Hash32, SignatureShare),
+        b: (Hash32, SignatureShare),
+    },
+    /// The party pre-voted both zero and one in the same ABBA round.
+    ChangedPreVote {
+        tag: Tag,
+        voter: NodeId,
+        round: usize,
+        zero: SignatureShare,
+        one: SignatureShare,
+    },
+    /// The party main-voted two different values in the same ABBA round.
+    ChangedMainVote {
+        tag: Tag,
+        voter: NodeId,
+        round: usize,
+        a: (MainVoteValue, SignatureShare),
+        b: (MainVoteValue, SignatureShare),
+    },
+    /// The party cast two different MVBA votes for the same proposer.
+    ChangedVote {
+        voter: NodeId,
+        a: (Vote, SignatureShare),
+        b: (Vote, SignatureShare),
+    },
+}
+
+impl Fault {
+    pub fn voter_at_fault(&self) -> NodeId {
+        match self {
+            Fault::ChangedReady { voter, .. }
+            | Fault::ChangedPreVote { voter, .. }
+            | Fault::ChangedMainVote { voter, .. }
+            | Fault::ChangedVote { voter, .. } => *voter,
+        }
+    }
+
+    pub fn validate(&self, pub_key_set: &PublicKeySet) -> Result<(), FaultError> {
+        let verify = |voter: NodeId, sig_share: &SignatureShare, sign_bytes: Vec<u8>| {
+            if pub_key_set
+                .public_key_share(voter)
+                .verify(sig_share, sign_bytes)
+            {
+                Ok(())
+            } else {
+                Err(FaultError::AccusedAnImproperlySignedMessage)
+            }
+        };
+
+        match self {
+            Fault::ChangedReady { tag, voter, a, b } => {
+                if a.0 == b.0 {
+                    return Err(FaultError::NotActuallyChanged);
+                }
+                verify(*voter, &a.1, vcbc::c_ready_bytes_to_sign(tag, &a.0)?)?;
+                verify(*voter, &b.1, vcbc::c_ready_bytes_to_sign(tag, &b.0)?)
+            }
+            Fault::ChangedPreVote {
+                tag,
+                voter,
+                round,
+                zero,
+                one,
+            } => {
+                verify(
+                    *voter,
+                    zero,
+                    abba::pre_vote_bytes_to_sign(tag, *round, false)?,
+                )?;
+                verify(
+                    *voter,
+                    one,
+                    abba::pre_vote_bytes_to_sign(tag, *round, true)?,
+                )
+            }
+            Fault::ChangedMainVote {
+                tag,
+                voter,
+                round,
+                a,
+                b,
+            } => {
+                if a.0 == b.0 {
+                    return Err(FaultError::NotActuallyChanged);
+                }
+                verify(
+                    *voter,
+                    &a.1,
+                    abba::main_vote_bytes_to_sign(tag, *round, &a.0)?,
+                )?;
+                verify(
+                    *voter,
+                    &b.1,
+                    abba::main_vote_bytes_to_sign(tag, *round, &b.0)?,
+                )
+            }
+            Fault::ChangedVote { voter, a, b } => {
+                if a.0 == b.0 || a.0.tag != b.0.tag {
+                    return Err(FaultError::NotActuallyChanged);
+                }
+                verify(*voter, &a.1, mvba::vote_bytes_to_sign(&a.0)?)?;
+                verify(*voter, &b.1, mvba::vote_bytes_to_sign(&b.0)?)
+            }
+        }
+    }
+}
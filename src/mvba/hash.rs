@@ -18,7 +18,7 @@ impl Hash32 {
     pub fn calculate(obj: impl Serialize) -> Result<Self, bincode::Error> {
         use tiny_keccak::{Hasher, Sha3};
 
-        let data = bincode::serialize(&obj)?;
+        let data = crate::signing::encode(&obj)?;
         let mut sha3 = Sha3::v256();
         let mut hash = [0; HASH32_SIZE];
         sha3.update(data.as_ref());
@@ -0,0 +1,616 @@
+//! Running many MVBA instances side by side.
+//!
+//! The [`MvbaManager`] routes each bundle to the instance of its [`Domain`].
+//! For each domain id, only the instances within a window of sequence numbers
+//! above the first undecided one are created. Bundles for the sequence numbers
+//! past the window are kept until the window reaches them. The instances that
+//! decided are kept while they are close to the window, to answer the parties
+//! that are late, their decision is kept after that.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Debug,
+    sync::Arc,
+};
+
+use blsttc::{PublicKeySet, SecretKeyShare};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    bundle::{Bundle, Outgoing},
+    consensus::{Consensus, Dissemination},
+    error::{Error, Result, Severity},
+    tag::Domain,
+    Decision, MessageValidity, NodeId,
+};
+
+const DEFAULT_WINDOW: usize = 4;
+const DEFAULT_MAX_EARLY_BUNDLES: usize = 1024;
+const DEFAULT_DECIDED_GRACE: usize = 4;
+const DEFAULT_MAX_INSTANCES: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct MvbaManagerConfig {
+    /// How many sequence numbers of a domain id, starting from the first
+    /// undecided one, can have a running instance.
+    pub window: usize,
+    /// The most bundles we keep for the domains past the window.
+    pub max_early_bundles: usize,
+    /// The most instances running at once, over all the domain ids. Bundles
+    /// that would start another one are dropped.
+    pub max_instances: usize,
+    /// How many sequence numbers of a domain id, below the first undecided
+    /// one, keep their decided instance to answer the parties that are late.
+    pub decided_grace: usize,
+    /// See [`Consensus::set_batch_verification`].
+    pub batch_verification: bool,
+    /// See [`Consensus::set_dissemination`].
+    pub dissemination: Dissemination,
+}
+
+impl Default for MvbaManagerConfig {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_WINDOW,
+            max_early_bundles: DEFAULT_MAX_EARLY_BUNDLES,
+            max_instances: DEFAULT_MAX_INSTANCES,
+            decided_grace: DEFAULT_DECIDED_GRACE,
+            batch_verification: false,
+            dissemination: Dissemination::Full,
+        }
+    }
+}
+
+pub struct MvbaManager<P: Debug + Clone + Serialize + Eq> {
+    self_id: NodeId,
+    sec_key_share: SecretKeyShare,
+    pub_key_set: PublicKeySet,
+    parties: Vec<NodeId>,
+    message_validity: Arc<dyn MessageValidity<P>>,
+    config: MvbaManagerConfig,
+    instances: BTreeMap<Domain, Consensus<P>>,
+    // the instances that decided, while they are in the grace period.
+    finished: BTreeMap<Domain, Consensus<P>>,
+    // the bundles of the domains past the window.
+    early: BTreeMap<Domain, Vec<Bundle<P>>>,
+    // the first undecided sequence number of each domain id.
+    floors: HashMap<String, usize>,
+    // the decided sequence numbers of each domain id, above its floor. They
+    // stay finished when their decision is removed.
+    decided: HashMap<String, BTreeSet<usize>>,
+    decisions: BTreeMap<Domain, Decision<P>>,
+    // the domains decided since the last call to `take_decided`.
+    fresh: Vec<Domain>,
+}
+
+impl<P: Debug + Clone + Serialize + DeserializeOwned + Eq> MvbaManager<P> {
+    pub fn new(
+        self_id: NodeId,
+        sec_key_share: SecretKeyShare,
+        pub_key_set: PublicKeySet,
+        parties: Vec<NodeId>,
+        message_validity: impl MessageValidity<P> + 'static,
+        config: MvbaManagerConfig,
+    ) -> Self {
+        Self {
+            self_id,
+            sec_key_share,
+            pub_key_set,
+            parties,
+            message_validity: Arc::new(message_validity),
+            config,
+            instances: BTreeMap::new(),
+            finished: BTreeMap::new(),
+            early: BTreeMap::new(),
+            floors: HashMap::new(),
+            decided: HashMap::new(),
+            decisions: BTreeMap::new(),
+            fresh: Vec::new(),
+        }
+    }
+
+    pub fn self_id(&self) -> NodeId {
+        self.self_id
+    }
+
+    pub fn pub_key_set(&self) -> PublicKeySet {
+        self.pub_key_set.clone()
+    }
+
+    /// The instance of `domain`, if it is running.
+    pub fn instance(&self, domain: &Domain) -> Option<&Consensus<P>> {
+        self.instances.get(domain)
+    }
+
+    /// The domains with a running instance.
+    pub fn running(&self) -> impl Iterator<Item = &Domain> {
+        self.instances.keys()
+    }
+
+    pub fn decision(&self, domain: &Domain) -> Option<&Decision<P>> {
+        self.decisions.get(domain)
+    }
+
+    pub fn decisions(&self) -> impl Iterator<Item = &Decision<P>> {
+        self.decisions.values()
+    }
+
+    /// Forgets the decision of `domain`, its instance is not started again and
+    /// bundles for it are still dropped.
+    pub fn remove_decision(&mut self, domain: &Domain) -> Option<Decision<P>> {
+        self.decisions.remove(domain)
+    }
+
+    /// The decisions made since the last call, in the order they were made.
+    pub fn take_decided(&mut self) -> Vec<Decision<P>> {
+        let fresh = std::mem::take(&mut self.fresh);
+        Vec::from_iter(
+            fresh
+                .iter()
+                .filter_map(|domain| self.decisions.get(domain).cloned()),
+        )
+    }
+
+    /// Proposes `proposal` in the instance of `domain`, which has to be within
+    /// the window.
+    pub fn propose(&mut self, domain: Domain, proposal: P) -> Result<Vec<Outgoing<P>>> {
+        if self.is_finished(&domain) || !self.in_window(&domain) {
+            return Err(Error::OutOfWindow(domain));
+        }
+        let mut outgoings = self.instance_mut(&domain)?.propose(proposal)?;
+        outgoings.extend(self.evict_decided(domain)?);
+        Ok(outgoings)
+    }
+
+    pub fn process_bundle(&mut self, bundle: &Bundle<P>) -> Result<Vec<Outgoing<P>>> {
+        let domain = bundle.domain().clone();
+        if self.is_finished(&domain) {
+            // the decided instances still answer the requests for their
+            // proposal and collect the coin shares
+            if let Some(consensus) = self.finished.get_mut(&domain) {
+                return consensus.process_bundle(bundle);
+            }
+            log::debug!(
+                "party {} dropped a bundle of the decided instance {domain}",
+                self.self_id
+            );
+            return Ok(vec![]);
+        }
+        if !self.in_window(&domain) {
+            let early_bundles: usize = self.early.values().map(Vec::len).sum();
+            if early_bundles >= self.config.max_early_bundles {
+                return Err(Error::OutOfWindow(domain));
+            }
+            self.early.entry(domain).or_default().push(bundle.clone());
+            return Ok(vec![]);
+        }
+
+        let mut outgoings = self.instance_mut(&domain)?.process_bundle(bundle)?;
+        outgoings.extend(self.evict_decided(domain)?);
+        Ok(outgoings)
+    }
+
+    /// Checks the proposals of the running instances that the validity
+    /// predicate could not judge before.
+    pub fn revalidate_pending(&mut self) -> Result<Vec<Outgoing<P>>> {
+        let mut outgoings = Vec::new();
+        for domain in Vec::from_iter(self.instances.keys().cloned()) {
+            if let Some(consensus) = self.instances.get_mut(&domain) {
+                outgoings.extend(consensus.revalidate_pending()?);
+                outgoings.extend(self.evict_decided(domain)?);
+            }
+        }
+        Ok(outgoings)
+    }
+
+    fn floor(&self, id: &str) -> usize {
+        self.floors.get(id).copied().unwrap_or_default()
+    }
+
+    fn is_finished(&self, domain: &Domain) -> bool {
+        domain.seq < self.floor(&domain.id)
+            || self
+                .decided
+                .get(&domain.id)
+                .is_some_and(|decided| decided.contains(&domain.seq))
+    }
+
+    fn in_window(&self, domain: &Domain) -> bool {
+        domain.seq < self.floor(&domain.id) + self.config.window
+    }
+
+    // instance_mut returns the instance of `domain`, it is started if there is
+    // room for it.
+    fn instance_mut(&mut self, domain: &Domain) -> Result<&mut Consensus<P>> {
+        if !self.instances.contains_key(domain) && self.instances.len() >= self.config.max_instances
+        {
+            return Err(Error::OutOfWindow(domain.clone()));
+        }
+        Ok(self.instances.entry(domain.clone()).or_insert_with(|| {
+            let mut consensus = Consensus::init_shared(
+                domain.clone(),
+                self.self_id,
+                self.sec_key_share.clone(),
+                self.pub_key_set.clone(),
+                self.parties.clone(),
+                self.message_validity.clone(),
+            );
+            consensus.set_batch_verification(self.config.batch_verification);
+            consensus.set_dissemination(self.config.dissemination);
+            consensus
+        }))
+    }
+
+    // evict_decided drops the instance of `domain` if it is decided, and
+    // replays the bundles of the domains that enter the window as a result.
+    fn evict_decided(&mut self, domain: Domain) -> Result<Vec<Outgoing<P>>> {
+        let mut outgoings = Vec::new();
+        let mut candidates = vec![domain];
+        while let Some(domain) = candidates.pop() {
+            let decision = match self.instances.get(&domain) {
+                Some(consensus) => match consensus.decided_proposal() {
+                    Some(decision) => decision,
+                    None => continue,
+                },
+                None => continue,
+            };
+            log::info!("party {} decided {domain}", self.self_id);
+            if let Some(consensus) = self.instances.remove(&domain) {
+                self.finished.insert(domain.clone(), consensus);
+            }
+            self.decisions.insert(domain.clone(), decision);
+            self.fresh.push(domain.clone());
+
+            let mut floor = self.floor(&domain.id);
+            let decided = self.decided.entry(domain.id.clone()).or_default();
+            decided.insert(domain.seq);
+            while decided.remove(&floor) {
+                floor += 1;
+            }
+            self.floors.insert(domain.id.clone(), floor);
+            let grace = self.config.decided_grace;
+            self.finished
+                .retain(|finished, _| finished.id != domain.id || finished.seq + grace >= floor);
+
+            let entering = Vec::from_iter(
+                self.early
+                    .keys()
+                    .filter(|early| early.id == domain.id && self.in_window(early))
+                    .cloned(),
+            );
+            for early in entering {
+                let bundles = self.early.remove(&early).unwrap_or_default();
+                for bundle in bundles {
+                    let result = self
+                        .instance_mut(&early)
+                        .and_then(|consensus| consensus.process_bundle(&bundle));
+                    match result {
+                        Ok(bundle_outgoings) => outgoings.extend(bundle_outgoings),
+                        Err(err) if err.severity() == Severity::Fatal => return Err(err),
+                        Err(err) => log::debug!(
+                            "party {} dropped an early bundle from {}: {err}",
+                            self.self_id,
+                            bundle.initiator
+                        ),
+                    }
+                }
+                candidates.push(early);
+            }
+        }
+        Ok(outgoings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blsttc::SecretKeySet;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{MvbaManager, MvbaManagerConfig};
+    use crate::mvba::{
+        bundle::{Message, Outgoing},
+        consensus::Dissemination,
+        error::{Error, Severity},
+        tag::Domain,
+        NodeId,
+    };
+
+    fn valid_proposal(_id: NodeId, _: &char) -> bool {
+        true
+    }
+
+    struct TestNet {
+        managers: Vec<MvbaManager<char>>,
+        buffer: Vec<Outgoing<char>>,
+        sks: SecretKeySet,
+    }
+
+    impl TestNet {
+        fn new(rng: &mut StdRng, config: MvbaManagerConfig) -> Self {
+            let sks = SecretKeySet::random(2, rng);
+            let parties = Vec::from_iter(0..4);
+            let managers = Vec::from_iter(parties.iter().map(|p| {
+                MvbaManager::new(
+                    *p,
+                    sks.secret_key_share(p),
+                    sks.public_keys(),
+                    parties.clone(),
+                    valid_proposal,
+                    config.clone(),
+                )
+            }));
+
+            Self {
+                managers,
+                buffer: Vec::new(),
+                sks,
+            }
+        }
+
+        fn propose(&mut self, domain: &Domain, rng: &mut StdRng) {
+            for manager in &mut self.managers {
+                self.buffer
+                    .append(&mut manager.propose(domain.clone(), rng.gen()).unwrap());
+            }
+        }
+
+        // delivers the outgoings in random order, returns the ones for the
+        // parties that are not in the net.
+        fn run(&mut self, rng: &mut StdRng) -> Vec<Outgoing<char>> {
+            let mut missed = Vec::new();
+            while !self.buffer.is_empty() {
+                let outgoing = self.buffer.remove(rng.gen_range(0..self.buffer.len()));
+                for manager in &mut self.managers {
+                    match &outgoing {
+                        Outgoing::Direct(id, bundle) if id == &manager.self_id() => self
+                            .buffer
+                            .append(&mut manager.process_bundle(bundle).unwrap()),
+                        Outgoing::Direct(..) => {}
+                        Outgoing::Gossip(bundle) => self
+                            .buffer
+                            .append(&mut manager.process_bundle(bundle).unwrap()),
+                    }
+                }
+                let delivered = match &outgoing {
+                    Outgoing::Direct(id, _) => self.managers.iter().any(|m| m.self_id() == *id),
+                    Outgoing::Gossip(_) => false,
+                };
+                if !delivered {
+                    missed.push(outgoing);
+                }
+            }
+            missed
+        }
+    }
+
+    #[test]
+    fn test_concurrent_instances() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let config = MvbaManagerConfig {
+            window: 2,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let domains = [
+            Domain::new("a", 0),
+            Domain::new("a", 1),
+            Domain::new("b", 0),
+        ];
+        for domain in &domains {
+            net.propose(domain, &mut rng);
+        }
+        let err = net.managers[0]
+            .propose(Domain::new("a", 2), 'x')
+            .unwrap_err();
+        assert!(matches!(err, Error::OutOfWindow(_)));
+        net.run(&mut rng);
+
+        let pk = net.sks.public_keys().public_key();
+        let reference = Vec::from_iter(
+            domains
+                .iter()
+                .map(|domain| net.managers[0].decision(domain).cloned().unwrap()),
+        );
+        for manager in &mut net.managers {
+            assert_eq!(manager.running().count(), 0);
+            assert_eq!(manager.take_decided().len(), domains.len());
+            assert!(manager.take_decided().is_empty());
+            for (domain, expected) in domains.iter().zip(&reference) {
+                let decision = manager.decision(domain).unwrap();
+                assert!(decision.validate(&pk).unwrap());
+                assert_eq!(decision, expected);
+            }
+        }
+
+        // the window moved past the decided instances
+        net.propose(&Domain::new("a", 2), &mut rng);
+        net.run(&mut rng);
+        assert!(net.managers[0].decision(&Domain::new("a", 2)).is_some());
+    }
+
+    #[test]
+    fn test_early_bundles_are_replayed() {
+        let mut rng = StdRng::from_seed([1u8; 32]);
+        let config = MvbaManagerConfig {
+            window: 1,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let mut late = net.managers.pop().unwrap();
+
+        let mut missed = Vec::from_iter((0..2).map(|seq| {
+            net.propose(&Domain::new("a", seq), &mut rng);
+            net.run(&mut rng)
+        }));
+
+        // the bundles of the second instance arrive first
+        missed.reverse();
+        for outgoing in missed.into_iter().flatten() {
+            let bundle = match outgoing {
+                Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+            };
+            late.process_bundle(&bundle).unwrap();
+        }
+
+        assert_eq!(late.running().count(), 0);
+        for seq in 0..2 {
+            let domain = Domain::new("a", seq);
+            assert_eq!(late.decision(&domain), net.managers[0].decision(&domain));
+        }
+    }
+
+    #[test]
+    fn test_removed_decisions_stay_finished() {
+        let mut rng = StdRng::from_seed([3u8; 32]);
+        let config = MvbaManagerConfig {
+            window: 2,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let mut late = net.managers.pop().unwrap();
+
+        let missed = Vec::from_iter((0..2).map(|seq| {
+            net.propose(&Domain::new("a", seq), &mut rng);
+            net.run(&mut rng)
+        }));
+        let bundles = Vec::from_iter(missed.into_iter().map(|missed| {
+            Vec::from_iter(missed.into_iter().map(|outgoing| match outgoing {
+                Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+            }))
+        }));
+
+        // the second instance decides first, and its decision is taken away
+        let second = Domain::new("a", 1);
+        for bundle in &bundles[1] {
+            late.process_bundle(bundle).unwrap();
+        }
+        assert!(late.remove_decision(&second).is_some());
+
+        // its bundles don't start it again
+        for bundle in &bundles[1] {
+            assert!(late.process_bundle(bundle).unwrap().is_empty());
+        }
+        assert_eq!(late.running().count(), 0);
+        assert!(late.propose(second.clone(), 'x').is_err());
+
+        // the floor moves past it once the first instance decides
+        for bundle in &bundles[0] {
+            late.process_bundle(bundle).unwrap();
+        }
+        assert_eq!(late.running().count(), 0);
+        assert!(late.decision(&Domain::new("a", 0)).is_some());
+        assert!(late
+            .propose(Domain::new("a", 3), 'x')
+            .unwrap()
+            .iter()
+            .any(|outgoing| matches!(outgoing, Outgoing::Gossip(_))));
+    }
+
+    #[test]
+    fn test_decided_instances_answer_late_parties() {
+        let mut rng = StdRng::from_seed([4u8; 32]);
+        let config = MvbaManagerConfig {
+            dissemination: Dissemination::Reference,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let mut late = net.managers.pop().unwrap();
+
+        let domain = Domain::new("a", 0);
+        net.propose(&domain, &mut rng);
+        let missed = net.run(&mut rng);
+        assert_eq!(net.managers[0].running().count(), 0);
+
+        // the fragments are lost, the late party decides on the digest alone
+        // and has to fetch the proposal from the others
+        for outgoing in missed {
+            let bundle = match outgoing {
+                Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+            };
+            if !matches!(bundle.message, Message::Avid(_)) {
+                net.buffer
+                    .append(&mut late.process_bundle(&bundle).unwrap());
+            }
+        }
+        while !net.buffer.is_empty() {
+            for outgoing in net.run(&mut rng) {
+                let bundle = match outgoing {
+                    Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+                };
+                net.buffer
+                    .append(&mut late.process_bundle(&bundle).unwrap());
+            }
+        }
+
+        assert_eq!(late.decision(&domain), net.managers[0].decision(&domain));
+    }
+
+    #[test]
+    fn test_early_bundles_are_bounded() {
+        let mut rng = StdRng::from_seed([2u8; 32]);
+        let config = MvbaManagerConfig {
+            window: 1,
+            max_early_bundles: 1,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let mut other = MvbaManager::new(
+            0,
+            net.sks.secret_key_share(0),
+            net.sks.public_keys(),
+            Vec::from_iter(0..4),
+            valid_proposal,
+            MvbaManagerConfig::default(),
+        );
+        let outgoings = other.propose(Domain::new("a", 1), 'x').unwrap();
+        let outgoings = outgoings
+            .into_iter()
+            .chain(other.propose(Domain::new("a", 2), 'y').unwrap());
+        let bundles = Vec::from_iter(outgoings.map(|outgoing| match outgoing {
+            Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+        }));
+
+        assert!(net.managers[1]
+            .process_bundle(&bundles[0])
+            .unwrap()
+            .is_empty());
+        let err = net.managers[1].process_bundle(&bundles[1]).unwrap_err();
+        assert!(matches!(err, Error::OutOfWindow(_)));
+        assert_eq!(err.severity(), Severity::Drop);
+        assert_eq!(net.managers[1].running().count(), 0);
+    }
+
+    #[test]
+    fn test_instances_are_bounded() {
+        let mut rng = StdRng::from_seed([5u8; 32]);
+        let config = MvbaManagerConfig {
+            max_instances: 2,
+            ..Default::default()
+        };
+        let mut net = TestNet::new(&mut rng, config);
+        let mut other = MvbaManager::new(
+            0,
+            net.sks.secret_key_share(0),
+            net.sks.public_keys(),
+            Vec::from_iter(0..4),
+            valid_proposal,
+            MvbaManagerConfig::default(),
+        );
+
+        // any domain id can be made up
+        let bundles = Vec::from_iter(["a", "b", "c"].into_iter().map(|id| {
+            match other.propose(Domain::new(id, 0), 'x').unwrap().remove(0) {
+                Outgoing::Gossip(bundle) | Outgoing::Direct(_, bundle) => bundle,
+            }
+        }));
+        net.managers[1].process_bundle(&bundles[0]).unwrap();
+        net.managers[1].process_bundle(&bundles[1]).unwrap();
+        let err = net.managers[1].process_bundle(&bundles[2]).unwrap_err();
+        assert!(matches!(err, Error::OutOfWindow(_)));
+        assert_eq!(err.severity(), Severity::Drop);
+        assert_eq!(net.managers[1].running().count(), 2);
+
+        // the running instances still take their bundles
+        net.managers[1].process_bundle(&bundles[0]).unwrap();
+    }
+}
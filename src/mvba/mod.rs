@@ -3,20 +3,25 @@ use serde::{Deserialize, Serialize};
 
 use self::tag::{Domain, Tag};
 
+pub mod atomic_broadcast;
 pub mod bundle;
 pub mod consensus;
 pub mod error;
+pub mod fault;
 pub mod hash;
+pub mod manager;
+pub mod observer;
 pub mod tag;
 
 mod abba;
+mod avid;
 mod broadcaster;
 // TODO: remove me
 #[allow(clippy::module_inception)]
 mod mvba;
 mod vcbc;
 
-pub type NodeId = usize;
+pub use crate::NodeId;
 
 /// A proof for the decided proposed data.
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
@@ -25,6 +30,8 @@ pub struct Proof {
     pub abba_signature: Signature,
     pub abba_round: usize,
     pub vcbc_signature: Signature,
+    /// The threshold coin that chose the order of the proposers.
+    pub permutation_coin: Signature,
 }
 
 impl Proof {
@@ -34,6 +41,11 @@ impl Proof {
         proposal: &P,
         pk: &PublicKey,
     ) -> Result<bool, crate::mvba::error::Error> {
+        let coin_sign_bytes = mvba::coin_bytes_to_sign(&domain)?;
+        if !pk.verify(&self.permutation_coin, coin_sign_bytes) {
+            return Ok(false);
+        }
+
         let tag = Tag::new(domain, self.proposer);
 
         if vcbc::verify_delivered_proposal(&tag, proposal, &self.vcbc_signature, pk)? {
@@ -47,12 +59,54 @@ impl Proof {
             Ok(false)
         }
     }
+
+    /// Returns the `parties` in the order they were tried as proposers.
+    ///
+    /// The decided proposer is the first one in this order that the parties
+    /// agreed on.
+    pub fn proposer_order(
+        &self,
+        parties: &[NodeId],
+    ) -> Result<Vec<NodeId>, crate::mvba::error::Error> {
+        Ok(mvba::proposer_permutation(&self.permutation_coin, parties)?)
+    }
+}
+
+/// The reason a proposal is rejected by the [`MessageValidity`] predicate.
+pub type Rejection = Box<dyn std::error::Error + Send + Sync>;
+
+/// The outcome of checking a proposal with the [`MessageValidity`] predicate.
+#[derive(Debug)]
+pub enum Validity {
+    Valid,
+    /// The proposal is not valid, and will never be.
+    Invalid(Rejection),
+    /// The proposal can't be judged yet, for example the application has to
+    /// look something up first. The proposal is kept until the host asks to
+    /// check it again with `revalidate_pending`.
+    Pending,
 }
 
 /// MessageValidity is same as &Q_{ID}$ ins spec: a global polynomial-time computable
 /// predicate QID known to all parties, which is determined by an external application.
 /// Each party may propose a value v together with a proof π that should satisfy QID .
-pub type MessageValidity<P> = fn(NodeId, &P) -> bool;
+///
+/// The predicate can hold the application state it needs to judge a proposal,
+/// like the current members or a pool of pending transactions. A plain
+/// `fn(NodeId, &P) -> bool` is a predicate too.
+pub trait MessageValidity<P>: Send + Sync {
+    fn validate(&self, proposer: NodeId, proposal: &P) -> Validity;
+}
+
+impl<P, F: Fn(NodeId, &P) -> bool + Send + Sync> MessageValidity<P> for F {
+    fn validate(&self, proposer: NodeId, proposal: &P) -> Validity {
+        if self(proposer, proposal) {
+            Validity::Valid
+        } else {
+            Validity::Invalid("rejected by the validity predicate".into())
+        }
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct Decision<P> {
@@ -85,8 +139,11 @@ pub fn mock_decision<P: Clone + Serialize>(
     let abba_sign_bytes =
         abba::main_vote_bytes_to_sign(&tag, abba_round, &MainVoteValue::Value(true))?;
 
+    let coin_sign_bytes = mvba::coin_bytes_to_sign(&domain)?;
+
     let vcbc_signature = sk.sign(vcbc_sign_bytes);
     let abba_signature = sk.sign(abba_sign_bytes);
+    let permutation_coin = sk.sign(coin_sign_bytes);
 
     Ok(Decision {
         domain,
@@ -96,6 +153,7 @@ pub fn mock_decision<P: Clone + Serialize>(
             abba_signature,
             abba_round,
             vcbc_signature,
+            permutation_coin,
         },
     })
 }
@@ -104,13 +162,95 @@ pub fn mock_decision<P: Clone + Serialize>(
 mod tests {
     use blsttc::SecretKey;
 
-    use super::{mock_decision, tag::Domain};
+    use super::{abba, bundle, hash::Hash32, mock_decision, mvba, tag::Domain, tag::Tag, vcbc};
+    use crate::wire;
+
+    // the domain "d" of sequence 1, and the proposer 2
+    const DOMAIN: &str = "0100000000000000640100000000000000";
+    const TAG: &str = "01000000000000006401000000000000000200000000000000";
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
 
     #[test]
     fn test_mocked_decision() {
         let sk = SecretKey::random();
         let domain = Domain::new("test-domain", 0);
-        let mocked_decision = mock_decision(domain, "test", 0, &sk).unwrap();
+        let mut mocked_decision = mock_decision(domain, "test", 0, &sk).unwrap();
         assert!(mocked_decision.validate(&sk.public_key()).unwrap());
+
+        mocked_decision.proof.permutation_coin = sk.sign("another coin");
+        assert!(!mocked_decision.validate(&sk.public_key()).unwrap());
+    }
+
+    #[test]
+    fn test_signing_bytes_golden() {
+        let domain = Domain::new("d", 1);
+        let tag = Tag::new(domain.clone(), 2);
+
+        // the strings are prefixed by their u64 length
+        let c_ready = vcbc::c_ready_bytes_to_sign(&tag, &Hash32::from([0xab; 32])).unwrap();
+        assert_eq!(
+            hex(&c_ready),
+            format!("01{TAG}0700000000000000632d7265616479{}", "ab".repeat(32))
+        );
+
+        let pre_vote = abba::pre_vote_bytes_to_sign(&tag, 1, true).unwrap();
+        assert_eq!(
+            hex(&pre_vote),
+            format!("01{TAG}08000000000000007072652d766f7465010000000000000001")
+        );
+
+        let main_vote =
+            abba::main_vote_bytes_to_sign(&tag, 1, &abba::message::MainVoteValue::Abstain).unwrap();
+        assert_eq!(
+            hex(&main_vote),
+            format!("01{TAG}09000000000000006d61696e2d766f7465010000000000000001000000")
+        );
+
+        let coin = mvba::coin_bytes_to_sign(&domain).unwrap();
+        assert_eq!(
+            hex(&coin),
+            format!("01{DOMAIN}0b000000000000007065726d75746174696f6e")
+        );
+
+        let vote = mvba::message::Vote {
+            tag,
+            value: false,
+            proof: None,
+        };
+        let vote = mvba::vote_bytes_to_sign(&vote).unwrap();
+        assert_eq!(hex(&vote), format!("01{TAG}0000"));
+    }
+
+    #[test]
+    fn test_wire_golden() {
+        let tag = Tag::new(Domain::new("d", 1), 2);
+        let bundle = bundle::Bundle::<char> {
+            initiator: 1,
+            target: Some(2),
+            message: vcbc::make_c_request_message(tag),
+        };
+
+        // version | protocol | length | initiator | target | Vcbc | tag | Request
+        let frame = wire::encode(&bundle).unwrap();
+        assert_eq!(
+            hex(&frame),
+            format!(
+                concat!(
+                    "01",
+                    "02",
+                    "32000000",
+                    "0100000000000000",
+                    "010200000000000000",
+                    "00000000",
+                    "{}",
+                    "03000000"
+                ),
+                TAG
+            )
+        );
+        assert_eq!(wire::decode(&frame).unwrap(), (bundle, frame.len()));
     }
 }
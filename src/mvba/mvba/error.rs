@@ -1,3 +1,5 @@
+use crate::mvba::error::Severity;
+use crate::mvba::{tag::Domain, NodeId};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,10 +8,31 @@ pub enum Error {
     Encoding(#[from] bincode::Error),
     #[error("blsttc Error {0}")]
     Blsttc(#[from] blsttc::error::Error),
-    #[error("invalid message {0}")]
-    InvalidMessage(String),
-    #[error("{0}")]
-    Generic(String),
+    #[error("invalid domain. expected: {expected}, got {got}")]
+    WrongDomain { expected: Domain, got: Domain },
+    #[error("invalid signature share from {from}")]
+    InvalidSignatureShare { from: NodeId },
+    #[error("proposal with an invalid proof")]
+    InvalidProof,
+    #[error("malformed vote: {0}")]
+    MalformedVote(&'static str),
+    #[error("double vote detected from {from}")]
+    DoubleVote { from: NodeId },
+    #[error("the order of the proposers is not revealed yet")]
+    ProposersNotRevealed,
+}
+
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::WrongDomain { .. } => Severity::Drop,
+            Error::InvalidSignatureShare { .. }
+            | Error::InvalidProof
+            | Error::MalformedVote(_)
+            | Error::DoubleVote { .. } => Severity::Penalize,
+            Error::Encoding(_) | Error::Blsttc(_) | Error::ProposersNotRevealed => Severity::Fatal,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,7 +1,7 @@
 use blsttc::{Signature, SignatureShare};
 use serde::{Deserialize, Serialize};
 
-use crate::mvba::{hash::Hash32, tag::Tag, NodeId};
+use crate::mvba::{hash::Hash32, tag::Domain, tag::Tag, NodeId};
 
 /// VoteAction definition.
 /// This is same as `v-vote` message in spec: (ID, v-vote, a, uj, ρj)
@@ -13,8 +13,33 @@ pub struct Vote {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
-pub struct Message {
+pub struct VoteMessage {
     pub vote: Vote,
     pub voter: NodeId,
     pub signature: SignatureShare,
 }
+
+/// The share of the coin that chooses the order of the proposers.
+/// This is same as the `(ID, coin-share)` message in spec.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CoinShareMessage {
+    pub domain: Domain,
+    pub sender: NodeId,
+    pub sig_share: SignatureShare,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Message {
+    Vote(VoteMessage),
+    CoinShare(CoinShareMessage),
+}
+
+impl Message {
+    pub fn domain(&self) -> &Domain {
+        match self {
+            Message::Vote(msg) => &msg.vote.tag.domain,
+            Message::CoinShare(msg) => &msg.domain,
+        }
+    }
+}
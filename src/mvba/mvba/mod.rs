@@ -1,30 +1,57 @@
 pub(crate) mod error;
 pub(crate) mod message;
 
-use self::message::{Message, Vote};
+use self::message::{CoinShareMessage, Message, Vote, VoteMessage};
 
 use self::{error::Error, error::Result};
 use super::broadcaster::Broadcaster;
+use super::fault::Fault;
 use super::hash::Hash32;
 use super::tag::Domain;
 use super::vcbc;
 use crate::mvba::tag::Tag;
 use crate::mvba::{bundle, NodeId};
-use blsttc::{PublicKeySet, SecretKeyShare, Signature};
-use serde::Serialize;
-use std::collections::HashMap;
+use blsttc::{PublicKeySet, SecretKeyShare, Signature, SignatureShare};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
 
 pub struct Mvba<P> {
     domain: Domain, // Tag is a combination of Domain and proposer ID. It is unique in each MVBA instances.
     i: NodeId,      // represents our unique identifier
-    l: usize,       // represents the index of current proposal
+    l: usize,       // represents the index of current proposal in the permutation
     v: Option<bool>, // represents the decision value of the current proposal. If no decision made yet, it is None.
-    proposals: HashMap<NodeId, (P, Signature)>,
-    votes_per_proposer: HashMap<NodeId, HashMap<NodeId, Vote>>,
+    // the digest of the c-delivered proposals, with their c-final signature.
+    proposals: HashMap<NodeId, (Hash32, Signature)>,
+    votes_per_proposer: HashMap<NodeId, HashMap<NodeId, VoteMessage>>,
     voted: bool,
+    coin_shares: BTreeMap<NodeId, SignatureShare>,
+    coin: Option<Signature>, // the threshold coin that chooses the order of the proposers
+    permutation: Vec<NodeId>, // the order of the proposers. It is empty until the coin is revealed
     pub_key_set: PublicKeySet,
     sec_key_share: SecretKeyShare,
     parties: Vec<NodeId>,
+    faults: BTreeMap<NodeId, Fault>,
+    // the proposals themselves are kept by VCBC
+    _proposal: PhantomData<P>,
+}
+
+/// The state of an MVBA instance, as kept in a snapshot.
+/// The keys are not part of it.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct State {
+    domain: Domain,
+    i: NodeId,
+    l: usize,
+    v: Option<bool>,
+    proposals: HashMap<NodeId, (Hash32, Signature)>,
+    votes_per_proposer: HashMap<NodeId, HashMap<NodeId, VoteMessage>>,
+    voted: bool,
+    coin_shares: BTreeMap<NodeId, SignatureShare>,
+    coin: Option<Signature>,
+    permutation: Vec<NodeId>,
+    parties: Vec<NodeId>,
+    faults: BTreeMap<NodeId, Fault>,
 }
 
 impl<P: Serialize + Eq> Mvba<P> {
@@ -41,11 +68,55 @@ impl<P: Serialize + Eq> Mvba<P> {
             l: 0,
             v: None,
             voted: false,
+            coin_shares: BTreeMap::new(),
+            coin: None,
+            permutation: Vec::new(),
             proposals: HashMap::new(),
             votes_per_proposer: HashMap::new(),
+            faults: BTreeMap::new(),
             pub_key_set,
             sec_key_share,
             parties,
+            _proposal: PhantomData,
+        }
+    }
+
+    /// restore rebuilds the instance from the `state` of its snapshot.
+    pub fn restore(state: State, sec_key_share: SecretKeyShare, pub_key_set: PublicKeySet) -> Self {
+        Self {
+            domain: state.domain,
+            i: state.i,
+            l: state.l,
+            v: state.v,
+            voted: state.voted,
+            coin_shares: state.coin_shares,
+            coin: state.coin,
+            permutation: state.permutation,
+            proposals: state.proposals,
+            votes_per_proposer: state.votes_per_proposer,
+            faults: state.faults,
+            pub_key_set,
+            sec_key_share,
+            parties: state.parties,
+            _proposal: PhantomData,
+        }
+    }
+
+    /// snapshot returns the state of the instance, without the keys.
+    pub fn snapshot(&self) -> State {
+        State {
+            domain: self.domain.clone(),
+            i: self.i,
+            l: self.l,
+            v: self.v,
+            proposals: self.proposals.clone(),
+            votes_per_proposer: self.votes_per_proposer.clone(),
+            voted: self.voted,
+            coin_shares: self.coin_shares.clone(),
+            coin: self.coin.clone(),
+            permutation: self.permutation.clone(),
+            parties: self.parties.clone(),
+            faults: self.faults.clone(),
         }
     }
 
@@ -56,21 +127,19 @@ impl<P: Serialize + Eq> Mvba<P> {
     pub fn set_proposal(
         &mut self,
         proposer: NodeId,
-        proposal: P,
+        digest: Hash32,
         signature: Signature,
         broadcaster: &mut Broadcaster<P>,
     ) -> Result<()> {
         debug_assert!(self.parties.contains(&proposer));
         let tag = self.build_tag(proposer);
-        let digest = Hash32::calculate(&proposal)?;
         let sign_bytes = vcbc::c_ready_bytes_to_sign(&tag, &digest)?;
         if !self.pub_key_set.public_key().verify(&signature, sign_bytes) {
-            return Err(Error::InvalidMessage(
-                "proposal with an invalid proof".to_string(),
-            ));
+            return Err(Error::InvalidProof);
         }
 
-        self.proposals.insert(proposer, (proposal, signature));
+        self.proposals.insert(proposer, (digest, signature));
+        self.share_coin(broadcaster)?;
         self.vote(broadcaster)
     }
 
@@ -101,71 +170,107 @@ impl<P: Serialize + Eq> Mvba<P> {
     }
 
     pub fn current_proposer(&self) -> Result<NodeId> {
-        match self.parties.get(self.l) {
+        match self.permutation.get(self.l) {
             Some(p) => Ok(*p),
-            None => Err(Error::Generic("parties is not initialized".to_string())),
+            None => Err(Error::ProposersNotRevealed),
         }
     }
 
+    pub fn is_current_proposer(&self, proposer: NodeId) -> bool {
+        self.permutation.get(self.l) == Some(&proposer)
+    }
+
+    /// The faults detected in the MVBA votes, keyed by the party at fault.
+    pub fn faults(&self) -> &BTreeMap<NodeId, Fault> {
+        &self.faults
+    }
+
+    /// The coin that chose the order of the proposers, once it is revealed.
+    pub fn coin(&self) -> Option<&Signature> {
+        self.coin.as_ref()
+    }
+
     pub fn completed_vote(&self) -> Option<bool> {
         self.v
     }
 
-    pub fn completed_vote_value(&self) -> Result<Option<&(P, Signature)>> {
+    pub fn completed_vote_value(&self) -> Result<Option<&(Hash32, Signature)>> {
         Ok(self.proposals.get(&self.current_proposer()?))
     }
 
     fn check_message(&mut self, msg: &Message) -> Result<()> {
-        if msg.vote.tag.domain != self.domain {
-            return Err(Error::InvalidMessage(format!(
-                "invalid domain. expected: {}, got {}",
-                self.domain, msg.vote.tag.domain
-            )));
+        if msg.domain() != &self.domain {
+            return Err(Error::WrongDomain {
+                expected: self.domain.clone(),
+                got: msg.domain().clone(),
+            });
+        }
+
+        match msg {
+            Message::Vote(msg) => self.check_vote(msg),
+            Message::CoinShare(msg) => {
+                let sign_bytes = coin_bytes_to_sign(&self.domain)?;
+                if !self
+                    .pub_key_set
+                    .public_key_share(msg.sender)
+                    .verify(&msg.sig_share, sign_bytes)
+                {
+                    return Err(Error::InvalidSignatureShare { from: msg.sender });
+                }
+                Ok(())
+            }
         }
+    }
 
-        let sign_bytes = bincode::serialize(&msg.vote)?;
+    fn check_vote(&self, msg: &VoteMessage) -> Result<()> {
+        let sign_bytes = vote_bytes_to_sign(&msg.vote)?;
         if !self
             .pub_key_set
             .public_key_share(msg.voter)
             .verify(&msg.signature, sign_bytes)
         {
-            return Err(Error::InvalidMessage("invalid signature".to_string()));
+            return Err(Error::InvalidSignatureShare { from: msg.voter });
         }
 
         if !msg.vote.value && msg.vote.proof.is_some() {
-            return Err(Error::InvalidMessage("no vote with proof".to_string()));
+            return Err(Error::MalformedVote("no vote with proof"));
         }
 
         if msg.vote.value && msg.vote.proof.is_none() {
-            return Err(Error::InvalidMessage("yes vote without proof".to_string()));
+            return Err(Error::MalformedVote("yes vote without proof"));
         }
 
         if let Some((digest, signature)) = &msg.vote.proof {
-            let sign_bytes = vcbc::c_ready_bytes_to_sign(&msg.vote.tag, digest)
-                .map_err(|e| Error::Generic(e.to_string()))?;
+            let sign_bytes = vcbc::c_ready_bytes_to_sign(&msg.vote.tag, digest)?;
             if !self.pub_key_set.public_key().verify(signature, sign_bytes) {
-                return Err(Error::InvalidMessage(
-                    "proposal with an invalid proof".to_string(),
-                ));
+                return Err(Error::InvalidProof);
             }
         };
 
         Ok(())
     }
 
-    pub fn add_vote(&mut self, msg: &Message, broadcaster: &mut Broadcaster<P>) -> Result<bool> {
+    pub fn add_vote(
+        &mut self,
+        msg: &VoteMessage,
+        broadcaster: &mut Broadcaster<P>,
+    ) -> Result<bool> {
         let votes = self.proposer_votes_mut(&msg.vote.tag.proposer);
         if let Some(exist) = votes.get(&msg.voter) {
-            if exist != &msg.vote {
-                return Err(Error::InvalidMessage(format!(
-                    "double vote detected from {:?}",
-                    msg.voter
-                )));
+            if exist.vote != msg.vote {
+                let fault = Fault::ChangedVote {
+                    voter: msg.voter,
+                    a: (exist.vote.clone(), exist.signature.clone()),
+                    b: (msg.vote.clone(), msg.signature.clone()),
+                };
+                log::warn!("party {} detected a fault: {fault:?}", self.i);
+                self.faults.entry(msg.voter).or_insert(fault);
+                return Err(Error::DoubleVote { from: msg.voter });
             }
             return Ok(false);
         }
 
-        votes.insert(msg.voter, msg.vote.clone());
+        votes.insert(msg.voter, msg.clone());
 
         if msg.vote.value && !self.proposals.contains_key(&msg.vote.tag.proposer) {
             // If a v-vote from Pj indicates 1 but Pi has not yet received Pa ’s proposal,
@@ -177,7 +282,7 @@ impl<P: Serialize + Eq> Mvba<P> {
                 self.i,
                 msg.vote.tag.proposer,
             );
-            let bundle_msg = vcbc::make_c_request_message(self.current_tag()?);
+            let bundle_msg = vcbc::make_c_request_message(self.build_tag(msg.vote.tag.proposer));
 
             broadcaster.send_to(Some(msg.vote.tag.proposer), bundle_msg, msg.voter);
 
@@ -196,6 +301,43 @@ impl<P: Serialize + Eq> Mvba<P> {
         log::trace!("party {} received message: {:?}", self.i, msg);
 
         self.check_message(&msg)?;
+        match msg {
+            Message::Vote(msg) => self.receive_vote(msg, broadcaster),
+            Message::CoinShare(msg) => self.receive_coin_share(msg, broadcaster),
+        }
+    }
+
+    fn receive_coin_share(
+        &mut self,
+        msg: CoinShareMessage,
+        broadcaster: &mut Broadcaster<P>,
+    ) -> Result<()> {
+        if let Some(exist) = self.coin_shares.get(&msg.sender) {
+            if exist != &msg.sig_share {
+                return Err(Error::DoubleVote { from: msg.sender });
+            }
+            return Ok(());
+        }
+        self.coin_shares.insert(msg.sender, msg.sig_share);
+
+        // wait for t + 1 coin shares, then use the coin to choose
+        // a random permutation of the proposers
+        if self.coin.is_none() && self.coin_shares.len() >= self.threshold() {
+            let coin = self.pub_key_set.combine_signatures(&self.coin_shares)?;
+            self.permutation = proposer_permutation(&coin, &self.parties)?;
+            self.coin = Some(coin);
+            log::debug!(
+                "party {} revealed the order of the proposers: {:?}",
+                self.i,
+                self.permutation
+            );
+
+            self.vote(broadcaster)?;
+        }
+        Ok(())
+    }
+
+    fn receive_vote(&mut self, msg: VoteMessage, broadcaster: &mut Broadcaster<P>) -> Result<()> {
         if !self.add_vote(&msg, broadcaster)? {
             return Ok(());
         }
@@ -203,12 +345,15 @@ impl<P: Serialize + Eq> Mvba<P> {
         // wait for n − t messages (v-echo, wj , πj ) to be c-delivered with tag ID|vcbc.j.0
         //from distinct Pj such that QID (wj , πj ) holds
         let threshold = self.threshold();
-        if self.proposals.len() >= threshold && self.v.is_none() {
+        if self.proposals.len() >= threshold
+            && self.v.is_none()
+            && self.is_current_proposer(msg.vote.tag.proposer)
+        {
             // wait for n − t messages (ID, v-vote, a, uj , ρj ) from distinct Pj such
             // that VID|a (uj , ρj) holds
             let votes = self.proposer_votes_mut(&msg.vote.tag.proposer);
             if votes.len() >= threshold {
-                if votes.values().any(|v| v.value) {
+                if votes.values().any(|v| v.vote.value) {
                     log::debug!(
                         "party {} completed for proposer {}.",
                         self.i,
@@ -228,14 +373,32 @@ impl<P: Serialize + Eq> Mvba<P> {
         Ok(())
     }
 
-    fn proposer_votes_mut(&mut self, proposer: &NodeId) -> &mut HashMap<NodeId, Vote> {
+    fn proposer_votes_mut(&mut self, proposer: &NodeId) -> &mut HashMap<NodeId, VoteMessage> {
         self.votes_per_proposer.entry(*proposer).or_default()
     }
 
+    // share_coin sends our share of the coin once we have n − t proposals
+    fn share_coin(&mut self, broadcaster: &mut Broadcaster<P>) -> Result<()> {
+        if self.proposals.len() >= self.threshold() && !self.coin_shares.contains_key(&self.i) {
+            let sign_bytes = coin_bytes_to_sign(&self.domain)?;
+            let msg = Message::CoinShare(CoinShareMessage {
+                domain: self.domain.clone(),
+                sender: self.i,
+                sig_share: self.sec_key_share.sign(sign_bytes),
+            });
+            log::debug!("party {} broadcasts {msg:?}", self.i);
+
+            broadcaster.broadcast(None, bundle::Message::Mvba(msg.clone()));
+            self.receive_message(msg, broadcaster)?;
+        }
+        Ok(())
+    }
+
     fn vote(&mut self, broadcaster: &mut Broadcaster<P>) -> Result<()> {
         // wait for n − t messages (v-echo, wj , πj ) to be c-delivered with tag ID|vcbc.j.0
-        //from distinct Pj such that QID (wj , πj ) holds
-        if self.proposals.len() >= self.threshold() && !self.voted {
+        //from distinct Pj such that QID (wj , πj ) holds,
+        // and for the coin to reveal the order of the proposers
+        if self.proposals.len() >= self.threshold() && self.coin.is_some() && !self.voted {
             let tag = self.current_tag()?;
             let vote = match self.proposals.get(&tag.proposer) {
                 None => {
@@ -247,15 +410,14 @@ impl<P: Serialize + Eq> Mvba<P> {
                         proof: None,
                     }
                 }
-                Some((proposal, signature)) => {
+                Some((digest, signature)) => {
                     // else
                     // let ρ be the message that completes the c-broadcast with tag ID|vcbc.a.0
                     // send the message (ID, v-vote, a, 1, ρ) to all parties
-                    let digest = Hash32::calculate(proposal)?;
                     Vote {
                         tag,
                         value: true,
-                        proof: Some((digest, signature.clone())),
+                        proof: Some((*digest, signature.clone())),
                     }
                 }
             };
@@ -272,13 +434,13 @@ impl<P: Serialize + Eq> Mvba<P> {
     fn broadcast(&mut self, vote: Vote, broadcaster: &mut Broadcaster<P>) -> Result<()> {
         log::debug!("party {} broadcasts {vote:?}", self.i);
 
-        let sign_bytes = bincode::serialize(&vote)?;
+        let sign_bytes = vote_bytes_to_sign(&vote)?;
         let sig = self.sec_key_share.sign(sign_bytes);
-        let msg = Message {
+        let msg = Message::Vote(VoteMessage {
             vote,
             voter: self.i,
             signature: sig,
-        };
+        });
         broadcaster.broadcast(None, bundle::Message::Mvba(msg.clone()));
         self.receive_message(msg, broadcaster)?;
         Ok(())
@@ -291,6 +453,29 @@ impl<P: Serialize + Eq> Mvba<P> {
     }
 }
 
+/// The bytes signed by the parties to cast the `vote`.
+pub(crate) fn vote_bytes_to_sign(vote: &Vote) -> std::result::Result<Vec<u8>, bincode::Error> {
+    crate::signing::signing_bytes(vote)
+}
+
+/// The bytes signed by the parties to reveal the coin of the `domain`.
+pub(crate) fn coin_bytes_to_sign(domain: &Domain) -> Result<Vec<u8>> {
+    Ok(crate::signing::signing_bytes(&(domain, "permutation"))?)
+}
+
+/// Returns `parties` in the order chosen by the `coin`.
+///
+/// The parties are sorted by the hash of the coin and their id, so the order
+/// can't be known before the coin is revealed.
+pub(crate) fn proposer_permutation(coin: &Signature, parties: &[NodeId]) -> Result<Vec<NodeId>> {
+    let mut keyed = Vec::with_capacity(parties.len());
+    for party in parties {
+        keyed.push((Hash32::calculate((coin, party))?, *party));
+    }
+    keyed.sort();
+    Ok(keyed.into_iter().map(|(_, party)| party).collect())
+}
+
 #[cfg(test)]
 #[path = "./tests.rs"]
 mod tests;
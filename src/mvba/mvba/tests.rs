@@ -1,7 +1,8 @@
-use super::message::{Message, Vote};
+use super::message::{CoinShareMessage, Message, Vote, VoteMessage};
 use super::NodeId;
-use super::{Error, Mvba};
+use super::{coin_bytes_to_sign, proposer_permutation, Error, Mvba};
 use crate::mvba::broadcaster::Broadcaster;
+use crate::mvba::fault::Fault;
 use crate::mvba::hash::Hash32;
 use crate::mvba::tag::{Domain, Tag};
 use crate::mvba::{bundle, vcbc};
@@ -15,6 +16,7 @@ struct TestNet {
     mvba: Mvba<char>,
     broadcaster: Broadcaster<char>,
     proposals: HashMap<NodeId, (char, Signature)>,
+    parties: Vec<NodeId>,
 }
 
 impl TestNet {
@@ -45,15 +47,57 @@ impl TestNet {
             proposals.insert(*p, (proposal, sig));
         }
 
-        let mvba = Mvba::new(domain, i, sec_key_share, sec_key_set.public_keys(), parties);
+        let mvba = Mvba::new(
+            domain,
+            i,
+            sec_key_share,
+            sec_key_set.public_keys(),
+            parties.clone(),
+        );
         Self {
             sec_key_set,
             mvba,
             broadcaster,
             proposals,
+            parties,
         }
     }
 
+    pub fn set_proposal(&mut self, proposer: NodeId) {
+        let (proposal, sig) = self.proposals.get(&proposer).unwrap().clone();
+        let digest = Hash32::calculate(proposal).unwrap();
+        self.mvba
+            .set_proposal(proposer, digest, sig, &mut self.broadcaster)
+            .unwrap();
+    }
+
+    pub fn make_coin_share_msg(&self, sender: NodeId) -> Message {
+        let sign_bytes = coin_bytes_to_sign(&self.mvba.domain).unwrap();
+        let sig_share = self.sec_key_set.secret_key_share(sender).sign(sign_bytes);
+        Message::CoinShare(CoinShareMessage {
+            domain: self.mvba.domain.clone(),
+            sender,
+            sig_share,
+        })
+    }
+
+    // the order of the proposers, as the coin will reveal it
+    pub fn proposer_order(&self) -> Vec<NodeId> {
+        let sign_bytes = coin_bytes_to_sign(&self.mvba.domain).unwrap();
+        let coin = self.sec_key_set.secret_key().sign(sign_bytes);
+        proposer_permutation(&coin, &self.parties).unwrap()
+    }
+
+    pub fn reveal_coin(&mut self, senders: &[NodeId]) {
+        for sender in senders {
+            let msg = self.make_coin_share_msg(*sender);
+            self.mvba
+                .receive_message(msg, &mut self.broadcaster)
+                .unwrap();
+        }
+        assert!(self.mvba.coin().is_some());
+    }
+
     pub fn make_vote_msg(&self, voter: NodeId, proposer: NodeId, value: bool) -> Message {
         let tag = self.mvba.build_tag(proposer);
 
@@ -67,10 +111,17 @@ impl TestNet {
 
         let vote = Vote { tag, value, proof };
         let signature = self.sign_vote(&vote, &voter);
-        Message {
+        Message::Vote(VoteMessage {
             voter,
             vote,
             signature,
+        })
+    }
+
+    pub fn make_vote(&self, voter: NodeId, proposer: NodeId, value: bool) -> VoteMessage {
+        match self.make_vote_msg(voter, proposer, value) {
+            Message::Vote(msg) => msg,
+            msg => panic!("Unexpected message: {msg:?}"),
         }
     }
 
@@ -80,7 +131,7 @@ impl TestNet {
     }
 
     fn sign_vote(&self, vote: &Vote, id: &NodeId) -> SignatureShare {
-        let data = bincode::serialize(&vote).unwrap();
+        let data = super::vote_bytes_to_sign(vote).unwrap();
         let sec_key_share = self.sec_key_set.secret_key_share(id);
         sec_key_share.sign(data)
     }
@@ -93,16 +144,16 @@ fn test_ignore_messages_with_wrong_id() {
     let i = TestNet::PARTY_Y;
     let mut t = TestNet::new(i);
 
-    let mut msg = t.make_vote_msg(voter, proposer, true);
+    let mut msg = t.make_vote(voter, proposer, true);
     msg.vote.tag.domain = Domain::new("another-domain", 0);
 
-    let result = t.mvba.receive_message(msg, &mut t.broadcaster);
+    let result = t
+        .mvba
+        .receive_message(Message::Vote(msg), &mut t.broadcaster);
     match result {
-        Err(Error::InvalidMessage(msg)) => {
-            assert_eq!(
-                msg,
-                "invalid domain. expected: test-domain[0], got another-domain[0]"
-            )
+        Err(Error::WrongDomain { expected, got }) => {
+            assert_eq!(expected, Domain::new("test-domain", 0));
+            assert_eq!(got, Domain::new("another-domain", 0));
         }
         res => panic!("Unexpected result: {res:?}"),
     }
@@ -115,15 +166,17 @@ fn test_ignore_messages_with_invalid_signature() {
     let i = TestNet::PARTY_Y;
     let mut t = TestNet::new(i);
 
-    let mut msg = t.make_vote_msg(voter, proposer, true);
+    let mut msg = t.make_vote(voter, proposer, true);
     msg.signature = t
         .sec_key_set
         .secret_key_share(voter)
         .sign("invalid_message_to_sign");
 
-    let result = t.mvba.receive_message(msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == *"invalid signature"));
+    let result = t
+        .mvba
+        .receive_message(Message::Vote(msg), &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::InvalidSignatureShare { from })
+        if from == voter));
 }
 
 #[test]
@@ -133,13 +186,17 @@ fn test_ignore_messages_no_vote_with_proof() {
     let i = TestNet::PARTY_Y;
     let mut t = TestNet::new(i);
 
-    let mut msg = t.make_vote_msg(voter, proposer, true);
+    let mut msg = t.make_vote(voter, proposer, true);
     msg.vote.value = false;
     msg.signature = t.sign_vote(&msg.vote, &voter);
 
-    let result = t.mvba.receive_message(msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == *"no vote with proof"));
+    let result = t
+        .mvba
+        .receive_message(Message::Vote(msg), &mut t.broadcaster);
+    assert!(matches!(
+        result,
+        Err(Error::MalformedVote("no vote with proof"))
+    ));
 }
 
 #[test]
@@ -149,13 +206,17 @@ fn test_ignore_messages_yes_vote_without_proof() {
     let i = TestNet::PARTY_Y;
     let mut t = TestNet::new(i);
 
-    let mut msg = t.make_vote_msg(voter, proposer, false);
+    let mut msg = t.make_vote(voter, proposer, false);
     msg.vote.value = true;
     msg.signature = t.sign_vote(&msg.vote, &voter);
 
-    let result = t.mvba.receive_message(msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == *"yes vote without proof"));
+    let result = t
+        .mvba
+        .receive_message(Message::Vote(msg), &mut t.broadcaster);
+    assert!(matches!(
+        result,
+        Err(Error::MalformedVote("yes vote without proof"))
+    ));
 }
 
 #[test]
@@ -165,15 +226,16 @@ fn test_ignore_proposal_with_an_invalid_proof() {
     let i = TestNet::PARTY_Y;
     let mut t = TestNet::new(i);
 
-    let mut msg = t.make_vote_msg(voter, proposer, true);
+    let mut msg = t.make_vote(voter, proposer, true);
     let inv_proposal = "invalid_proposal".as_bytes();
     let inv_sig = SecretKey::random().sign(inv_proposal);
     msg.vote.proof = Some((Hash32::calculate(inv_proposal).unwrap(), inv_sig));
     msg.signature = t.sign_vote(&msg.vote, &voter);
 
-    let result = t.mvba.receive_message(msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == *"proposal with an invalid proof"));
+    let result = t
+        .mvba
+        .receive_message(Message::Vote(msg), &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::InvalidProof)));
 }
 
 #[test]
@@ -188,51 +250,32 @@ fn test_double_vote() {
 
     t.mvba.receive_message(msg_1, &mut t.broadcaster).unwrap();
     let result = t.mvba.receive_message(msg_2, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-        if msg == format!("double vote detected from {voter:?}")));
+    assert!(matches!(result, Err(Error::DoubleVote { from }) if from == voter));
+
+    let fault = t.mvba.faults().get(&voter).unwrap();
+    assert!(matches!(fault, Fault::ChangedVote { a, b, .. } if a.0.value && !b.0.value));
+    assert!(fault.validate(&t.sec_key_set.public_keys()).is_ok());
 }
 
 #[test]
 fn test_normal_case() {
     let i = TestNet::PARTY_Y;
     let mut t = TestNet::new(i);
+    let proposer = t.proposer_order()[0];
 
     assert!(t.mvba.completed_vote().is_none());
 
-    let proposal_x = t.proposals.get(&TestNet::PARTY_X).unwrap().clone();
-    let proposal_y = t.proposals.get(&TestNet::PARTY_Y).unwrap().clone();
-    let proposal_s = t.proposals.get(&TestNet::PARTY_S).unwrap().clone();
-
-    let msg_x = t.make_vote_msg(TestNet::PARTY_X, TestNet::PARTY_X, true);
-    let msg_y = t.make_vote_msg(TestNet::PARTY_Y, TestNet::PARTY_X, true);
-    let msg_s = t.make_vote_msg(TestNet::PARTY_S, TestNet::PARTY_X, true);
-
-    t.mvba
-        .set_proposal(
-            TestNet::PARTY_X,
-            proposal_x.0,
-            proposal_x.1,
-            &mut t.broadcaster,
-        )
-        .unwrap();
-    t.mvba
-        .set_proposal(
-            TestNet::PARTY_Y,
-            proposal_y.0,
-            proposal_y.1,
-            &mut t.broadcaster,
-        )
-        .unwrap();
+    let msg_x = t.make_vote_msg(TestNet::PARTY_X, proposer, true);
+    let msg_y = t.make_vote_msg(TestNet::PARTY_Y, proposer, true);
+    let msg_s = t.make_vote_msg(TestNet::PARTY_S, proposer, true);
 
+    for party in t.parties.clone() {
+        t.set_proposal(party);
+    }
+    // we don't know the proposer before the coin is revealed
     assert!(!t.is_broadcasted(&msg_y));
-    t.mvba
-        .set_proposal(
-            TestNet::PARTY_S,
-            proposal_s.0,
-            proposal_s.1,
-            &mut t.broadcaster,
-        )
-        .unwrap();
+
+    t.reveal_coin(&[TestNet::PARTY_X, TestNet::PARTY_S]);
     assert!(t.is_broadcasted(&msg_y));
 
     t.mvba.receive_message(msg_x, &mut t.broadcaster).unwrap();
@@ -243,71 +286,109 @@ fn test_normal_case() {
 
 #[test]
 fn test_normal_case_no_vote() {
-    // Node_x is offline
     let i = TestNet::PARTY_Y;
     let mut t = TestNet::new(i);
-
-    let proposal_y = t.proposals.get(&TestNet::PARTY_Y).unwrap().clone();
-    let proposal_b = t.proposals.get(&TestNet::PARTY_B).unwrap().clone();
-    let proposal_s = t.proposals.get(&TestNet::PARTY_S).unwrap().clone();
-
-    let msg_y_proposal_x = t.make_vote_msg(TestNet::PARTY_Y, TestNet::PARTY_X, false);
-    let msg_b_proposal_x = t.make_vote_msg(TestNet::PARTY_B, TestNet::PARTY_X, false);
-    let msg_s_proposal_x = t.make_vote_msg(TestNet::PARTY_S, TestNet::PARTY_X, false);
-
-    let msg_y_proposal_y = t.make_vote_msg(TestNet::PARTY_Y, TestNet::PARTY_Y, true);
-    let msg_b_proposal_y = t.make_vote_msg(TestNet::PARTY_B, TestNet::PARTY_Y, true);
-    let msg_s_proposal_y = t.make_vote_msg(TestNet::PARTY_S, TestNet::PARTY_Y, true);
-
-    t.mvba
-        .set_proposal(
-            TestNet::PARTY_Y,
-            proposal_y.0,
-            proposal_y.1,
-            &mut t.broadcaster,
-        )
-        .unwrap();
-    t.mvba
-        .set_proposal(
-            TestNet::PARTY_B,
-            proposal_b.0,
-            proposal_b.1,
-            &mut t.broadcaster,
-        )
-        .unwrap();
-    t.mvba
-        .set_proposal(
-            TestNet::PARTY_S,
-            proposal_s.0,
-            proposal_s.1,
-            &mut t.broadcaster,
-        )
-        .unwrap();
-    assert!(t.is_broadcasted(&msg_y_proposal_x));
+    let order = t.proposer_order();
+    // The first proposer is offline
+    let offline = order[0];
+    let next = order[1];
+
+    let msg_y_proposal_offline = t.make_vote_msg(TestNet::PARTY_Y, offline, false);
+    let msg_b_proposal_offline = t.make_vote_msg(TestNet::PARTY_B, offline, false);
+    let msg_s_proposal_offline = t.make_vote_msg(TestNet::PARTY_S, offline, false);
+
+    let msg_y_proposal_next = t.make_vote_msg(TestNet::PARTY_Y, next, true);
+    let msg_b_proposal_next = t.make_vote_msg(TestNet::PARTY_B, next, true);
+    let msg_s_proposal_next = t.make_vote_msg(TestNet::PARTY_S, next, true);
+
+    for party in t.parties.clone() {
+        if party != offline {
+            t.set_proposal(party);
+        }
+    }
+    t.reveal_coin(&[TestNet::PARTY_B, TestNet::PARTY_S]);
+    assert!(t.is_broadcasted(&msg_y_proposal_offline));
 
     t.mvba
-        .receive_message(msg_b_proposal_x, &mut t.broadcaster)
+        .receive_message(msg_b_proposal_offline, &mut t.broadcaster)
         .unwrap();
     t.mvba
-        .receive_message(msg_s_proposal_x, &mut t.broadcaster)
+        .receive_message(msg_s_proposal_offline, &mut t.broadcaster)
         .unwrap();
 
     assert!(!t.mvba.completed_vote().unwrap());
 
     // Let move to the next proposal
     t.mvba.move_to_next_proposal(&mut t.broadcaster).unwrap();
-    assert!(t.is_broadcasted(&msg_y_proposal_y));
+    assert_eq!(t.mvba.current_proposer().unwrap(), next);
+    assert!(t.is_broadcasted(&msg_y_proposal_next));
 
     t.mvba
-        .receive_message(msg_b_proposal_y, &mut t.broadcaster)
+        .receive_message(msg_b_proposal_next, &mut t.broadcaster)
         .unwrap();
     t.mvba
-        .receive_message(msg_s_proposal_y, &mut t.broadcaster)
+        .receive_message(msg_s_proposal_next, &mut t.broadcaster)
         .unwrap();
 
     assert!(t.mvba.completed_vote().unwrap());
 }
 
+#[test]
+fn test_coin_share_after_n_minus_t_proposals() {
+    let i = TestNet::PARTY_Y;
+    let mut t = TestNet::new(i);
+    let coin_share_y = t.make_coin_share_msg(TestNet::PARTY_Y);
+
+    t.set_proposal(TestNet::PARTY_X);
+    t.set_proposal(TestNet::PARTY_Y);
+    assert!(!t.is_broadcasted(&coin_share_y));
+
+    t.set_proposal(TestNet::PARTY_S);
+    assert!(t.is_broadcasted(&coin_share_y));
+    assert!(t.mvba.coin().is_none());
+    assert!(t.mvba.current_proposer().is_err());
+
+    t.reveal_coin(&[TestNet::PARTY_X, TestNet::PARTY_S]);
+    assert_eq!(t.mvba.permutation, t.proposer_order());
+    assert_eq!(t.mvba.current_proposer().unwrap(), t.proposer_order()[0]);
+}
+
+#[test]
+fn test_permutation_is_deterministic() {
+    let t = TestNet::new(TestNet::PARTY_X);
+    let order = t.proposer_order();
+    assert_eq!(order, t.proposer_order());
+
+    let mut sorted = order;
+    sorted.sort();
+    assert_eq!(sorted, t.parties);
+}
+
+#[test]
+fn test_invalid_coin_share() {
+    let i = TestNet::PARTY_Y;
+    let mut t = TestNet::new(i);
+
+    let mut msg = t.make_coin_share_msg(TestNet::PARTY_B);
+    if let Message::CoinShare(coin_share) = &mut msg {
+        coin_share.sig_share = t
+            .sec_key_set
+            .secret_key_share(TestNet::PARTY_B)
+            .sign("invalid_message_to_sign");
+    }
+
+    let result = t.mvba.receive_message(msg, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::InvalidSignatureShare { from })
+        if from == TestNet::PARTY_B));
+
+    let mut msg = t.make_coin_share_msg(TestNet::PARTY_B);
+    if let Message::CoinShare(coin_share) = &mut msg {
+        coin_share.domain = Domain::new("another-domain", 0);
+    }
+    let result = t.mvba.receive_message(msg, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::WrongDomain { .. })));
+}
+
 #[test]
 fn test_request_proposal() {
     let i = TestNet::PARTY_Y;
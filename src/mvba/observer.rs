@@ -0,0 +1,444 @@
+//! Following the outcome of an MVBA instance without taking part in it.
+//!
+//! An observer holds no key share: it never signs or sends anything. It
+//! watches the gossip of the parties and collects the pieces of a [`Proof`]:
+//! the c-final signature of each delivered proposal, the ABBA decision that
+//! accepted a proposer and the coin that ordered the proposers. Each piece is
+//! verified against the public key set of the parties as it arrives.
+//!
+//! In the reference and erasure coded modes the proposals are not c-sent, so
+//! the observer can't see them. A decision relayed by a party is taken in by
+//! [`Observer::process_decision`] instead.
+
+use std::collections::{BTreeMap, HashMap};
+
+use blsttc::{PublicKeySet, Signature, SignatureShare};
+use serde::Serialize;
+
+use super::{
+    abba::{self, message::Action as AbbaAction},
+    bundle::{Bundle, Message},
+    error::{Error, Result},
+    hash::Hash32,
+    mvba::{self as mvba_inner, message::Message as MvbaMessage},
+    tag::{Domain, Tag},
+    vcbc::{self, message::Action as VcbcAction},
+    Decision, MessageValidity, NodeId, Proof, Validity,
+};
+
+pub struct Observer<P> {
+    domain: Domain,
+    pub_key_set: PublicKeySet,
+    parties: Vec<NodeId>,
+    message_validity: Box<dyn MessageValidity<P>>,
+    // the first valid proposal c-sent by each proposer.
+    sent: HashMap<NodeId, P>,
+    // the proposals that the validity predicate could not judge yet.
+    pending: HashMap<NodeId, P>,
+    // the digest and c-final signature of each delivered proposal.
+    finals: HashMap<NodeId, (Hash32, Signature)>,
+    // the proposals that we know both the content and c-final signature of.
+    delivered: HashMap<NodeId, (P, Signature)>,
+    // the proposer that ABBA decided on, with the signature and round of the decision.
+    decided: Option<(NodeId, Signature, usize)>,
+    coin_shares: BTreeMap<NodeId, SignatureShare>,
+    coin: Option<Signature>,
+    decision: Option<Decision<P>>,
+}
+
+impl<P: Clone + Serialize> Observer<P> {
+    pub fn new(
+        domain: Domain,
+        pub_key_set: PublicKeySet,
+        parties: Vec<NodeId>,
+        message_validity: impl MessageValidity<P> + 'static,
+    ) -> Self {
+        Self {
+            domain,
+            pub_key_set,
+            parties,
+            message_validity: Box::new(message_validity),
+            sent: HashMap::new(),
+            pending: HashMap::new(),
+            finals: HashMap::new(),
+            delivered: HashMap::new(),
+            decided: None,
+            coin_shares: BTreeMap::new(),
+            coin: None,
+            decision: None,
+        }
+    }
+
+    pub fn domain(&self) -> &Domain {
+        &self.domain
+    }
+
+    pub fn pub_key_set(&self) -> PublicKeySet {
+        self.pub_key_set.clone()
+    }
+
+    /// Takes in a bundle gossiped by the parties.
+    ///
+    /// Messages that don't carry a piece of the proof are ignored.
+    pub fn process_bundle(&mut self, bundle: &Bundle<P>) -> Result<()> {
+        if bundle.domain() != &self.domain {
+            return Err(Error::WrongDomain {
+                expected: self.domain.clone(),
+                got: bundle.domain().clone(),
+            });
+        }
+        if self.decision.is_some() {
+            return Ok(());
+        }
+
+        match &bundle.message {
+            Message::Vcbc(msg) => {
+                let proposer = self.check_proposer(&msg.tag)?;
+                match &msg.action {
+                    VcbcAction::Send(proposal) => {
+                        if bundle.initiator == proposer
+                            && !self.sent.contains_key(&proposer)
+                            && !self.pending.contains_key(&proposer)
+                        {
+                            self.check_proposal(proposer, proposal.clone());
+                        }
+                    }
+                    VcbcAction::Final(digest, sig) => {
+                        let sign_bytes = vcbc::c_ready_bytes_to_sign(&msg.tag, digest)?;
+                        if !self.pub_key_set.public_key().verify(sig, sign_bytes) {
+                            return Err(Error::InvalidSignature(bundle.initiator));
+                        }
+                        self.finals.insert(proposer, (*digest, sig.clone()));
+                    }
+                    VcbcAction::Answer(proposal, sig) => {
+                        let pk = self.pub_key_set.public_key();
+                        if !vcbc::verify_delivered_proposal(&msg.tag, proposal, sig, &pk)? {
+                            return Err(Error::InvalidSignature(bundle.initiator));
+                        }
+                        self.delivered
+                            .insert(proposer, (proposal.clone(), sig.clone()));
+                    }
+                    // the proposal is not c-sent in reference mode
+                    VcbcAction::SendDigest(_) | VcbcAction::Ready(..) | VcbcAction::Request => {}
+                }
+                self.deliver(proposer)?;
+            }
+            Message::Abba(msg) => {
+                let proposer = self.check_proposer(&msg.tag)?;
+                if let AbbaAction::Decision(action) = &msg.action {
+                    // only the proposer that is accepted is part of the proof
+                    if action.value && self.decided.is_none() {
+                        let pk = self.pub_key_set.public_key();
+                        abba::verify_decided_proposal(&msg.tag, &action.sig, action.round, &pk)?;
+                        self.decided = Some((proposer, action.sig.clone(), action.round));
+                    }
+                }
+            }
+            Message::Mvba(MvbaMessage::CoinShare(msg)) => {
+                if self.coin.is_none() && !self.coin_shares.contains_key(&msg.sender) {
+                    let sign_bytes = mvba_inner::coin_bytes_to_sign(&self.domain)?;
+                    if !self
+                        .pub_key_set
+                        .public_key_share(msg.sender)
+                        .verify(&msg.sig_share, sign_bytes)
+                    {
+                        return Err(mvba_inner::error::Error::InvalidSignatureShare {
+                            from: msg.sender,
+                        }
+                        .into());
+                    }
+                    self.coin_shares.insert(msg.sender, msg.sig_share.clone());
+                    if self.coin_shares.len() > self.pub_key_set.threshold() {
+                        let coin = self
+                            .pub_key_set
+                            .combine_signatures(&self.coin_shares)
+                            .map_err(mvba_inner::error::Error::from)?;
+                        self.coin = Some(coin);
+                    }
+                }
+            }
+            // the observer follows the proposals through c-final and c-answer
+            Message::Mvba(MvbaMessage::Vote(_)) | Message::Avid(_) => {}
+        }
+
+        self.try_decide();
+        Ok(())
+    }
+
+    /// Takes in a decision relayed by a party, once its proof is checked.
+    pub fn process_decision(&mut self, decision: Decision<P>) -> Result<()> {
+        if decision.domain != self.domain {
+            return Err(Error::WrongDomain {
+                expected: self.domain.clone(),
+                got: decision.domain,
+            });
+        }
+        if self.decision.is_some() {
+            return Ok(());
+        }
+        self.check_proposer(&Tag::new(decision.domain.clone(), decision.proof.proposer))?;
+        if !decision.validate(&self.pub_key_set.public_key())? {
+            return Err(Error::InvalidDecision(decision.domain));
+        }
+
+        log::info!(
+            "observer took the decision on proposer {}",
+            decision.proof.proposer
+        );
+        self.decision = Some(decision);
+        Ok(())
+    }
+
+    /// The decided proposal with its proof, once we have seen all of it.
+    pub fn decided_proposal(&self) -> Option<Decision<P>> {
+        self.decision.clone()
+    }
+
+    /// Checks the proposals that the validity predicate could not judge
+    /// before, once the application has what it needs to judge them.
+    pub fn revalidate_pending(&mut self) -> Result<()> {
+        if self.decision.is_some() {
+            return Ok(());
+        }
+        for (proposer, proposal) in std::mem::take(&mut self.pending) {
+            self.check_proposal(proposer, proposal);
+            self.deliver(proposer)?;
+        }
+        self.try_decide();
+        Ok(())
+    }
+
+    // check_proposal keeps the c-sent proposal of `proposer` if it is valid.
+    fn check_proposal(&mut self, proposer: NodeId, proposal: P) {
+        match self.message_validity.validate(proposer, &proposal) {
+            Validity::Valid => {
+                self.sent.insert(proposer, proposal);
+            }
+            Validity::Invalid(reason) => {
+                log::debug!("observer ignored the proposal of {proposer}: {reason}");
+            }
+            Validity::Pending => {
+                self.pending.insert(proposer, proposal);
+            }
+        }
+    }
+
+    fn check_proposer(&self, tag: &Tag) -> Result<NodeId> {
+        if !self.parties.contains(&tag.proposer) {
+            return Err(Error::UnknownTarget(tag.proposer));
+        }
+        Ok(tag.proposer)
+    }
+
+    // deliver matches the c-sent proposal of `proposer` with its c-final signature.
+    fn deliver(&mut self, proposer: NodeId) -> Result<()> {
+        if self.delivered.contains_key(&proposer) {
+            return Ok(());
+        }
+        if let (Some(proposal), Some((digest, sig))) =
+            (self.sent.get(&proposer), self.finals.get(&proposer))
+        {
+            if &Hash32::calculate(proposal)? == digest {
+                self.delivered
+                    .insert(proposer, (proposal.clone(), sig.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn try_decide(&mut self) {
+        if let (Some((proposer, abba_signature, abba_round)), Some(coin)) =
+            (&self.decided, &self.coin)
+        {
+            if let Some((proposal, vcbc_signature)) = self.delivered.get(proposer) {
+                log::info!("observer followed the decision on proposer {proposer}");
+                self.decision = Some(Decision {
+                    domain: self.domain.clone(),
+                    proposal: proposal.clone(),
+                    proof: Proof {
+                        proposer: *proposer,
+                        abba_signature: abba_signature.clone(),
+                        abba_round: *abba_round,
+                        vcbc_signature: vcbc_signature.clone(),
+                        permutation_coin: coin.clone(),
+                    },
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blsttc::{SecretKey, SecretKeySet};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::Observer;
+    use crate::mvba::{
+        abba::message::Action,
+        bundle::{Bundle, Message, Outgoing},
+        consensus::{Consensus, Dissemination},
+        error::{Error, Severity},
+        tag::Domain,
+        NodeId,
+    };
+
+    fn valid_proposal(_id: NodeId, _: &char) -> bool {
+        true
+    }
+
+    struct TestNet {
+        cons: Vec<Consensus<char>>,
+        observer: Observer<char>,
+        sks: SecretKeySet,
+    }
+
+    impl TestNet {
+        fn new(rng: &mut StdRng) -> Self {
+            let domain = Domain::new("test-domain", 0);
+            let sks = SecretKeySet::random(2, rng);
+            let parties = Vec::from_iter(0..4);
+            let cons = Vec::from_iter(parties.iter().map(|p| {
+                Consensus::init(
+                    domain.clone(),
+                    *p,
+                    sks.secret_key_share(p),
+                    sks.public_keys(),
+                    parties.clone(),
+                    valid_proposal,
+                )
+            }));
+            let observer = Observer::new(domain, sks.public_keys(), parties, valid_proposal);
+
+            Self {
+                cons,
+                observer,
+                sks,
+            }
+        }
+
+        // runs the consensus to the end, the observer sees all the gossips
+        fn run(&mut self, rng: &mut StdRng) -> Vec<Bundle<char>> {
+            let mut buffer = Vec::new();
+            for c in &mut self.cons {
+                buffer.append(&mut c.propose(rng.gen()).unwrap());
+            }
+
+            let mut gossips = Vec::new();
+            while !buffer.is_empty() {
+                let outgoing = buffer.remove(rng.gen_range(0..buffer.len()));
+                for c in &mut self.cons {
+                    match &outgoing {
+                        Outgoing::Direct(id, bundle) if id == &c.self_id() => {
+                            buffer.append(&mut c.process_bundle(bundle).unwrap())
+                        }
+                        Outgoing::Direct(..) => {}
+                        Outgoing::Gossip(bundle) => {
+                            buffer.append(&mut c.process_bundle(bundle).unwrap())
+                        }
+                    }
+                }
+                if let Outgoing::Gossip(bundle) = outgoing {
+                    self.observer.process_bundle(&bundle).unwrap();
+                    gossips.push(bundle);
+                }
+            }
+            gossips
+        }
+    }
+
+    #[test]
+    fn test_observer_follows_decision() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let mut net = TestNet::new(&mut rng);
+        net.run(&mut rng);
+
+        let decision = net.observer.decided_proposal().unwrap();
+        assert!(decision
+            .validate(&net.sks.public_keys().public_key())
+            .unwrap());
+        for c in &net.cons {
+            let decided = c.decided_proposal().unwrap();
+            assert_eq!(decided.proposal, decision.proposal);
+            assert_eq!(decided.proof.proposer, decision.proof.proposer);
+        }
+    }
+
+    // the proposals are not c-sent, the observer needs a relayed decision
+    fn check_relayed_decision(dissemination: Dissemination, seed: u8) {
+        let mut rng = StdRng::from_seed([seed; 32]);
+        let mut net = TestNet::new(&mut rng);
+        for c in &mut net.cons {
+            c.set_dissemination(dissemination);
+        }
+        net.run(&mut rng);
+        assert!(net.observer.decided_proposal().is_none());
+
+        let decision = net.cons[0].decided_proposal().unwrap();
+        let mut forged = decision.clone();
+        forged.proof.proposer = (forged.proof.proposer + 1) % 4;
+        let err = net.observer.process_decision(forged).unwrap_err();
+        assert!(matches!(err, Error::InvalidDecision(_)));
+        assert!(net.observer.decided_proposal().is_none());
+
+        net.observer.process_decision(decision.clone()).unwrap();
+        assert_eq!(net.observer.decided_proposal(), Some(decision));
+    }
+
+    #[test]
+    fn test_observer_reference_mode() {
+        check_relayed_decision(Dissemination::Reference, 3);
+    }
+
+    #[test]
+    fn test_observer_erasure_coded() {
+        check_relayed_decision(Dissemination::ErasureCoded, 4);
+    }
+
+    #[test]
+    fn test_observer_rejects_forged_decision() {
+        let mut rng = StdRng::from_seed([1u8; 32]);
+        let mut net = TestNet::new(&mut rng);
+        let gossips = net.run(&mut rng);
+
+        let mut observer = Observer::new(
+            Domain::new("test-domain", 0),
+            net.sks.public_keys(),
+            Vec::from_iter(0..4),
+            valid_proposal,
+        );
+        let forger = SecretKey::random();
+        for mut bundle in gossips {
+            if let Message::Abba(msg) = &mut bundle.message {
+                if let Action::Decision(action) = &mut msg.action {
+                    action.sig = forger.sign("forged decision");
+                    let err = observer.process_bundle(&bundle).unwrap_err();
+                    assert_eq!(err.severity(), Severity::Penalize);
+                    continue;
+                }
+            }
+            observer.process_bundle(&bundle).unwrap();
+        }
+        assert!(observer.decided_proposal().is_none());
+    }
+
+    #[test]
+    fn test_observer_drops_other_domains() {
+        let mut rng = StdRng::from_seed([2u8; 32]);
+        let mut net = TestNet::new(&mut rng);
+        let gossips = net.run(&mut rng);
+
+        let mut observer = Observer::new(
+            Domain::new("test-domain", 1),
+            net.sks.public_keys(),
+            Vec::from_iter(0..4),
+            valid_proposal,
+        );
+        for bundle in gossips {
+            let err = observer.process_bundle(&bundle).unwrap_err();
+            assert!(matches!(err, Error::WrongDomain { .. }));
+            assert_eq!(err.severity(), Severity::Drop);
+        }
+        assert!(observer.decided_proposal().is_none());
+    }
+}
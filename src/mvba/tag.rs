@@ -41,7 +41,7 @@ impl Debug for Tag {
 }
 
 impl Tag {
-    pub fn new(domain: Domain, proposer: usize) -> Self {
+    pub fn new(domain: Domain, proposer: NodeId) -> Self {
         Self { domain, proposer }
     }
 }
@@ -1,4 +1,6 @@
-use crate::mvba::{hash, NodeId};
+use super::message::ActionKind;
+use crate::mvba::error::Severity;
+use crate::mvba::{hash, hash::Hash32, tag::Tag, NodeId};
 use core::fmt::Debug;
 use thiserror::Error;
 
@@ -10,12 +12,36 @@ pub enum Error {
     Blsttc(#[from] blsttc::error::Error),
     #[error("invalid hash length {0}")]
     InvalidHashLength(#[from] hash::InvalidLength),
-    #[error("duplicated message {0} from {1:?}")]
-    DuplicatedMessage(NodeId, String),
-    #[error("invalid message {0}")]
-    InvalidMessage(String),
-    #[error("generic error {0}")]
-    Generic(String),
+    #[error("duplicated {1} message from {0}")]
+    DuplicatedMessage(NodeId, ActionKind),
+    #[error("invalid tag. expected {expected}, got {got}")]
+    WrongTag { expected: Tag, got: Tag },
+    #[error("invalid proposal: {0}")]
+    InvalidProposal(crate::mvba::Rejection),
+    #[error("received the proposal digest out of reference mode")]
+    UnexpectedDigest,
+    #[error("received c-ready before the proposal")]
+    MissingDigest,
+    #[error("c-ready for an unknown digest. expected {expected:?}, got {got:?}")]
+    UnknownDigest { expected: Hash32, got: Hash32 },
+    #[error("proposal for another digest. expected {expected:?}, got {got:?}")]
+    DigestMismatch { expected: Hash32, got: Hash32 },
+}
+
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::Encoding(_) | Error::Blsttc(_) => Severity::Fatal,
+            Error::DuplicatedMessage(..) | Error::WrongTag { .. } | Error::MissingDigest => {
+                Severity::Drop
+            }
+            Error::InvalidHashLength(_)
+            | Error::InvalidProposal(_)
+            | Error::UnexpectedDigest
+            | Error::UnknownDigest { .. }
+            | Error::DigestMismatch { .. } => Severity::Penalize,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
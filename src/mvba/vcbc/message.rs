@@ -8,6 +8,7 @@ pub enum Action<P> {
     Final(Hash32, Signature),      // this is same as $c-final$ in spec
     Request,                       // this is same as $c-request$ in spec
     Answer(P, Signature),          // this is same as $c-answer$ in spec
+    SendDigest(Hash32),            // c-send of the proposal digest, in reference mode
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -16,14 +17,49 @@ pub struct Message<P> {
     pub action: Action<P>,
 }
 
+/// The kind of a VCBC message, without its content.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ActionKind {
+    Send,
+    Ready,
+    Final,
+    Request,
+    Answer,
+    SendDigest,
+}
+
+impl ActionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionKind::Send => "c-send",
+            ActionKind::Ready => "c-ready",
+            ActionKind::Final => "c-final",
+            ActionKind::Request => "c-request",
+            ActionKind::Answer => "c-answer",
+            ActionKind::SendDigest => "c-send-digest",
+        }
+    }
+}
+
+impl std::fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl<P> Message<P> {
-    pub fn action_str(&self) -> &str {
+    pub fn kind(&self) -> ActionKind {
         match self.action {
-            Action::Send(_) => "c-send",
-            Action::Ready(_, _) => "c-ready",
-            Action::Final(_, _) => "c-final",
-            Action::Request => "c-request",
-            Action::Answer(_, _) => "c-answer",
+            Action::Send(_) => ActionKind::Send,
+            Action::Ready(_, _) => ActionKind::Ready,
+            Action::Final(_, _) => ActionKind::Final,
+            Action::Request => ActionKind::Request,
+            Action::Answer(_, _) => ActionKind::Answer,
+            Action::SendDigest(_) => ActionKind::SendDigest,
         }
     }
+
+    pub fn action_str(&self) -> &str {
+        self.kind().as_str()
+    }
 }
@@ -2,17 +2,19 @@ pub(crate) mod error;
 pub(crate) mod message;
 
 use std::collections::hash_map::Entry::Vacant;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use blsttc::{PublicKey, PublicKeySet, SecretKeyShare, Signature, SignatureShare};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use self::error::{Error, Result};
 use self::message::{Action, Message};
+use super::fault::Fault;
 use super::hash::Hash32;
 use super::tag::Tag;
-use super::{bundle, MessageValidity, NodeId};
+use super::{bundle, MessageValidity, NodeId, Validity};
 use crate::mvba::broadcaster::Broadcaster;
 
 // make_c_request_message creates the payload message to request a proposal
@@ -43,7 +45,7 @@ pub fn c_ready_bytes_to_sign(
     tag: &Tag,
     digest: &Hash32,
 ) -> std::result::Result<Vec<u8>, bincode::Error> {
-    bincode::serialize(&(tag, "c-ready", digest))
+    crate::signing::signing_bytes(&(tag, "c-ready", digest))
 }
 
 // Protocol VCBC for verifiable and authenticated consistent broadcast.
@@ -58,7 +60,35 @@ pub(crate) struct Vcbc<P> {
     pub_key_set: PublicKeySet,
     sec_key_share: SecretKeyShare,
     final_messages: HashMap<NodeId, Message<P>>,
-    message_validity: MessageValidity<P>,
+    message_validity: Arc<dyn MessageValidity<P>>,
+    // the proposal that the validity predicate could not judge yet.
+    pending: Option<P>,
+    // valid c-ready shares for a digest other than ours, kept as evidence.
+    other_readys: HashMap<NodeId, (Hash32, SignatureShare)>,
+    faults: BTreeMap<NodeId, Fault>,
+    // checks the c-ready shares once combined, instead of one by one on arrival.
+    batch_verification: bool,
+    // c-broadcasts the digest of the proposal instead of the proposal itself.
+    reference_mode: bool,
+}
+
+/// The state of a VCBC instance, as kept in a snapshot.
+/// The keys and the validity predicate are not part of it.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct State<P> {
+    tag: Tag,
+    i: NodeId,
+    m_bar: Option<P>,
+    u_bar: Option<Signature>,
+    wd: HashMap<NodeId, SignatureShare>,
+    rd: usize,
+    d: Option<Hash32>,
+    final_messages: HashMap<NodeId, Message<P>>,
+    pending: Option<P>,
+    other_readys: HashMap<NodeId, (Hash32, SignatureShare)>,
+    faults: BTreeMap<NodeId, Fault>,
+    batch_verification: bool,
+    reference_mode: bool,
 }
 
 /// Tries to insert a key-value pair into the map.
@@ -71,7 +101,7 @@ fn try_insert<P>(map: &mut HashMap<NodeId, Message<P>>, k: NodeId, v: Message<P>
         e.insert(v);
         Ok(())
     } else {
-        Err(Error::DuplicatedMessage(k, v.action_str().to_string()))
+        Err(Error::DuplicatedMessage(k, v.kind()))
     }
 }
 
@@ -81,7 +111,7 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
         self_id: NodeId,
         pub_key_set: PublicKeySet,
         sec_key_share: SecretKeyShare,
-        message_validity: MessageValidity<P>,
+        message_validity: Arc<dyn MessageValidity<P>>,
     ) -> Self {
         Self {
             tag,
@@ -95,9 +125,82 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
             pub_key_set,
             sec_key_share,
             message_validity,
+            pending: None,
+            other_readys: HashMap::new(),
+            faults: BTreeMap::new(),
+            batch_verification: false,
+            reference_mode: false,
+        }
+    }
+
+    /// restore rebuilds the instance from the `state` of its snapshot.
+    pub fn restore(
+        state: State<P>,
+        pub_key_set: PublicKeySet,
+        sec_key_share: SecretKeyShare,
+        message_validity: Arc<dyn MessageValidity<P>>,
+    ) -> Self {
+        Self {
+            tag: state.tag,
+            i: state.i,
+            m_bar: state.m_bar,
+            u_bar: state.u_bar,
+            wd: state.wd,
+            rd: state.rd,
+            d: state.d,
+            final_messages: state.final_messages,
+            pub_key_set,
+            sec_key_share,
+            message_validity,
+            pending: state.pending,
+            other_readys: state.other_readys,
+            faults: state.faults,
+            batch_verification: state.batch_verification,
+            reference_mode: state.reference_mode,
+        }
+    }
+
+    /// snapshot returns the state of the instance, without the keys.
+    pub fn snapshot(&self) -> State<P> {
+        State {
+            tag: self.tag.clone(),
+            i: self.i,
+            m_bar: self.m_bar.clone(),
+            u_bar: self.u_bar.clone(),
+            wd: self.wd.clone(),
+            rd: self.rd,
+            d: self.d,
+            final_messages: self.final_messages.clone(),
+            pending: self.pending.clone(),
+            other_readys: self.other_readys.clone(),
+            faults: self.faults.clone(),
+            batch_verification: self.batch_verification,
+            reference_mode: self.reference_mode,
         }
     }
 
+    /// In batch verification mode, the proposer collects the c-ready shares
+    /// without checking them, and checks the signature they combine into.
+    /// The shares are checked one by one only if it is invalid.
+    pub fn set_batch_verification(&mut self, enabled: bool) {
+        self.batch_verification = enabled;
+    }
+
+    /// In reference mode, the proposer c-broadcasts the digest of its proposal
+    /// and the proposal is delivered apart, by `deliver_dispersed`.
+    /// The parties certify the digest only once they have a valid proposal for
+    /// it, so a certified proposal can always be fetched. The parties that
+    /// don't have it request it, and check the answer with the validity
+    /// predicate too.
+    pub fn set_reference_mode(&mut self, enabled: bool) {
+        self.reference_mode = enabled;
+    }
+
+    /// The faults detected in this VCBC instance, keyed by the party at fault.
+    pub fn faults(&self) -> &BTreeMap<NodeId, Fault> {
+        &self.faults
+    }
+
     /// c_broadcast sends the messages `m` to all other parties.
     /// It also adds the message to message_log and process it.
     pub fn c_broadcast(&mut self, m: P, broadcaster: &mut Broadcaster<P>) -> Result<()> {
@@ -105,9 +208,17 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
 
         // Upon receiving message (ID.j.s, in, c-broadcast, m):
         // send (ID.j.s, c-send, m) to all parties
+        let action = if self.reference_mode {
+            // the proposer keeps the proposal to answer the requests for it
+            let d = Hash32::calculate(&m)?;
+            self.m_bar = Some(m);
+            Action::SendDigest(d)
+        } else {
+            Action::Send(m)
+        };
         let send_msg = Message {
             tag: self.tag.clone(),
-            action: Action::Send(m),
+            action,
         };
         self.broadcast(send_msg, broadcaster)
     }
@@ -128,59 +239,83 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
         );
 
         if msg.tag != self.tag {
-            return Err(Error::InvalidMessage(format!(
-                "invalid tag. expected {}, got {}",
-                self.tag, msg.tag
-            )));
+            return Err(Error::WrongTag {
+                expected: self.tag.clone(),
+                got: msg.tag,
+            });
         }
 
         match msg.action.clone() {
             Action::Send(m) => {
                 // Upon receiving message (ID.j.s, c-send, m) from Pl:
                 // if j = l and m̄ = ⊥ then
-                if initiator == self.tag.proposer && self.m_bar.is_none() {
-                    if !(self.message_validity)(initiator, &m) {
-                        return Err(Error::InvalidMessage("invalid proposal".to_string()));
-                    }
-                    // m̄ ← m
-                    self.m_bar = Some(m.clone());
-
-                    let d = Hash32::calculate(&m)?;
+                if initiator == self.tag.proposer && self.m_bar.is_none() && self.pending.is_none()
+                {
+                    self.check_proposal(m, broadcaster)?;
+                }
+            }
+            Action::SendDigest(d) => {
+                if !self.reference_mode {
+                    return Err(Error::UnexpectedDigest);
+                }
+                if initiator == self.tag.proposer && self.d.is_none() {
                     self.d = Some(d);
-
-                    // compute an S1-signature share ν on (ID.j.s, c-ready, H(m))
-                    let sign_bytes = c_ready_bytes_to_sign(&self.tag, &d)?;
-                    let s1 = self.sec_key_share.sign(sign_bytes);
-
-                    let ready_msg = Message {
-                        tag: self.tag.clone(),
-                        action: Action::Ready(d, s1),
-                    };
-
-                    // send (ID.j.s, c-ready, H(m), ν) to Pj
-                    self.send_to(ready_msg, self.tag.proposer, broadcaster)?;
+                    // the digest is certified once we have the proposal,
+                    // only the proposer has it already.
+                    if let Some(m) = &self.m_bar {
+                        let expected = Hash32::calculate(m)?;
+                        if expected != d {
+                            return Err(Error::DigestMismatch { expected, got: d });
+                        }
+                        self.send_ready(d, broadcaster)?;
+                    }
                 }
             }
             Action::Ready(msg_d, sig_share) => {
                 let d = match self.d {
                     Some(d) => d,
-                    None => return Err(Error::Generic("protocol violated. no digest".to_string())),
+                    None => return Err(Error::MissingDigest),
                 };
                 let sign_bytes = c_ready_bytes_to_sign(&self.tag, &d)?;
 
                 if d != msg_d {
                     log::warn!("party {} received c-ready with unknown digest. expected {d:?}, got {msg_d:?}", self.i);
-                    return Err(Error::Generic("Invalid digest".to_string()));
+                    self.check_changed_ready(initiator, msg_d, sig_share)?;
+                    return Err(Error::UnknownDigest {
+                        expected: d,
+                        got: msg_d,
+                    });
+                }
+
+                // In batch verification mode the shares in Wd were not checked on
+                // arrival, an invalid share gives way to the next share of its sender.
+                if self.batch_verification {
+                    if let Some(kept) = self.wd.get(&initiator) {
+                        if kept != &sig_share
+                            && !self
+                                .pub_key_set
+                                .public_key_share(initiator)
+                                .verify(kept, &sign_bytes)
+                        {
+                            self.wd.remove(&initiator);
+                            self.rd -= 1;
+                        }
+                    }
                 }
 
                 // Upon receiving message (ID.j.s, c-ready, d, νl) from Pl for the first time:
                 if let Vacant(e) = self.wd.entry(initiator) {
-                    let valid_sig = self
-                        .pub_key_set
-                        .public_key_share(initiator)
-                        .verify(&sig_share, sign_bytes);
+                    // only the proposer collects the shares, the shares are
+                    // checked once combined in batch verification mode.
+                    let valid_sig = if self.batch_verification {
+                        self.i == msg.tag.proposer
+                    } else {
+                        self.pub_key_set
+                            .public_key_share(initiator)
+                            .verify(&sig_share, &sign_bytes)
+                    };
 
-                    if !valid_sig {
+                    if !valid_sig && !self.batch_verification {
                         log::warn!(
                             "party {} received c-ready with invalid signature share",
                             self.i
@@ -190,7 +325,18 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
                     // if i = j and νl is a valid S1-signature share then
                     if self.i == msg.tag.proposer && valid_sig {
                         // Wd ← Wd ∪ {νl}
-                        e.insert(sig_share);
+                        e.insert(sig_share.clone());
+                        if let Some(other) = self.other_readys.remove(&initiator) {
+                            // the evidence has to hold valid shares
+                            if !self.batch_verification
+                                || self
+                                    .pub_key_set
+                                    .public_key_share(initiator)
+                                    .verify(&sig_share, &sign_bytes)
+                            {
+                                self.add_fault(initiator, (d, sig_share), other);
+                            }
+                        }
 
                         //  rd ← rd + 1
                         self.rd += 1;
@@ -199,7 +345,10 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
                         // spec: if rd = n+t+1/2 then
                         if self.rd >= self.threshold() {
                             // combine the shares in Wd to an S1 -threshold signature µ
-                            let sig = self.pub_key_set.combine_signatures(self.wd.iter())?;
+                            let sig = match self.combine_ready_shares(&sign_bytes)? {
+                                Some(sig) => sig,
+                                None => return Ok(()),
+                            };
 
                             let final_msg = Message {
                                 tag: self.tag.clone(),
@@ -216,6 +365,15 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
                 // Upon receiving message (ID.j.s, c-final, d, µ):
                 let d = match self.d {
                     Some(d) => d,
+                    None if self.reference_mode => {
+                        // the digest is all we need, c-final certifies it
+                        let sign_bytes = c_ready_bytes_to_sign(&self.tag, &msg_d)?;
+                        if self.pub_key_set.public_key().verify(&sig, sign_bytes) {
+                            self.d = Some(msg_d);
+                            self.u_bar = Some(sig);
+                        }
+                        return Ok(());
+                    }
                     None => {
                         log::warn!(
                             "party {} received c-final before receiving c-send, logging message",
@@ -253,32 +411,50 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
                 // Upon receiving message (ID.j.s, c-request) from Pl :
                 if let Some(u) = &self.u_bar {
                     // if µ̄  != ⊥ then
-
-                    // proposal is known because we have the valid signature
-                    debug_assert!(self.m_bar.is_some());
-                    if let Some(m) = &self.m_bar {
-                        let answer_msg = Message {
-                            tag: self.tag.clone(),
-                            action: Action::Answer(m.clone(), u.clone()),
-                        };
-
+                    let action = match (&self.m_bar, self.d) {
                         // send (ID.j.s, c-answer, m̄, µ̄) to Pl
-                        self.send_to(answer_msg, initiator, broadcaster)?;
-                    }
+                        (Some(m), _) => Action::Answer(m.clone(), u.clone()),
+                        // in reference mode we may only know the digest,
+                        // it is certified by c-final
+                        (None, Some(d)) if self.reference_mode => Action::Final(d, u.clone()),
+                        _ => return Ok(()),
+                    };
+                    let answer_msg = Message {
+                        tag: self.tag.clone(),
+                        action,
+                    };
+                    self.send_to(answer_msg, initiator, broadcaster)?;
                 }
             }
             Action::Answer(m, u) => {
                 // Upon receiving message (ID.j.s, c-answer, m, µ) from Pl :
-                if self.u_bar.is_none() {
+                // in reference mode µ̄ can be known without m̄
+                if self.read_delivered().is_none() {
                     // if µ̄ = ⊥ and ...
                     let d = Hash32::calculate(&m)?;
                     let sign_bytes = c_ready_bytes_to_sign(&self.tag, &d)?;
                     if self.pub_key_set.public_key().verify(&u, sign_bytes) {
                         // ... µ is a valid S1 -signature on (ID.j.s, c-ready, H(m)) then
+                        // the parties certify only valid proposals, but in
+                        // reference mode we may not have checked it yet.
+                        match self.message_validity.validate(self.tag.proposer, &m) {
+                            Validity::Valid => {}
+                            Validity::Invalid(reason) => {
+                                return Err(Error::InvalidProposal(reason))
+                            }
+                            Validity::Pending => {
+                                log::debug!(
+                                    "party {} can't judge the answer of {initiator} yet",
+                                    self.i
+                                );
+                                return Ok(());
+                            }
+                        }
                         // µ̄ ← µ
                         // m̄ ← m
                         self.u_bar = Some(u);
                         self.m_bar = Some(m);
+                        self.d = Some(d);
                     }
                 }
             }
@@ -291,6 +467,175 @@ impl<P: Debug + Clone + Serialize + Eq> Vcbc<P> {
         Ok(())
     }
 
+    /// deliver_dispersed processes the proposal `m` that is rebuilt from the
+    /// fragments dispersed by the proposer, the same as c-send from it.
+    pub fn deliver_dispersed(&mut self, m: P, broadcaster: &mut Broadcaster<P>) -> Result<()> {
+        if self.m_bar.is_some() || self.pending.is_some() {
+            return Ok(());
+        }
+        self.check_proposal(m, broadcaster)?;
+
+        for (initiator, final_msg) in std::mem::take(&mut self.final_messages) {
+            self.receive_message(initiator, final_msg, broadcaster)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the pending proposal again, if the validity predicate could not
+    /// judge it before. Returns `InvalidProposal` if it is rejected now.
+    pub fn revalidate_pending(&mut self, broadcaster: &mut Broadcaster<P>) -> Result<()> {
+        if let Some(m) = self.pending.take() {
+            self.check_proposal(m, broadcaster)?;
+
+            for (initiator, final_msg) in std::mem::take(&mut self.final_messages) {
+                self.receive_message(initiator, final_msg, broadcaster)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the proposal is waiting for the validity predicate.
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // check_proposal checks the proposal `m` with the validity predicate and,
+    // if it is valid, sends our c-ready share for it to the proposer.
+    fn check_proposal(&mut self, m: P, broadcaster: &mut Broadcaster<P>) -> Result<()> {
+        let d = Hash32::calculate(&m)?;
+        // in reference mode the proposer c-sent the digest of its proposal
+        if let Some(expected) = self.d {
+            if expected != d {
+                return Err(Error::DigestMismatch { expected, got: d });
+            }
+        }
+
+        match self.message_validity.validate(self.tag.proposer, &m) {
+            Validity::Valid => {}
+            Validity::Invalid(reason) => return Err(Error::InvalidProposal(reason)),
+            Validity::Pending => {
+                log::debug!(
+                    "party {} can't judge the proposal of {} yet",
+                    self.i,
+                    self.tag.proposer
+                );
+                self.pending = Some(m);
+                return Ok(());
+            }
+        }
+
+        // m̄ ← m
+        self.m_bar = Some(m);
+        self.d = Some(d);
+        self.send_ready(d, broadcaster)
+    }
+
+    // send_ready sends our c-ready share for the digest `d` to the proposer.
+    fn send_ready(&mut self, d: Hash32, broadcaster: &mut Broadcaster<P>) -> Result<()> {
+        // compute an S1-signature share ν on (ID.j.s, c-ready, H(m))
+        let sign_bytes = c_ready_bytes_to_sign(&self.tag, &d)?;
+        let s1 = self.sec_key_share.sign(sign_bytes);
+
+        let ready_msg = Message {
+            tag: self.tag.clone(),
+            action: Action::Ready(d, s1),
+        };
+
+        // send (ID.j.s, c-ready, H(m), ν) to Pj
+        self.send_to(ready_msg, self.tag.proposer, broadcaster)
+    }
+
+    // combine_ready_shares combines the shares in Wd. In batch verification
+    // mode the combined signature is checked, if it is invalid the invalid shares
+    // are removed from Wd and we wait for more shares.
+    fn combine_ready_shares(&mut self, sign_bytes: &[u8]) -> Result<Option<Signature>> {
+        let sig = self.pub_key_set.combine_signatures(self.wd.iter())?;
+        if !self.batch_verification || self.pub_key_set.public_key().verify(&sig, sign_bytes) {
+            return Ok(Some(sig));
+        }
+
+        let invalid = Vec::from_iter(
+            self.wd
+                .iter()
+                .filter(|(id, share)| {
+                    !self
+                        .pub_key_set
+                        .public_key_share(*id)
+                        .verify(share, sign_bytes)
+                })
+                .map(|(id, _)| *id),
+        );
+        for id in invalid {
+            log::warn!(
+                "party {} received c-ready with invalid signature share from {id}",
+                self.i
+            );
+            self.wd.remove(&id);
+            self.rd -= 1;
+        }
+
+        if self.rd >= self.threshold() {
+            Ok(Some(self.pub_key_set.combine_signatures(self.wd.iter())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // check_changed_ready keeps the valid c-ready shares for a digest other
+    // than ours, as they prove the party at fault if it also sent c-ready for our digest.
+    fn check_changed_ready(
+        &mut self,
+        initiator: NodeId,
+        digest: Hash32,
+        sig_share: SignatureShare,
+    ) -> Result<()> {
+        let sign_bytes = c_ready_bytes_to_sign(&self.tag, &digest)?;
+        if !self
+            .pub_key_set
+            .public_key_share(initiator)
+            .verify(&sig_share, sign_bytes)
+        {
+            return Ok(());
+        }
+
+        match (self.d, self.wd.get(&initiator)) {
+            (Some(d), Some(ours)) => {
+                let ours = ours.clone();
+                self.add_fault(initiator, (d, ours), (digest, sig_share));
+            }
+            _ => {
+                self.other_readys
+                    .entry(initiator)
+                    .or_insert((digest, sig_share));
+            }
+        }
+        Ok(())
+    }
+
+    fn add_fault(
+        &mut self,
+        voter: NodeId,
+        a: (Hash32, SignatureShare),
+        b: (Hash32, SignatureShare),
+    ) {
+        log::warn!(
+            "party {} detected c-ready equivocation from {voter}",
+            self.i
+        );
+        self.faults.entry(voter).or_insert(Fault::ChangedReady {
+            tag: self.tag.clone(),
+            voter,
+            a,
+            b,
+        });
+    }
+
+    /// The digest of the delivered proposal with its signature. In reference
+    /// mode the digest can be delivered before the proposal.
+    pub fn read_delivered_digest(&self) -> Option<(Hash32, Signature)> {
+        Some((self.d?, self.u_bar.clone()?))
+    }
+
     pub fn read_delivered(&self) -> Option<(P, Signature)> {
         if let (Some(proposal), Some(sig)) = (self.m_bar.clone(), self.u_bar.clone()) {
             Some((proposal, sig))
@@ -8,6 +8,7 @@ use crate::mvba::vcbc::c_ready_bytes_to_sign;
 use blsttc::SecretKeySet;
 use quickcheck_macros::quickcheck;
 use std::collections::BTreeMap;
+use std::sync::Arc;
 
 fn valid_proposal(_: NodeId, _: &char) -> bool {
     true
@@ -34,7 +35,7 @@ impl Net {
         let public_key_set = secret_key_set.public_keys();
         let domain = Domain::new("testing-vcbc", 0);
 
-        let nodes = BTreeMap::from_iter((1..=n).map(|self_id| {
+        let nodes = BTreeMap::from_iter((1..=n as NodeId).map(|self_id| {
             let key_share = secret_key_set.secret_key_share(self_id);
             let broadcaster = Broadcaster::new(self_id);
 
@@ -44,7 +45,7 @@ impl Net {
                 self_id,
                 public_key_set.clone(),
                 key_share,
-                valid_proposal,
+                Arc::new(valid_proposal),
             );
             (self_id, (vcbc, broadcaster))
         }));
@@ -158,12 +159,12 @@ fn test_vcbc_happy_path() {
 #[quickcheck]
 fn prop_vcbc_terminates_under_randomized_msg_delivery(
     n: usize,
-    proposer: usize,
+    proposer: NodeId,
     proposal: char,
     msg_order: Vec<(NodeId, usize)>,
 ) {
     let n = n % 10 + 1; // Large n is wasteful, and n must be > 0
-    let proposer = proposer % n + 1; // NodeId's start at 1
+    let proposer = proposer % n as NodeId + 1; // NodeId's start at 1
     let mut net = Net::new(n, proposer);
 
     // First the proposer will initiate VCBC by broadcasting the proposal:
@@ -1,15 +1,18 @@
-use super::message::{Action, Message};
+use super::message::{Action, ActionKind, Message};
 use super::Error;
 use super::{NodeId, Vcbc};
 use crate::mvba::broadcaster::Broadcaster;
 
 use crate::mvba::bundle;
+use crate::mvba::fault::Fault;
 use crate::mvba::hash::Hash32;
 use crate::mvba::tag::{Domain, Tag};
 use crate::mvba::vcbc::c_ready_bytes_to_sign;
+use crate::mvba::{MessageValidity, Validity};
 use blsttc::{SecretKeySet, Signature, SignatureShare};
 
 use rand::{thread_rng, Rng};
+use std::sync::{Arc, Mutex};
 
 fn valid_proposal(_: NodeId, _: &char) -> bool {
     true
@@ -46,7 +49,7 @@ impl TestNet {
             i,
             sec_key_set.public_keys(),
             sec_key_share,
-            valid_proposal,
+            Arc::new(valid_proposal),
         );
 
         // Creating a random proposal
@@ -129,10 +132,10 @@ fn test_ignore_messages_with_invalid_tag() {
         .vcbc
         .receive_message(TestNet::PARTY_B, final_msg, &mut t.broadcaster);
     match result {
-        Err(Error::InvalidMessage(msg)) => assert_eq!(
-            msg,
-            "invalid tag. expected test-domain[0].0, got another-domain[0].0"
-        ),
+        Err(Error::WrongTag { expected, got }) => {
+            assert_eq!(expected.to_string(), "test-domain[0].0");
+            assert_eq!(got.to_string(), "another-domain[0].0");
+        }
         res => panic!("Unexpected result: {res:?}"),
     }
 }
@@ -142,15 +145,169 @@ fn test_invalid_message() {
     let i = TestNet::PARTY_X;
     let j = TestNet::PARTY_B;
     let mut t = TestNet::new(i, j);
-    t.vcbc.message_validity = invalid_proposal;
+    t.vcbc.message_validity = Arc::new(invalid_proposal);
 
     let msg = t.make_send_msg(t.m);
 
     let result = t
         .vcbc
         .receive_message(TestNet::PARTY_B, msg, &mut t.broadcaster);
-    assert!(matches!(result, Err(Error::InvalidMessage(msg))
-    if msg == *"invalid proposal"));
+    assert!(matches!(result, Err(Error::InvalidProposal(_))));
+}
+
+// KnownProposals judges the proposals it knows, the others are pending.
+struct KnownProposals(Arc<Mutex<Vec<char>>>);
+
+impl MessageValidity<char> for KnownProposals {
+    fn validate(&self, _: NodeId, proposal: &char) -> Validity {
+        if self.0.lock().unwrap().contains(proposal) {
+            Validity::Valid
+        } else {
+            Validity::Pending
+        }
+    }
+}
+
+#[test]
+fn test_pending_proposal() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_S;
+    let mut t = TestNet::new(i, j);
+    let known = Arc::new(Mutex::new(Vec::new()));
+    t.vcbc.message_validity = Arc::new(KnownProposals(known.clone()));
+
+    let send_msg = t.make_send_msg(t.m());
+    let final_msg = t.make_final_msg(&t.d());
+    t.vcbc
+        .receive_message(j, send_msg, &mut t.broadcaster)
+        .unwrap();
+    t.vcbc
+        .receive_message(j, final_msg, &mut t.broadcaster)
+        .unwrap();
+
+    let ready_msg_x = t.make_ready_msg(&t.d(), &i);
+    assert!(t.vcbc.has_pending());
+    assert!(!t.is_send_to(&j, &ready_msg_x));
+    assert!(t.vcbc.read_delivered().is_none());
+
+    // still unknown
+    t.vcbc.revalidate_pending(&mut t.broadcaster).unwrap();
+    assert!(t.vcbc.has_pending());
+
+    known.lock().unwrap().push(t.m());
+    t.vcbc.revalidate_pending(&mut t.broadcaster).unwrap();
+    assert!(!t.vcbc.has_pending());
+    assert!(t.is_send_to(&j, &ready_msg_x));
+    assert_eq!(t.vcbc.read_delivered(), Some((t.m(), t.u())));
+}
+
+#[test]
+fn test_reference_mode() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_S;
+    let mut t = TestNet::new(i, j);
+
+    let digest_msg = Message {
+        tag: t.vcbc.tag.clone(),
+        action: Action::SendDigest(t.d()),
+    };
+    let result = t
+        .vcbc
+        .receive_message(j, digest_msg.clone(), &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::UnexpectedDigest)));
+
+    // the digest is certified only once we have the proposal for it
+    t.vcbc.set_reference_mode(true);
+    t.vcbc
+        .receive_message(j, digest_msg, &mut t.broadcaster)
+        .unwrap();
+    let ready_msg = t.make_ready_msg(&t.d(), &i);
+    assert!(!t.is_send_to(&j, &ready_msg));
+
+    let other = if t.m() == 'a' { 'b' } else { 'a' };
+    let result = t.vcbc.deliver_dispersed(other, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::DigestMismatch { .. })));
+    assert!(!t.is_send_to(&j, &ready_msg));
+
+    t.vcbc.deliver_dispersed(t.m(), &mut t.broadcaster).unwrap();
+    assert!(t.is_send_to(&j, &ready_msg));
+}
+
+#[test]
+fn test_reference_mode_fetch() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_S;
+    let mut t = TestNet::new(i, j);
+    t.vcbc.set_reference_mode(true);
+
+    // c-final certifies the digest, we don't have the proposal
+    let final_msg = t.make_final_msg(&t.d());
+    t.vcbc
+        .receive_message(j, final_msg.clone(), &mut t.broadcaster)
+        .unwrap();
+    assert_eq!(t.vcbc.read_delivered_digest(), Some((t.d(), t.u())));
+    assert!(t.vcbc.read_delivered().is_none());
+
+    // we can only answer with the certified digest
+    let request_msg = Message {
+        tag: t.vcbc.tag.clone(),
+        action: Action::Request,
+    };
+    t.vcbc
+        .receive_message(TestNet::PARTY_Y, request_msg, &mut t.broadcaster)
+        .unwrap();
+    assert!(t.is_send_to(&TestNet::PARTY_Y, &final_msg));
+
+    let answer_msg = Message {
+        tag: t.vcbc.tag.clone(),
+        action: Action::Answer(t.m(), t.u()),
+    };
+    t.vcbc
+        .receive_message(j, answer_msg, &mut t.broadcaster)
+        .unwrap();
+    assert_eq!(t.vcbc.read_delivered(), Some((t.m(), t.u())));
+}
+
+#[test]
+fn test_reference_mode_invalid_answer() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_B;
+    let mut t = TestNet::new(i, j);
+    t.vcbc.set_reference_mode(true);
+    t.vcbc.message_validity = Arc::new(invalid_proposal);
+
+    let final_msg = t.make_final_msg(&t.d());
+    t.vcbc
+        .receive_message(j, final_msg, &mut t.broadcaster)
+        .unwrap();
+
+    // the fetched proposal is checked, even if it is certified
+    let answer_msg = Message {
+        tag: t.vcbc.tag.clone(),
+        action: Action::Answer(t.m(), t.u()),
+    };
+    let result = t
+        .vcbc
+        .receive_message(TestNet::PARTY_Y, answer_msg, &mut t.broadcaster);
+    assert!(matches!(result, Err(Error::InvalidProposal(_))));
+    assert!(t.vcbc.read_delivered().is_none());
+}
+
+#[test]
+fn test_reference_mode_c_send() {
+    let i = TestNet::PARTY_S;
+    let j = TestNet::PARTY_S; // i and j are same
+    let mut t = TestNet::new(i, j);
+    t.vcbc.set_reference_mode(true);
+
+    t.vcbc.c_broadcast(t.m, &mut t.broadcaster).unwrap();
+
+    let digest_msg = Message {
+        tag: t.vcbc.tag.clone(),
+        action: Action::SendDigest(t.d()),
+    };
+    assert!(t.is_broadcasted(&digest_msg));
+    assert!(!t.is_broadcasted(&t.make_send_msg(t.m())));
 }
 
 #[test]
@@ -220,6 +377,30 @@ fn test_final_message_first() {
     assert!(t.vcbc.read_delivered().is_some());
 }
 
+#[test]
+fn test_duplicated_final_message() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_S;
+    let mut t = TestNet::new(i, j);
+
+    let final_msg = t.make_final_msg(&t.d());
+
+    t.vcbc
+        .receive_message(TestNet::PARTY_S, final_msg.clone(), &mut t.broadcaster)
+        .unwrap();
+    let result = t
+        .vcbc
+        .receive_message(TestNet::PARTY_S, final_msg, &mut t.broadcaster);
+
+    assert!(matches!(
+        result,
+        Err(Error::DuplicatedMessage(
+            TestNet::PARTY_S,
+            ActionKind::Final
+        ))
+    ));
+}
+
 #[test]
 fn test_request_for_proposal() {
     let i = TestNet::PARTY_X;
@@ -321,3 +502,133 @@ fn test_c_answer() {
 
     assert!(t.vcbc.read_delivered().is_some());
 }
+
+#[test]
+fn test_changed_ready() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    t.vcbc.c_broadcast(t.m, &mut t.broadcaster).unwrap();
+
+    let other_digest = Hash32::calculate("other-proposal").unwrap();
+    let ready_msg_b = t.make_ready_msg(&t.d(), &TestNet::PARTY_B);
+    let other_ready_msg_b = t.make_ready_msg(&other_digest, &TestNet::PARTY_B);
+
+    t.vcbc
+        .receive_message(TestNet::PARTY_B, ready_msg_b, &mut t.broadcaster)
+        .unwrap();
+    assert!(t
+        .vcbc
+        .receive_message(TestNet::PARTY_B, other_ready_msg_b, &mut t.broadcaster)
+        .is_err());
+
+    let fault = t.vcbc.faults().get(&TestNet::PARTY_B).unwrap();
+    assert!(matches!(fault, Fault::ChangedReady { a, b, .. }
+        if a.0 == t.d() && b.0 == other_digest));
+    assert!(fault.validate(&t.sec_key_set.public_keys()).is_ok());
+}
+
+#[test]
+fn test_changed_ready_other_digest_first() {
+    let i = TestNet::PARTY_X;
+    let j = TestNet::PARTY_X;
+    let mut t = TestNet::new(i, j);
+
+    t.vcbc.c_broadcast(t.m, &mut t.broadcaster).unwrap();
+
+    let other_digest = Hash32::calculate("other-proposal").unwrap();
+    let ready_msg_b = t.make_ready_msg(&t.d(), &TestNet::PARTY_B);
+    let other_ready_msg_b = t.make_ready_msg(&other_digest, &TestNet::PARTY_B);
+
+    assert!(t
+        .vcbc
+        .receive_message(TestNet::PARTY_B, other_ready_msg_b, &mut t.broadcaster)
+        .is_err());
+    assert!(t.vcbc.faults().is_empty());
+
+    t.vcbc
+        .receive_message(TestNet::PARTY_B, ready_msg_b, &mut t.broadcaster)
+        .unwrap();
+
+    let fault = t.vcbc.faults().get(&TestNet::PARTY_B).unwrap();
+    assert_eq!(fault.voter_at_fault(), TestNet::PARTY_B);
+    assert!(fault.validate(&t.sec_key_set.public_keys()).is_ok());
+}
+
+#[test]
+fn test_batch_verification_drops_invalid_ready() {
+    let i = TestNet::PARTY_S;
+    let j = TestNet::PARTY_S; // i and j are same
+    let mut t = TestNet::new(i, j);
+    t.vcbc.set_batch_verification(true);
+
+    t.vcbc.c_broadcast(t.m, &mut t.broadcaster).unwrap();
+
+    let ready_msg_x = t.make_ready_msg(&t.d(), &TestNet::PARTY_X);
+    let ready_msg_y = t.make_ready_msg(&t.d(), &TestNet::PARTY_Y);
+    let invalid_ready_msg_b = Message {
+        tag: t.vcbc.tag.clone(),
+        action: Action::Ready(
+            t.d(),
+            t.sec_key_set
+                .secret_key_share(TestNet::PARTY_B)
+                .sign("invalid-msg"),
+        ),
+    };
+
+    t.vcbc
+        .receive_message(TestNet::PARTY_X, ready_msg_x, &mut t.broadcaster)
+        .unwrap();
+    // the invalid share is only found once the shares are combined
+    t.vcbc
+        .receive_message(TestNet::PARTY_B, invalid_ready_msg_b, &mut t.broadcaster)
+        .unwrap();
+    assert!(!t.vcbc.wd.contains_key(&TestNet::PARTY_B));
+    assert!(t.vcbc.read_delivered().is_none());
+
+    t.vcbc
+        .receive_message(TestNet::PARTY_Y, ready_msg_y, &mut t.broadcaster)
+        .unwrap();
+
+    let final_msg = t.make_final_msg(&t.d());
+    assert!(t.is_broadcasted(&final_msg));
+    assert!(t.vcbc.read_delivered().is_some());
+}
+
+#[test]
+fn test_batch_verification_invalid_ready_before_valid_one() {
+    let i = TestNet::PARTY_S;
+    let j = TestNet::PARTY_S; // i and j are same
+    let mut t = TestNet::new(i, j);
+    t.vcbc.set_batch_verification(true);
+
+    t.vcbc.c_broadcast(t.m, &mut t.broadcaster).unwrap();
+
+    let invalid_ready_msg_b = Message {
+        tag: t.vcbc.tag.clone(),
+        action: Action::Ready(
+            t.d(),
+            t.sec_key_set
+                .secret_key_share(TestNet::PARTY_B)
+                .sign("invalid-msg"),
+        ),
+    };
+    let ready_msg_b = t.make_ready_msg(&t.d(), &TestNet::PARTY_B);
+    let ready_msg_x = t.make_ready_msg(&t.d(), &TestNet::PARTY_X);
+
+    // the valid share of B takes the place of its invalid share
+    t.vcbc
+        .receive_message(TestNet::PARTY_B, invalid_ready_msg_b, &mut t.broadcaster)
+        .unwrap();
+    t.vcbc
+        .receive_message(TestNet::PARTY_B, ready_msg_b, &mut t.broadcaster)
+        .unwrap();
+    t.vcbc
+        .receive_message(TestNet::PARTY_X, ready_msg_x, &mut t.broadcaster)
+        .unwrap();
+
+    let final_msg = t.make_final_msg(&t.d());
+    assert!(t.is_broadcasted(&final_msg));
+    assert!(t.vcbc.read_delivered().is_some());
+}
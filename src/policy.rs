@@ -0,0 +1,69 @@
+use std::collections::BTreeSet;
+
+use core::fmt::Debug;
+
+use crate::{Error, Generation, Proposition, Reconfig, Result};
+
+/// The admission rules a [`crate::Membership`] instance applies to proposed reconfigs.
+///
+/// Membership itself already rejects joins of existing members and leaves of
+/// non-members, a policy can only add further restrictions on top of these.
+/// Custom rules report rejections with [`Error::PolicyViolation`].
+///
+/// Every elder must run the same policy, elders that disagree on the policy
+/// will reject each others votes and can stall consensus.
+pub trait MembershipPolicy<T: Proposition>: Debug + Send {
+    /// Checks a single proposed reconfig against the `members` of the generation
+    /// before `gen`.
+    fn validate_reconfig(
+        &self,
+        reconfig: &Reconfig<T>,
+        members: &BTreeSet<T>,
+        gen: Generation,
+    ) -> Result<()>;
+
+    /// Checks all reconfigs carried by a single vote for generation `gen`.
+    ///
+    /// Votes merge the proposals of other elders, so rejecting a combination of
+    /// reconfigs that are each valid on their own can leave the generation
+    /// undecided when elders propose competing reconfigs.
+    fn validate_proposals(
+        &self,
+        reconfigs: &BTreeSet<Reconfig<T>>,
+        members: &BTreeSet<T>,
+        gen: Generation,
+    ) -> Result<()> {
+        reconfigs
+            .iter()
+            .try_for_each(|reconfig| self.validate_reconfig(reconfig, members, gen))
+    }
+
+    /// Called once generation `gen` has been decided with the given reconfigs.
+    fn on_decision(&mut self, _gen: Generation, _reconfigs: &BTreeSet<Reconfig<T>>) {}
+}
+
+/// Rejects joins once the section has reached `max_members`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityPolicy {
+    pub max_members: usize,
+}
+
+impl Default for CapacityPolicy {
+    fn default() -> Self {
+        Self { max_members: 7 }
+    }
+}
+
+impl<T: Proposition> MembershipPolicy<T> for CapacityPolicy {
+    fn validate_reconfig(
+        &self,
+        reconfig: &Reconfig<T>,
+        members: &BTreeSet<T>,
+        _gen: Generation,
+    ) -> Result<()> {
+        match reconfig {
+            Reconfig::Join(_) if members.len() >= self.max_members => Err(Error::MembersAtCapacity),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+
+use core::fmt::Debug;
+
+use crate::consensus::{Consensus, VoteResponse};
+use crate::mvba::{self, bundle::Outgoing};
+use crate::vote::{Proposition, SignedVote};
+use crate::{
+    Decision, Fault, Handover, Membership, MembershipFollower, NodeId, Reconfig, SignatureScheme,
+};
+
+/// What a [`Protocol`] wants its host to do after handling an input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action<M, D, F> {
+    /// Send the message to a single participant.
+    Send(NodeId, M),
+    /// Send the message to all participants, ourselves included.
+    Broadcast(M),
+    /// The protocol has reached a decision.
+    Decided(D),
+    /// The participant has been caught misbehaving.
+    FaultDetected(NodeId, F),
+}
+
+/// The actions returned by a [`Protocol`].
+pub type Actions<P> =
+    Vec<Action<<P as Protocol>::Message, <P as Protocol>::Decision, <P as Protocol>::Fault>>;
+
+/// A sans-IO state machine that can be hosted by a generic event loop.
+///
+/// The host feeds in the messages it receives and periodic ticks, and carries
+/// out the actions returned in response.
+pub trait Protocol {
+    type Message: Debug + Clone;
+    type Decision: Debug + Clone;
+    type Fault: Debug + Clone;
+    type Error: std::error::Error;
+
+    /// Handles a message received from `sender`.
+    fn handle_message(
+        &mut self,
+        sender: NodeId,
+        message: Self::Message,
+    ) -> Result<Actions<Self>, Self::Error>;
+
+    /// Called periodically by the host, so the protocol can act on timeouts.
+    fn tick(&mut self) -> Result<Actions<Self>, Self::Error> {
+        Ok(Vec::new())
+    }
+}
+
+type VoteAction<T, C, D, F> = Action<SignedVote<T, C>, D, F>;
+
+/// Reports the faults recorded by `consensus` that were not in `faults_before`.
+fn new_faults<T: Proposition, C: SignatureScheme, D>(
+    faults_before: &BTreeMap<NodeId, Fault<T, C>>,
+    consensus: &Consensus<T, C>,
+) -> Vec<VoteAction<T, C, D, Fault<T, C>>> {
+    Vec::from_iter(
+        consensus
+            .faults
+            .iter()
+            .filter(|(id, fault)| faults_before.get(id) != Some(fault))
+            .map(|(id, fault)| Action::FaultDetected(*id, fault.clone())),
+    )
+}
+
+fn vote_response_actions<T: Proposition, C: SignatureScheme, D, F>(
+    response: VoteResponse<T, C>,
+) -> Vec<VoteAction<T, C, D, F>> {
+    match response {
+        VoteResponse::WaitingForMoreVotes => vec![],
+        VoteResponse::Broadcast(vote) => vec![Action::Broadcast(vote)],
+    }
+}
+
+impl<T: Proposition, C: SignatureScheme> Protocol for Membership<T, C> {
+    type Message = SignedVote<Reconfig<T>, C>;
+    type Decision = Decision<Reconfig<T>, C>;
+    type Fault = Fault<Reconfig<T>, C>;
+    type Error = crate::Error;
+
+    fn handle_message(
+        &mut self,
+        _sender: NodeId,
+        message: Self::Message,
+    ) -> crate::Result<Actions<Self>> {
+        let vote_gen = message.vote.gen;
+        let faults_before = self.consensus_at_gen(vote_gen)?.faults.clone();
+        let gen_before = self.gen;
+
+        let mut actions = vote_response_actions(self.handle_signed_vote(message)?);
+
+        let consensus = self.consensus_at_gen(vote_gen)?;
+        actions.extend(new_faults(&faults_before, consensus));
+        if self.gen > gen_before {
+            actions.extend(consensus.decision.clone().map(Action::Decided));
+        }
+        Ok(actions)
+    }
+
+    fn tick(&mut self) -> crate::Result<Actions<Self>> {
+        Membership::tick(self)
+    }
+}
+
+impl<T: Proposition, C: SignatureScheme> Protocol for MembershipFollower<T, C> {
+    type Message = SignedVote<Reconfig<T>, C>;
+    type Decision = Decision<Reconfig<T>, C>;
+    type Fault = Fault<Reconfig<T>, C>;
+    type Error = crate::Error;
+
+    fn handle_message(
+        &mut self,
+        _sender: NodeId,
+        message: Self::Message,
+    ) -> crate::Result<Actions<Self>> {
+        Ok(Vec::from_iter(
+            self.handle_signed_vote(message)?.map(Action::Decided),
+        ))
+    }
+}
+
+impl<T: Proposition, C: SignatureScheme> Protocol for Handover<T, C> {
+    type Message = SignedVote<T, C>;
+    type Decision = Decision<T, C>;
+    type Fault = Fault<T, C>;
+    type Error = crate::Error;
+
+    fn handle_message(
+        &mut self,
+        _sender: NodeId,
+        message: Self::Message,
+    ) -> crate::Result<Actions<Self>> {
+        let faults_before = self.consensus.faults.clone();
+        let decided_before = self.consensus.decision.is_some();
+
+        let mut actions = vote_response_actions(self.handle_signed_vote(message)?);
+
+        actions.extend(new_faults(&faults_before, &self.consensus));
+        if !decided_before {
+            actions.extend(self.consensus.decision.clone().map(Action::Decided));
+        }
+        Ok(actions)
+    }
+
+    fn tick(&mut self) -> crate::Result<Actions<Self>> {
+        Handover::tick(self)
+    }
+}
+
+impl<P, D, F> From<Outgoing<P>> for Action<mvba::bundle::Bundle<P>, D, F> {
+    fn from(outgoing: Outgoing<P>) -> Self {
+        match outgoing {
+            Outgoing::Gossip(bundle) => Action::Broadcast(bundle),
+            Outgoing::Direct(recipient, bundle) => Action::Send(recipient, bundle),
+        }
+    }
+}
+
+impl<P: Debug + Clone + serde::Serialize + serde::de::DeserializeOwned + Eq> Protocol
+    for mvba::consensus::Consensus<P>
+{
+    type Message = mvba::bundle::Bundle<P>;
+    type Decision = mvba::Decision<P>;
+    type Fault = mvba::fault::Fault;
+    type Error = mvba::error::Error;
+
+    fn handle_message(
+        &mut self,
+        sender: NodeId,
+        message: Self::Message,
+    ) -> mvba::error::Result<Actions<Self>> {
+        if sender != message.initiator {
+            return Err(mvba::error::Error::WrongSender {
+                sender,
+                initiator: message.initiator,
+            });
+        }
+        let decided_before = self.decided_proposal().is_some();
+        let faults_before = self.faults();
+        let new_faults = |consensus: &Self| {
+            Vec::from_iter(
+                consensus
+                    .faults()
+                    .into_iter()
+                    .filter(|fault| !faults_before.contains(fault))
+                    .map(|fault| Action::FaultDetected(fault.voter_at_fault(), fault)),
+            )
+        };
+
+        let outgoings = match self.process_bundle(&message) {
+            Ok(outgoings) => outgoings,
+            Err(err) => {
+                // an equivocating message is rejected, the fault is what we report
+                let faults = new_faults(self);
+                if faults.is_empty() {
+                    return Err(err);
+                }
+                return Ok(faults);
+            }
+        };
+
+        let mut actions = Vec::from_iter(outgoings.into_iter().map(Action::from));
+        actions.extend(new_faults(self));
+        if !decided_before {
+            actions.extend(self.decided_proposal().map(Action::Decided));
+        }
+        Ok(actions)
+    }
+}
+
+impl<P: Debug + Clone + serde::Serialize> Protocol for mvba::observer::Observer<P> {
+    type Message = mvba::bundle::Bundle<P>;
+    type Decision = mvba::Decision<P>;
+    type Fault = mvba::fault::Fault;
+    type Error = mvba::error::Error;
+
+    fn handle_message(
+        &mut self,
+        _sender: NodeId,
+        message: Self::Message,
+    ) -> mvba::error::Result<Actions<Self>> {
+        // bundles are often relayed to observers, so the sender is not checked
+        let decided_before = self.decided_proposal().is_some();
+        self.process_bundle(&message)?;
+        if decided_before {
+            return Ok(vec![]);
+        }
+        Ok(Vec::from_iter(self.decided_proposal().map(Action::Decided)))
+    }
+}
+
+impl<T: Debug + Clone + serde::Serialize + serde::de::DeserializeOwned + Eq> Protocol
+    for mvba::atomic_broadcast::AtomicBroadcast<T>
+{
+    type Message = mvba::bundle::Bundle<Vec<T>>;
+    type Decision = mvba::Decision<Vec<T>>;
+    type Fault = mvba::fault::Fault;
+    type Error = mvba::error::Error;
+
+    fn handle_message(
+        &mut self,
+        sender: NodeId,
+        message: Self::Message,
+    ) -> mvba::error::Result<Actions<Self>> {
+        if sender != message.initiator {
+            return Err(mvba::error::Error::WrongSender {
+                sender,
+                initiator: message.initiator,
+            });
+        }
+        let seq_before = self.seq();
+        let outgoings = self.process_bundle(&message)?;
+
+        let mut actions = Vec::from_iter(outgoings.into_iter().map(Action::from));
+        actions.extend(
+            (seq_before..self.seq())
+                .filter_map(|seq| self.entry(seq).cloned())
+                .map(Action::Decided),
+        );
+        Ok(actions)
+    }
+}
+
+impl<P: Debug + Clone + serde::Serialize + serde::de::DeserializeOwned + Eq> Protocol
+    for mvba::manager::MvbaManager<P>
+{
+    type Message = mvba::bundle::Bundle<P>;
+    type Decision = mvba::Decision<P>;
+    type Fault = mvba::fault::Fault;
+    type Error = mvba::error::Error;
+
+    fn handle_message(
+        &mut self,
+        sender: NodeId,
+        message: Self::Message,
+    ) -> mvba::error::Result<Actions<Self>> {
+        if sender != message.initiator {
+            return Err(mvba::error::Error::WrongSender {
+                sender,
+                initiator: message.initiator,
+            });
+        }
+        let outgoings = self.process_bundle(&message)?;
+
+        let mut actions = Vec::from_iter(outgoings.into_iter().map(Action::from));
+        actions.extend(self.take_decided().into_iter().map(Action::Decided));
+        Ok(actions)
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+
+use tokio::sync::mpsc;
+
+use super::Transport;
+use crate::NodeId;
+
+/// An in-process [`Transport`] over tokio channels, for tests and simulations.
+#[derive(Debug)]
+pub struct ChannelTransport<M> {
+    id: NodeId,
+    peers: BTreeMap<NodeId, mpsc::UnboundedSender<(NodeId, M)>>,
+    inbox: mpsc::UnboundedReceiver<(NodeId, M)>,
+}
+
+/// Connects a transport for each of `ids` to all the others.
+pub fn channel_network<M>(
+    ids: impl IntoIterator<Item = NodeId>,
+) -> BTreeMap<NodeId, ChannelTransport<M>> {
+    let inboxes = BTreeMap::from_iter(ids.into_iter().map(|id| (id, mpsc::unbounded_channel())));
+    let peers = BTreeMap::from_iter(inboxes.iter().map(|(id, (tx, _))| (*id, tx.clone())));
+    BTreeMap::from_iter(inboxes.into_iter().map(|(id, (_, inbox))| {
+        let transport = ChannelTransport {
+            id,
+            peers: peers.clone(),
+            inbox,
+        };
+        (id, transport)
+    }))
+}
+
+impl<M> ChannelTransport<M> {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+}
+
+impl<M: Clone + Send + 'static> Transport<M> for ChannelTransport<M> {
+    async fn send(&mut self, recipient: NodeId, message: M) {
+        match self.peers.get(&recipient) {
+            // a peer that has stopped is no different to one that is offline
+            Some(peer) => {
+                let _ = peer.send((self.id, message));
+            }
+            None => log::warn!("{} has no channel to {recipient}", self.id),
+        }
+    }
+
+    async fn broadcast(&mut self, message: M) {
+        for peer in self.peers.values() {
+            let _ = peer.send((self.id, message.clone()));
+        }
+    }
+
+    async fn recv(&mut self) -> Option<(NodeId, M)> {
+        self.inbox.recv().await
+    }
+}
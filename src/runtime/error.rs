@@ -0,0 +1,11 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error<E> {
+    #[error("protocol error {0}")]
+    Protocol(E),
+    #[error("the runner has stopped")]
+    Stopped,
+}
+
+pub type Result<T, E> = std::result::Result<T, Error<E>>;
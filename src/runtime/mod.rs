@@ -0,0 +1,186 @@
+//! A tokio task hosting a [`Protocol`], enabled by the `tokio` feature.
+//!
+//! The [`Runner`] feeds the messages arriving on a [`Transport`] into the
+//! protocol, sends out the messages it produces in response, and ticks it on a
+//! timer so that it can re-send anything that was lost. Decisions and detected
+//! faults are reported through the [`RunnerHandle`].
+
+pub mod channel;
+pub mod error;
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use self::error::{Error, Result};
+use crate::{Action, Actions, NodeId, Protocol};
+
+pub use self::channel::{channel_network, ChannelTransport};
+
+/// How often the protocol is ticked, unless set with [`Runner::tick_interval`].
+pub const DEFAULT_TICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Carries the messages of a protocol between the participants.
+///
+/// Delivery is best effort: the protocols recover lost messages when ticked.
+pub trait Transport<M>: Send + 'static {
+    /// Sends `message` to `recipient`.
+    fn send(&mut self, recipient: NodeId, message: M) -> impl Future<Output = ()> + Send;
+
+    /// Sends `message` to all participants, ourselves included.
+    fn broadcast(&mut self, message: M) -> impl Future<Output = ()> + Send;
+
+    /// Waits for the next message and the id of its sender, or `None` once the
+    /// transport is closed.
+    ///
+    /// The runner polls this alongside its timer, so it must be cancel safe.
+    fn recv(&mut self) -> impl Future<Output = Option<(NodeId, M)>> + Send;
+}
+
+/// What the runner reports back to its handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<D, F> {
+    Decided(D),
+    FaultDetected(NodeId, F),
+}
+
+type Call<P> = Box<dyn FnOnce(&mut P) -> Actions<P> + Send>;
+type EventOf<P> = Event<<P as Protocol>::Decision, <P as Protocol>::Fault>;
+
+pub struct Runner<P, Tr> {
+    protocol: P,
+    transport: Tr,
+    tick_interval: Duration,
+}
+
+impl<P, Tr> Runner<P, Tr>
+where
+    P: Protocol + Send + 'static,
+    P::Message: Send,
+    P::Decision: Send,
+    P::Fault: Send,
+    P::Error: Send,
+    Tr: Transport<P::Message>,
+{
+    pub fn new(protocol: P, transport: Tr) -> Self {
+        Self {
+            protocol,
+            transport,
+            tick_interval: DEFAULT_TICK_INTERVAL,
+        }
+    }
+
+    pub fn tick_interval(mut self, tick_interval: Duration) -> Self {
+        self.tick_interval = tick_interval;
+        self
+    }
+
+    /// Spawns the runner on the current tokio runtime.
+    pub fn spawn(self) -> RunnerHandle<P> {
+        let (calls_tx, calls_rx) = mpsc::unbounded_channel();
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        RunnerHandle {
+            calls: calls_tx,
+            events: events_rx,
+            task: tokio::spawn(self.run(calls_rx, events_tx)),
+        }
+    }
+
+    async fn run(
+        mut self,
+        mut calls: mpsc::UnboundedReceiver<Call<P>>,
+        events: mpsc::UnboundedSender<EventOf<P>>,
+    ) -> P {
+        let mut ticker = tokio::time::interval(self.tick_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // the first tick completes straight away, there is nothing to re-send yet
+        ticker.tick().await;
+
+        loop {
+            let result = tokio::select! {
+                received = self.transport.recv() => match received {
+                    Some((sender, message)) => self.protocol.handle_message(sender, message),
+                    None => break,
+                },
+                call = calls.recv() => match call {
+                    Some(call) => Ok(call(&mut self.protocol)),
+                    None => break,
+                },
+                _ = ticker.tick() => self.protocol.tick(),
+            };
+
+            match result {
+                Ok(actions) => self.perform(actions, &events).await,
+                Err(err) => log::warn!("failed to handle input: {err}"),
+            }
+        }
+
+        self.protocol
+    }
+
+    async fn perform(&mut self, actions: Actions<P>, events: &mpsc::UnboundedSender<EventOf<P>>) {
+        for action in actions {
+            match action {
+                Action::Send(recipient, message) => self.transport.send(recipient, message).await,
+                Action::Broadcast(message) => self.transport.broadcast(message).await,
+                Action::Decided(decision) => {
+                    let _ = events.send(Event::Decided(decision));
+                }
+                Action::FaultDetected(id, fault) => {
+                    let _ = events.send(Event::FaultDetected(id, fault));
+                }
+            }
+        }
+    }
+}
+
+/// Lets the owner of a spawned [`Runner`] drive it and observe its progress.
+pub struct RunnerHandle<P: Protocol> {
+    calls: mpsc::UnboundedSender<Call<P>>,
+    events: mpsc::UnboundedReceiver<EventOf<P>>,
+    task: JoinHandle<P>,
+}
+
+impl<P> RunnerHandle<P>
+where
+    P: Protocol + Send + 'static,
+    P::Error: Send,
+{
+    /// Runs `f` on the protocol inside the runner task and carries out the
+    /// actions it returns, e.g. to broadcast a proposal.
+    pub async fn call<F>(&self, f: F) -> Result<(), P::Error>
+    where
+        F: FnOnce(&mut P) -> std::result::Result<Actions<P>, P::Error> + Send + 'static,
+    {
+        let (result_tx, result_rx) = oneshot::channel();
+        let call: Call<P> = Box::new(move |protocol| match f(protocol) {
+            Ok(actions) => {
+                let _ = result_tx.send(Ok(()));
+                actions
+            }
+            Err(err) => {
+                let _ = result_tx.send(Err(err));
+                vec![]
+            }
+        });
+        self.calls.send(call).map_err(|_| Error::Stopped)?;
+        result_rx
+            .await
+            .map_err(|_| Error::Stopped)?
+            .map_err(Error::Protocol)
+    }
+
+    /// Waits for the next decision or fault, `None` once the runner has stopped.
+    pub async fn next_event(&mut self) -> Option<EventOf<P>> {
+        self.events.recv().await
+    }
+
+    /// Stops the runner and hands back the protocol.
+    pub async fn stop(self) -> Result<P, P::Error> {
+        drop(self.calls);
+        self.task.await.map_err(|_| Error::Stopped)
+    }
+}
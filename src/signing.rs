@@ -0,0 +1,75 @@
+//! The bytes that the parties sign.
+//!
+//! They are encoded apart from the wire format of the messages, with a fixed
+//! bincode configuration, and start with the version of the encoding. So a new
+//! wire format, or a new default configuration of bincode, doesn't change what
+//! is signed. The golden vectors in the tests pin these bytes: if they break,
+//! the signatures of the deployed parties break too.
+
+use bincode::Options;
+use serde::Serialize;
+
+/// The version of the signing bytes encoding, the first byte of the signed bytes.
+pub const SIGNING_VERSION: u8 = 1;
+
+// The bincode configuration of the signing bytes: integers have a fixed size
+// and are little endian, sequences and strings are prefixed by a u64 length.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .allow_trailing_bytes()
+}
+
+/// Encodes `msg` canonically, without the version.
+///
+/// It is the encoding of the values that are hashed, like the digest of a proposal.
+pub fn encode<M: Serialize + ?Sized>(msg: &M) -> Result<Vec<u8>, bincode::Error> {
+    options().serialize(msg)
+}
+
+/// The bytes to sign for `msg`.
+pub fn signing_bytes<M: Serialize + ?Sized>(msg: &M) -> Result<Vec<u8>, bincode::Error> {
+    let mut bytes = vec![SIGNING_VERSION];
+    options().serialize_into(&mut bytes, msg)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ballot, Vote};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn test_signing_bytes_golden() {
+        // version | 7u64 | "vote" prefixed by its u64 length | true
+        let bytes = signing_bytes(&(7u64, "vote", true)).unwrap();
+        assert_eq!(
+            hex(&bytes),
+            "01\
+             0700000000000000\
+             0400000000000000766f7465\
+             01"
+        );
+        assert_eq!(encode(&(7u64, "vote", true)).unwrap(), bytes[1..]);
+
+        // version | gen | the variant of the ballot, u32 | the proposal | no faults
+        let vote = Vote::<u8> {
+            gen: 3,
+            ballot: Ballot::Propose(5),
+            faults: Default::default(),
+        };
+        assert_eq!(
+            hex(&vote.to_bytes().unwrap()),
+            "01\
+             0300000000000000\
+             00000000\
+             05\
+             0000000000000000"
+        );
+    }
+}
@@ -1,35 +1,39 @@
 use std::collections::BTreeMap;
 
-use blsttc::{PublicKeySet, SecretKeyShare, Signature};
 use core::fmt::Debug;
 use log::info;
 
 use crate::consensus::{Consensus, VoteResponse};
+use crate::liveness::Liveness;
+use crate::protocol::{Action, Actions};
 use crate::vote::{simplify_votes, Ballot, Proposition, SignedVote, Vote};
-use crate::{Error, NodeId, Result};
+use crate::{Bls, Error, NodeId, Result, SignatureScheme};
 
 pub type UniqueSectionId = u64;
 
 #[derive(Debug)]
-pub struct Handover<T: Proposition> {
-    pub consensus: Consensus<T>,
+pub struct Handover<T: Proposition, C: SignatureScheme = Bls> {
+    pub consensus: Consensus<T, C>,
     pub gen: UniqueSectionId,
+    pub liveness: Liveness,
 }
 
-impl<T: Proposition> Handover<T> {
+impl<T: Proposition, C: SignatureScheme> Handover<T, C> {
     pub fn from(
-        secret_key: (NodeId, SecretKeyShare),
-        elders: PublicKeySet,
+        secret_key: (NodeId, C::SecretKeyShare),
+        elders: C::PublicKeySet,
         n_elders: usize,
         gen: UniqueSectionId,
+        stall_timeout: u64,
     ) -> Self {
-        Handover::<T> {
-            consensus: Consensus::<T>::from(secret_key, elders, n_elders),
+        Handover::<T, C> {
+            consensus: Consensus::<T, C>::from(secret_key, elders, n_elders),
             gen,
+            liveness: Liveness::new(stall_timeout),
         }
     }
 
-    pub fn propose(&mut self, proposal: T) -> Result<SignedVote<T>> {
+    pub fn propose(&mut self, proposal: T) -> Result<SignedVote<T, C>> {
         let vote = Vote {
             gen: self.gen,
             ballot: Ballot::Propose(proposal),
@@ -48,7 +52,7 @@ impl<T: Proposition> Handover<T> {
     }
 
     // Get someone up to speed on our view of the current votes
-    pub fn anti_entropy(&self) -> Result<Vec<SignedVote<T>>> {
+    pub fn anti_entropy(&self) -> Result<Vec<SignedVote<T, C>>> {
         info!("[HDVR] anti-entropy from {:?}", self.id());
 
         if let Some(_decision) = self.consensus.decision.as_ref() {
@@ -65,7 +69,7 @@ impl<T: Proposition> Handover<T> {
         }
     }
 
-    pub fn resolve_votes<'a>(&self, proposals: &'a BTreeMap<T, Signature>) -> Option<&'a T> {
+    pub fn resolve_votes<'a>(&self, proposals: &'a BTreeMap<T, C::Signature>) -> Option<&'a T> {
         // we need to choose one deterministically
         // proposals are comparable because they impl Ord so we arbitrarily pick the max
         proposals.keys().max()
@@ -75,21 +79,67 @@ impl<T: Proposition> Handover<T> {
         self.consensus.id()
     }
 
-    pub fn handle_signed_vote(&mut self, signed_vote: SignedVote<T>) -> Result<VoteResponse<T>> {
+    pub fn handle_signed_vote(
+        &mut self,
+        signed_vote: SignedVote<T, C>,
+    ) -> Result<VoteResponse<T, C>> {
         self.validate_proposals(&signed_vote)?;
 
-        self.consensus.handle_signed_vote(signed_vote)
+        let voter = signed_vote.voter;
+        let decided_before = self.consensus.decision.is_some();
+        let vote_response = self.consensus.handle_signed_vote(signed_vote)?;
+
+        if voter != self.id() {
+            self.liveness.observe_vote(voter, self.gen, decided_before);
+        }
+        if !decided_before && self.consensus.decision.is_some() {
+            self.liveness
+                .observe_decision(self.gen, self.consensus.votes.keys().copied());
+        }
+        Ok(vote_response)
+    }
+
+    /// Advances the logical clock used to notice a stalled handover.
+    ///
+    /// Works like [`crate::Membership::tick`] for the single generation of the
+    /// handover.
+    pub fn tick(&mut self) -> Result<Actions<Self>> {
+        let undecided = self.consensus.decision.is_none() && !self.consensus.votes.is_empty();
+        let stalled = self.liveness.tick(undecided);
+
+        let mut actions = Vec::new();
+        let lagging = self.liveness.take_lagging();
+        if !lagging.is_empty() {
+            let votes = self.anti_entropy()?;
+            for elder in lagging.into_keys().filter(|elder| *elder != self.id()) {
+                actions.extend(votes.iter().map(|v| Action::Send(elder, v.clone())));
+            }
+        }
+
+        if stalled {
+            if let Some(vote) = self.consensus.votes.get(&self.id()) {
+                actions.push(Action::Broadcast(vote.clone()));
+            }
+            let votes = self.anti_entropy()?;
+            for elder in self.liveness.peers() {
+                if elder != self.id() && !self.consensus.votes.contains_key(&elder) {
+                    actions.extend(votes.iter().map(|v| Action::Send(elder, v.clone())));
+                }
+            }
+        }
+
+        Ok(actions)
     }
 
-    pub fn sign_vote(&self, vote: Vote<T>) -> Result<SignedVote<T>> {
+    pub fn sign_vote(&self, vote: Vote<T, C>) -> Result<SignedVote<T, C>> {
         self.consensus.sign_vote(vote)
     }
 
-    pub fn cast_vote(&mut self, signed_vote: SignedVote<T>) -> Result<SignedVote<T>> {
+    pub fn cast_vote(&mut self, signed_vote: SignedVote<T, C>) -> Result<SignedVote<T, C>> {
         self.consensus.cast_vote(signed_vote)
     }
 
-    pub fn validate_proposals(&self, signed_vote: &SignedVote<T>) -> Result<()> {
+    pub fn validate_proposals(&self, signed_vote: &SignedVote<T, C>) -> Result<()> {
         if signed_vote.vote.gen != self.gen {
             return Err(Error::BadGeneration {
                 requested_gen: signed_vote.vote.gen,
@@ -1,23 +1,101 @@
 use std::collections::{BTreeMap, BTreeSet};
 
-use blsttc::{PublicKeySet, SecretKeyShare};
 use core::fmt::Debug;
 use log::info;
 use serde::{Deserialize, Serialize};
 
 use crate::consensus::{Consensus, VoteResponse};
+use crate::decision::Decision;
+use crate::liveness::{Liveness, DEFAULT_STALL_TIMEOUT};
+use crate::mvba::hash::Hash32;
+use crate::policy::{CapacityPolicy, MembershipPolicy};
+use crate::protocol::{Action, Actions, Protocol};
+use crate::storage::{Record, Storage};
 use crate::vote::{simplify_votes, Ballot, Proposition, SignedVote, Vote};
-use crate::{Error, NodeId, Result};
+use crate::{verify_sig, verify_sig_share, Bls, Error, NodeId, Result, SignatureScheme};
 
-const SOFT_MAX_MEMBERS: usize = 7;
+pub(crate) const DEFAULT_CHECKPOINT_INTERVAL: Generation = 64;
 pub type Generation = u64;
 
 #[derive(Debug)]
-pub struct Membership<T: Proposition> {
-    pub consensus: Consensus<Reconfig<T>>,
+pub struct Membership<T: Proposition, C: SignatureScheme = Bls> {
+    pub consensus: Consensus<Reconfig<T>, C>,
     pub gen: Generation,
     pub forced_reconfigs: BTreeMap<Generation, BTreeSet<Reconfig<T>>>,
-    pub history: BTreeMap<Generation, Consensus<Reconfig<T>>>,
+    pub history: BTreeMap<Generation, Consensus<Reconfig<T>, C>>,
+    pub checkpoint: Option<Checkpoint<T, C>>,
+    /// The elders' signature shares over the checkpoint, until it is signed.
+    pub checkpoint_shares: BTreeMap<NodeId, C::SignatureShare>,
+    pub config: MembershipConfig<T>,
+    pub storage: Option<Box<dyn Storage<T, C>>>,
+    pub liveness: Liveness,
+    // The votes for the next generation received while waiting for its elders
+    // to be rotated, see [`Membership::expect_elder_rotation`].
+    pending_rotation: Option<Vec<SignedVote<Reconfig<T>, C>>>,
+}
+
+#[derive(Debug)]
+pub struct MembershipConfig<T: Proposition> {
+    /// The admission rules applied to proposed reconfigs.
+    pub policy: Box<dyn MembershipPolicy<T>>,
+    /// Once `2 * checkpoint_interval` generations have been decided since the last
+    /// checkpoint, a new checkpoint is taken `checkpoint_interval` generations
+    /// behind the current generation and the history before it is pruned.
+    /// `None` disables checkpointing.
+    pub checkpoint_interval: Option<Generation>,
+    /// Ticks the generation we are deciding may stay undecided before we
+    /// re-send our votes, see [`Membership::tick`].
+    pub stall_timeout: u64,
+}
+
+impl<T: Proposition> Default for MembershipConfig<T> {
+    fn default() -> Self {
+        Self {
+            policy: Box::new(CapacityPolicy::default()),
+            checkpoint_interval: Some(DEFAULT_CHECKPOINT_INTERVAL),
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+        }
+    }
+}
+
+/// A snapshot of the members at generation `gen`.
+///
+/// The `decision` that ended generation `gen` carries the section signature
+/// over the last reconfigs applied to `members`. The `signature` of the elders
+/// binds `members` to `gen`, it is combined from the shares the elders exchange
+/// with [`Membership::sign_checkpoint`] and [`Membership::handle_checkpoint_share`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(serialize = "", deserialize = "T: Deserialize<'de>"))]
+pub struct Checkpoint<T: Proposition, C: SignatureScheme = Bls> {
+    pub gen: Generation,
+    pub members: BTreeSet<T>,
+    pub decision: Decision<Reconfig<T>, C>,
+    pub signature: Option<C::Signature>,
+}
+
+// What the elders sign for a checkpoint.
+#[derive(Serialize)]
+struct CheckpointStatement {
+    gen: Generation,
+    members: Hash32,
+}
+
+impl<T: Proposition, C: SignatureScheme> Checkpoint<T, C> {
+    pub fn validate(&self, public_key: &C::PublicKey) -> Result<()> {
+        if self.decision.generation != self.gen {
+            return Err(Error::InvalidCheckpoint);
+        }
+        self.decision.validate(public_key)?;
+        let signature = self.signature.as_ref().ok_or(Error::UnsignedCheckpoint)?;
+        verify_sig::<C, _>(&self.statement()?, signature, public_key)
+    }
+
+    fn statement(&self) -> Result<CheckpointStatement> {
+        Ok(CheckpointStatement {
+            gen: self.gen,
+            members: Hash32::calculate(&self.members)?,
+        })
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -44,21 +122,135 @@ impl<T: Proposition> Reconfig<T> {
     }
 }
 
-impl<T: Proposition> Membership<T> {
+impl<T: Proposition, C: SignatureScheme> Membership<T, C> {
     pub fn from(
-        secret_key: (NodeId, SecretKeyShare),
-        elders: PublicKeySet,
+        secret_key: (NodeId, C::SecretKeyShare),
+        elders: C::PublicKeySet,
         n_elders: usize,
+        config: MembershipConfig<T>,
     ) -> Self {
         Membership {
             consensus: Consensus::from(secret_key, elders, n_elders),
             gen: 0,
             forced_reconfigs: Default::default(),
             history: BTreeMap::default(),
+            checkpoint: None,
+            checkpoint_shares: BTreeMap::new(),
+            liveness: Liveness::new(config.stall_timeout),
+            config,
+            storage: None,
+            pending_rotation: None,
+        }
+    }
+
+    /// Rebuilds the membership state from the write-ahead log in `storage`.
+    ///
+    /// All further votes and decisions are recorded to `storage` before they are
+    /// returned to the caller, so an elder that restarts will never cast a vote that
+    /// conflicts with a vote it cast before the restart.
+    pub fn recover(
+        secret_key: (NodeId, C::SecretKeyShare),
+        elders: C::PublicKeySet,
+        n_elders: usize,
+        config: MembershipConfig<T>,
+        storage: impl Storage<T, C> + 'static,
+    ) -> Result<Self> {
+        Self::recover_with_rotations(
+            secret_key,
+            elders,
+            n_elders,
+            BTreeMap::new(),
+            config,
+            storage,
+        )
+    }
+
+    /// Like [`Self::recover`], for a log that contains elder rotations.
+    ///
+    /// `secret_key`, `elders` and `n_elders` are the keys we started out with,
+    /// `rotated_secret_keys` holds our secret key share for each generation the
+    /// elders were rotated at. The public keys are read back from the log.
+    pub fn recover_with_rotations(
+        secret_key: (NodeId, C::SecretKeyShare),
+        elders: C::PublicKeySet,
+        n_elders: usize,
+        mut rotated_secret_keys: BTreeMap<Generation, (NodeId, C::SecretKeyShare)>,
+        config: MembershipConfig<T>,
+        storage: impl Storage<T, C> + 'static,
+    ) -> Result<Self> {
+        let mut membership = Self::from(secret_key, elders, n_elders, config);
+
+        for record in storage.records()? {
+            membership.replay(record, &mut rotated_secret_keys)?;
+        }
+
+        membership.storage = Some(Box::new(storage));
+        Ok(membership)
+    }
+
+    fn replay(
+        &mut self,
+        record: Record<T, C>,
+        rotated_secret_keys: &mut BTreeMap<Generation, (NodeId, C::SecretKeyShare)>,
+    ) -> Result<()> {
+        match record {
+            Record::ForcedReconfig(gen, reconfig) => self.apply_forced_reconfig(gen, reconfig),
+            Record::RotateElders(gen, elders, n_elders) => {
+                let secret_key = rotated_secret_keys
+                    .remove(&gen)
+                    .ok_or(Error::MissingRotatedSecretKey(gen))?;
+                self.apply_elder_rotation(gen, secret_key, elders, n_elders)?;
+                // the votes kept aside were recorded again once handled
+                self.pending_rotation = None;
+            }
+            Record::ExpectElderRotation(gen) => {
+                if gen == self.gen + 1 {
+                    self.pending_rotation.get_or_insert_with(Vec::new);
+                }
+            }
+            Record::HeldVote(signed_vote) => {
+                if let Some(pending) = self.pending_rotation.as_mut() {
+                    pending.push(signed_vote);
+                }
+            }
+            Record::Vote(signed_vote) => {
+                self.handle_signed_vote(signed_vote)?;
+            }
+            Record::Cast(signed_vote) => {
+                // The generation may have advanced since this vote was cast
+                self.consensus_at_gen_mut(signed_vote.vote.gen)?
+                    .cast_vote(signed_vote)?;
+            }
+            Record::Checkpoint(checkpoint) => {
+                // a compacted log starts from its checkpoint
+                self.gen = checkpoint.gen;
+                self.history.clear();
+                self.forced_reconfigs = self.forced_reconfigs.split_off(&(checkpoint.gen + 1));
+                self.checkpoint = Some(checkpoint);
+            }
+            Record::Decided(decision) => {
+                let replayed_decision = self
+                    .consensus_at_gen(decision.generation)?
+                    .decision
+                    .as_ref()
+                    .map(|d| BTreeSet::from_iter(d.proposals.keys()));
+
+                if replayed_decision != Some(BTreeSet::from_iter(decision.proposals.keys())) {
+                    return Err(Error::RecordedDecisionMismatch(decision.generation));
+                }
+            }
         }
+        Ok(())
     }
 
-    pub fn consensus_at_gen(&self, gen: Generation) -> Result<&Consensus<Reconfig<T>>> {
+    fn persist(&mut self, record: Record<T, C>) -> Result<()> {
+        if let Some(storage) = self.storage.as_mut() {
+            storage.append(&record)?;
+        }
+        Ok(())
+    }
+
+    pub fn consensus_at_gen(&self, gen: Generation) -> Result<&Consensus<Reconfig<T>, C>> {
         if gen == self.gen + 1 {
             Ok(&self.consensus)
         } else {
@@ -69,7 +261,10 @@ impl<T: Proposition> Membership<T> {
         }
     }
 
-    pub fn consensus_at_gen_mut(&mut self, gen: Generation) -> Result<&mut Consensus<Reconfig<T>>> {
+    pub fn consensus_at_gen_mut(
+        &mut self,
+        gen: Generation,
+    ) -> Result<&mut Consensus<Reconfig<T>, C>> {
         if gen == self.gen + 1 {
             Ok(&mut self.consensus)
         } else {
@@ -80,64 +275,117 @@ impl<T: Proposition> Membership<T> {
         }
     }
 
-    pub fn force_join(&mut self, actor: T) {
-        let forced_reconfigs = self.forced_reconfigs.entry(self.gen).or_default();
-
-        // remove any leave reconfigs for this actor
-        forced_reconfigs.remove(&Reconfig::Leave(actor.clone()));
-        forced_reconfigs.insert(Reconfig::Join(actor));
+    pub fn force_join(&mut self, actor: T) -> Result<()> {
+        self.force_reconfig(Reconfig::Join(actor))
     }
 
-    pub fn force_leave(&mut self, actor: T) {
-        let forced_reconfigs = self.forced_reconfigs.entry(self.gen).or_default();
+    pub fn force_leave(&mut self, actor: T) -> Result<()> {
+        self.force_reconfig(Reconfig::Leave(actor))
+    }
 
-        // remove any leave reconfigs for this actor
-        forced_reconfigs.remove(&Reconfig::Join(actor.clone()));
-        forced_reconfigs.insert(Reconfig::Leave(actor));
+    fn force_reconfig(&mut self, reconfig: Reconfig<T>) -> Result<()> {
+        self.persist(Record::ForcedReconfig(self.gen, reconfig.clone()))?;
+        self.apply_forced_reconfig(self.gen, reconfig);
+        Ok(())
     }
 
-    pub fn members(&self, gen: Generation) -> Result<BTreeSet<T>> {
-        let mut members = BTreeSet::new();
+    fn apply_forced_reconfig(&mut self, gen: Generation, reconfig: Reconfig<T>) {
+        let forced_reconfigs = self.forced_reconfigs.entry(gen).or_default();
 
-        self.forced_reconfigs
-            .get(&0) // forced reconfigs at generation 0
-            .cloned()
-            .unwrap_or_default()
-            .into_iter()
-            .for_each(|r| r.apply(&mut members));
+        // remove any opposing reconfigs for this actor
+        match &reconfig {
+            Reconfig::Join(actor) => forced_reconfigs.remove(&Reconfig::Leave(actor.clone())),
+            Reconfig::Leave(actor) => forced_reconfigs.remove(&Reconfig::Join(actor.clone())),
+        };
+        forced_reconfigs.insert(reconfig);
+    }
 
-        if gen == 0 {
-            return Ok(members);
+    /// Announces that the elders will be rotated for the next generation.
+    ///
+    /// Call it once generation `self.gen` is decided, when its reconfigs change
+    /// the elders. Until [`Self::rotate_elders`] installs the new keys, the votes
+    /// for `self.gen + 1` are kept aside and we don't vote on it ourselves.
+    pub fn expect_elder_rotation(&mut self) -> Result<()> {
+        if !self.consensus.votes.is_empty() {
+            return Err(Error::ElderRotationAfterVoting(self.gen + 1));
         }
+        if self.pending_rotation.is_none() {
+            self.persist(Record::ExpectElderRotation(self.gen + 1))?;
+            self.pending_rotation = Some(Vec::new());
+        }
+        Ok(())
+    }
 
-        for (history_gen, consensus) in self.history.iter() {
-            self.forced_reconfigs
-                .get(history_gen)
-                .cloned()
-                .unwrap_or_default()
-                .into_iter()
-                .for_each(|r| r.apply(&mut members));
-
-            let decision = if let Some(decision) = consensus.decision.as_ref() {
-                decision
-            } else {
-                panic!("historical consensus entry without decision {history_gen}: {consensus:?}");
-            };
-
-            for (reconfig, _sig) in decision.proposals.iter() {
-                reconfig.apply(&mut members);
+    /// Installs a new elder key set for the next generation.
+    ///
+    /// This must happen after generation `self.gen` is decided and before any
+    /// vote for `self.gen + 1` is handled, unless the rotation was announced
+    /// with [`Self::expect_elder_rotation`]. Votes for `self.gen + 1` onwards are
+    /// then validated against the new keys, while the history keeps the keys it
+    /// was decided with. The votes kept aside while waiting for the keys are
+    /// handled now, the actions they lead to are returned.
+    pub fn rotate_elders(
+        &mut self,
+        secret_key: (NodeId, C::SecretKeyShare),
+        elders: C::PublicKeySet,
+        n_elders: usize,
+    ) -> Result<Actions<Self>> {
+        let gen = self.gen + 1;
+        if !self.consensus.votes.is_empty() {
+            return Err(Error::ElderRotationAfterVoting(gen));
+        }
+        self.persist(Record::RotateElders(gen, elders.clone(), n_elders))?;
+        self.apply_elder_rotation(gen, secret_key, elders, n_elders)?;
+
+        let mut actions = Vec::new();
+        for vote in self.pending_rotation.take().unwrap_or_default() {
+            // the votes signed with the keys we rotated away from are dropped here
+            match self.handle_message(vote.voter, vote) {
+                Ok(vote_actions) => actions.extend(vote_actions),
+                Err(err) => info!("[{}] dropping a vote kept for gen {gen}: {err}", self.id()),
             }
+        }
+        Ok(actions)
+    }
 
-            if history_gen == &gen {
-                return Ok(members);
-            }
+    fn apply_elder_rotation(
+        &mut self,
+        gen: Generation,
+        secret_key: (NodeId, C::SecretKeyShare),
+        elders: C::PublicKeySet,
+        n_elders: usize,
+    ) -> Result<()> {
+        // A compacted log keeps the last rotation before its checkpoint, the
+        // keys it installed are still in use.
+        if gen > self.gen + 1 || !self.consensus.votes.is_empty() {
+            return Err(Error::ElderRotationAfterVoting(gen));
         }
+        self.consensus = Consensus::from(secret_key, elders, n_elders);
+        self.liveness.forget_elders();
+        Ok(())
+    }
 
-        Err(Error::InvalidGeneration(gen))
+    pub fn members(&self, gen: Generation) -> Result<BTreeSet<T>> {
+        let decisions = self.history.iter().map(|(history_gen, consensus)| {
+            if let Some(decision) = consensus.decision.as_ref() {
+                (history_gen, decision)
+            } else {
+                panic!("historical consensus entry without decision {history_gen}: {consensus:?}");
+            }
+        });
+        members_at(
+            self.checkpoint.as_ref(),
+            &self.forced_reconfigs,
+            decisions,
+            gen,
+        )
     }
 
-    pub fn propose(&mut self, reconfig: Reconfig<T>) -> Result<SignedVote<Reconfig<T>>> {
+    pub fn propose(&mut self, reconfig: Reconfig<T>) -> Result<SignedVote<Reconfig<T>, C>> {
         info!("[{}] proposing {:?}", self.id(), reconfig);
+        if self.pending_rotation.is_some() {
+            return Err(Error::AwaitingElderRotation(self.gen + 1));
+        }
         let vote = Vote {
             gen: self.gen + 1,
             ballot: Ballot::Propose(reconfig),
@@ -155,7 +403,7 @@ impl<T: Proposition> Membership<T> {
         self.cast_vote(signed_vote)
     }
 
-    pub fn anti_entropy(&self, from_gen: Generation) -> Result<Vec<SignedVote<Reconfig<T>>>> {
+    pub fn anti_entropy(&self, from_gen: Generation) -> Result<Vec<SignedVote<Reconfig<T>, C>>> {
         info!("[MBR] anti-entropy from gen {}", from_gen);
 
         let mut msgs = self
@@ -179,22 +427,97 @@ impl<T: Proposition> Membership<T> {
         Ok(msgs)
     }
 
+    /// Advances the logical clock used to notice stalled generations.
+    ///
+    /// Every `stall_timeout` ticks that the generation we are deciding stays
+    /// undecided, we re-broadcast our vote and send the votes we have to the
+    /// elders we have not heard from in this generation. Elders still voting on
+    /// a generation we decided a while ago, or that didn't vote on it at all,
+    /// are sent what they need to catch up. The elders that didn't vote are only
+    /// known once they are set with [`Liveness::set_elders`].
+    pub fn tick(&mut self) -> Result<Actions<Self>> {
+        let undecided = !self.consensus.votes.is_empty();
+        let stalled = self.liveness.tick(undecided);
+
+        let mut actions = Vec::new();
+        let id = self.id();
+        for (elder, gen) in self.liveness.take_lagging() {
+            if elder == id {
+                continue;
+            }
+            info!("[{id}] {elder} is stuck on gen {gen}");
+            for vote in self.anti_entropy(gen.saturating_sub(1))? {
+                actions.push(Action::Send(elder, vote));
+            }
+        }
+
+        if stalled {
+            info!("[{}] gen {} has stalled", self.id(), self.gen + 1);
+            if let Some(vote) = self.consensus.votes.get(&self.id()) {
+                actions.push(Action::Broadcast(vote.clone()));
+            }
+            let votes = self.anti_entropy(self.gen)?;
+            for elder in self.liveness.peers() {
+                if elder != self.id() && !self.consensus.votes.contains_key(&elder) {
+                    actions.extend(votes.iter().map(|v| Action::Send(elder, v.clone())));
+                }
+            }
+        }
+
+        Ok(actions)
+    }
+
     pub fn id(&self) -> NodeId {
         self.consensus.id()
     }
 
     pub fn handle_signed_vote(
         &mut self,
-        signed_vote: SignedVote<Reconfig<T>>,
-    ) -> Result<VoteResponse<Reconfig<T>>> {
+        signed_vote: SignedVote<Reconfig<T>, C>,
+    ) -> Result<VoteResponse<Reconfig<T>, C>> {
         self.validate_proposals(&signed_vote)?;
 
         let vote_gen = signed_vote.vote.gen;
+        let voter = signed_vote.voter;
+
+        if self.pending_rotation.is_some() && vote_gen == self.gen + 1 {
+            info!(
+                "[{}] keeping {voter}'s vote until the elders are rotated",
+                self.id()
+            );
+            let held = self
+                .pending_rotation
+                .iter()
+                .flatten()
+                .any(|v| v == &signed_vote);
+            if !held {
+                self.persist(Record::HeldVote(signed_vote.clone()))?;
+                self.pending_rotation
+                    .get_or_insert_with(Vec::new)
+                    .push(signed_vote);
+            }
+            return Ok(VoteResponse::WaitingForMoreVotes);
+        }
 
         let consensus = self.consensus_at_gen_mut(vote_gen)?;
-        let vote_response = consensus.handle_signed_vote(signed_vote)?;
+        let vote_response = consensus.handle_signed_vote(signed_vote.clone())?;
+        let decision = consensus.decision.clone();
+
+        if voter != self.id() {
+            self.liveness
+                .observe_vote(voter, vote_gen, vote_gen <= self.gen);
+        }
+
+        self.persist(Record::Vote(signed_vote))?;
+        if let VoteResponse::Broadcast(vote) = &vote_response {
+            self.persist(Record::Cast(vote.clone()))?;
+        }
+
+        if let (Some(decision), true) = (decision, vote_gen == self.gen + 1) {
+            let reconfigs = BTreeSet::from_iter(decision.proposals.keys().cloned());
+            self.persist(Record::Decided(decision))?;
+            self.config.policy.on_decision(vote_gen, &reconfigs);
 
-        if consensus.decision.is_some() && vote_gen == self.gen + 1 {
             let next_consensus = Consensus::from(
                 self.consensus.secret_key.clone(),
                 self.consensus.elders.clone(),
@@ -203,52 +526,239 @@ impl<T: Proposition> Membership<T> {
 
             let decided_consensus = std::mem::replace(&mut self.consensus, next_consensus);
             self.gen += 1;
+            self.liveness
+                .observe_decision(self.gen, decided_consensus.votes.keys().copied());
             self.history.insert(self.gen, decided_consensus);
+            self.maybe_checkpoint()?;
         }
 
         Ok(vote_response)
     }
 
-    pub fn sign_vote(&self, vote: Vote<Reconfig<T>>) -> Result<SignedVote<Reconfig<T>>> {
+    fn maybe_checkpoint(&mut self) -> Result<()> {
+        let interval = match self.config.checkpoint_interval {
+            Some(interval) if interval > 0 => interval,
+            _ => return Ok(()),
+        };
+        let checkpoint_gen = self.checkpoint.as_ref().map(|c| c.gen).unwrap_or(0);
+        if self.gen - checkpoint_gen < 2 * interval {
+            return Ok(());
+        }
+
+        // Forced reconfigs are keyed by the current generation, so anything at or
+        // before `gen` is final and can be folded into the checkpoint.
+        let gen = self.gen - interval;
+        let members = self.members(gen)?;
+        let decision = self
+            .consensus_at_gen(gen)?
+            .decision
+            .clone()
+            .ok_or(Error::InvalidGeneration(gen))?;
+
+        self.history = self.history.split_off(&(gen + 1));
+        self.forced_reconfigs = self.forced_reconfigs.split_off(&(gen + 1));
+        self.checkpoint = Some(Checkpoint {
+            gen,
+            members,
+            decision,
+            signature: None,
+        });
+        self.checkpoint_shares.clear();
+        self.compact_storage()
+    }
+
+    // Rewrites the log from the checkpoint, without the records of the
+    // generations it pruned.
+    fn compact_storage(&mut self) -> Result<()> {
+        let (storage, checkpoint) = match (self.storage.as_mut(), self.checkpoint.as_ref()) {
+            (Some(storage), Some(checkpoint)) => (storage, checkpoint),
+            _ => return Ok(()),
+        };
+        let gen = checkpoint.gen;
+        let records = storage.records()?;
+
+        // the keys used after the checkpoint were installed by the last rotation
+        // up to the generation that follows it
+        let last_rotation = records
+            .iter()
+            .rposition(|r| matches!(r, Record::RotateElders(g, ..) if *g <= gen + 1));
+        let mut compacted = vec![Record::Checkpoint(checkpoint.clone())];
+        compacted.extend(
+            records
+                .into_iter()
+                .enumerate()
+                .filter(|(i, record)| match record {
+                    Record::ForcedReconfig(g, _) => *g > gen,
+                    Record::Vote(vote) | Record::Cast(vote) => vote.vote.gen > gen,
+                    Record::Decided(decision) => decision.generation > gen,
+                    Record::RotateElders(g, ..) => *g > gen + 1 || Some(*i) == last_rotation,
+                    Record::ExpectElderRotation(g) => *g > gen,
+                    Record::HeldVote(vote) => vote.vote.gen > gen,
+                    Record::Checkpoint(_) => false,
+                })
+                .map(|(_, record)| record),
+        );
+        storage.compact(&compacted)
+    }
+
+    /// Our signature share over the current checkpoint, to send to the other
+    /// elders with the generation of the checkpoint.
+    pub fn sign_checkpoint(&self) -> Result<Option<(Generation, C::SignatureShare)>> {
+        match self.checkpoint.as_ref() {
+            Some(checkpoint) => {
+                let share = self.consensus.sign(&checkpoint.statement()?)?;
+                Ok(Some((checkpoint.gen, share)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Handles the signature share of `voter` over the checkpoint at `gen`.
+    /// Once more than `threshold` elders signed it, the checkpoint is signed,
+    /// written to storage and can be handed out to the followers.
+    pub fn handle_checkpoint_share(
+        &mut self,
+        voter: NodeId,
+        gen: Generation,
+        share: C::SignatureShare,
+    ) -> Result<()> {
+        let checkpoint = match self.checkpoint.as_mut() {
+            Some(checkpoint) if checkpoint.gen == gen => checkpoint,
+            _ => return Err(Error::InvalidGeneration(gen)),
+        };
+        if checkpoint.signature.is_some() {
+            return Ok(());
+        }
+
+        let elders = &self.consensus.elders;
+        verify_sig_share::<C, _>(&checkpoint.statement()?, &share, voter, elders)?;
+        self.checkpoint_shares.insert(voter, share);
+        if self.checkpoint_shares.len() > C::threshold(elders) {
+            checkpoint.signature = Some(C::combine_shares(elders, &self.checkpoint_shares)?);
+            self.checkpoint_shares.clear();
+            // the signed checkpoint takes the place of the unsigned one in storage
+            self.compact_storage()?;
+        }
+        Ok(())
+    }
+
+    pub fn sign_vote(&self, vote: Vote<Reconfig<T>, C>) -> Result<SignedVote<Reconfig<T>, C>> {
         self.consensus.sign_vote(vote)
     }
 
     pub fn cast_vote(
         &mut self,
-        signed_vote: SignedVote<Reconfig<T>>,
-    ) -> Result<SignedVote<Reconfig<T>>> {
-        self.consensus.cast_vote(signed_vote)
+        signed_vote: SignedVote<Reconfig<T>, C>,
+    ) -> Result<SignedVote<Reconfig<T>, C>> {
+        let vote = self.consensus.cast_vote(signed_vote.clone())?;
+
+        self.persist(Record::Cast(signed_vote.clone()))?;
+        if vote != signed_vote {
+            self.persist(Record::Cast(vote.clone()))?;
+        }
+
+        Ok(vote)
     }
 
-    pub fn validate_proposals(&self, signed_vote: &SignedVote<Reconfig<T>>) -> Result<()> {
+    pub fn validate_proposals(&self, signed_vote: &SignedVote<Reconfig<T>, C>) -> Result<()> {
+        let gen = signed_vote.vote.gen;
+
         // ensure we have a consensus instance for this votes generations
-        let _ = self.consensus_at_gen(signed_vote.vote.gen)?;
+        let _ = self.consensus_at_gen(gen)?;
 
-        signed_vote
-            .proposals()
-            .into_iter()
-            .try_for_each(|reconfig| self.validate_reconfig(reconfig, signed_vote.vote.gen))
+        assert!(gen > 0);
+        let members = self.members(gen - 1)?;
+        let reconfigs = signed_vote.proposals();
+        for reconfig in reconfigs.iter() {
+            validate_membership_change(reconfig, &members)?;
+        }
+        self.config
+            .policy
+            .validate_proposals(&reconfigs, &members, gen)
     }
 
     pub fn validate_reconfig(&self, reconfig: Reconfig<T>, gen: Generation) -> Result<()> {
         assert!(gen > 0);
         let members = self.members(gen - 1)?;
-        match reconfig {
-            Reconfig::Join(actor) => {
-                if members.contains(&actor) {
-                    Err(Error::JoinRequestForExistingMember)
-                } else if members.len() >= SOFT_MAX_MEMBERS {
-                    Err(Error::MembersAtCapacity)
-                } else {
-                    Ok(())
-                }
+        validate_membership_change(&reconfig, &members)?;
+        self.config
+            .policy
+            .validate_reconfig(&reconfig, &members, gen)
+    }
+}
+
+/// Applies the forced reconfigs and the reconfigs of the `decisions`, ordered by
+/// generation, on top of the `checkpoint` to find the members at `gen`.
+pub(crate) fn members_at<'a, T: Proposition + 'a, C: SignatureScheme + 'a>(
+    checkpoint: Option<&Checkpoint<T, C>>,
+    forced_reconfigs: &BTreeMap<Generation, BTreeSet<Reconfig<T>>>,
+    decisions: impl IntoIterator<Item = (&'a Generation, &'a Decision<Reconfig<T>, C>)>,
+    gen: Generation,
+) -> Result<BTreeSet<T>> {
+    let (mut members, checkpoint_gen) = match checkpoint {
+        Some(checkpoint) if gen < checkpoint.gen => {
+            return Err(Error::PrunedGeneration {
+                requested_gen: gen,
+                checkpoint_gen: checkpoint.gen,
+            })
+        }
+        Some(checkpoint) => (checkpoint.members.clone(), checkpoint.gen),
+        None => {
+            let mut members = BTreeSet::new();
+            forced_reconfigs
+                .get(&0) // forced reconfigs at generation 0
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .for_each(|r| r.apply(&mut members));
+            (members, 0)
+        }
+    };
+
+    if gen == checkpoint_gen {
+        return Ok(members);
+    }
+
+    for (history_gen, decision) in decisions {
+        if *history_gen <= checkpoint_gen {
+            continue;
+        }
+        forced_reconfigs
+            .get(history_gen)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .for_each(|r| r.apply(&mut members));
+
+        for (reconfig, _sig) in decision.proposals.iter() {
+            reconfig.apply(&mut members);
+        }
+
+        if history_gen == &gen {
+            return Ok(members);
+        }
+    }
+
+    Err(Error::InvalidGeneration(gen))
+}
+
+fn validate_membership_change<T: Proposition>(
+    reconfig: &Reconfig<T>,
+    members: &BTreeSet<T>,
+) -> Result<()> {
+    match reconfig {
+        Reconfig::Join(actor) => {
+            if members.contains(actor) {
+                Err(Error::JoinRequestForExistingMember)
+            } else {
+                Ok(())
             }
-            Reconfig::Leave(actor) => {
-                if !members.contains(&actor) {
-                    Err(Error::LeaveRequestForNonMember)
-                } else {
-                    Ok(())
-                }
+        }
+        Reconfig::Leave(actor) => {
+            if !members.contains(actor) {
+                Err(Error::LeaveRequestForNonMember)
+            } else {
+                Ok(())
             }
         }
     }
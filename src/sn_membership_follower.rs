@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::info;
+
+use crate::sn_membership::{members_at, DEFAULT_CHECKPOINT_INTERVAL};
+use crate::vote::{Proposition, SignedVote};
+use crate::{Bls, Checkpoint, Decision, Error, Generation, Reconfig, Result, SignatureScheme};
+
+/// Tracks the members decided by the elders, without being one of them.
+///
+/// A follower only holds the elders' public keys. It never votes, it rebuilds the
+/// history from the super majority over super majority votes that the elders emit
+/// in [`crate::Membership::anti_entropy`], or from their [`Decision`]s.
+#[derive(Debug)]
+pub struct MembershipFollower<T: Proposition, C: SignatureScheme = Bls> {
+    pub elders: C::PublicKeySet,
+    pub gen: Generation,
+    pub forced_reconfigs: BTreeMap<Generation, BTreeSet<Reconfig<T>>>,
+    pub history: BTreeMap<Generation, Decision<Reconfig<T>, C>>,
+    pub checkpoint: Option<Checkpoint<T, C>>,
+    /// See [`crate::MembershipConfig::checkpoint_interval`].
+    pub checkpoint_interval: Option<Generation>,
+}
+
+impl<T: Proposition, C: SignatureScheme> MembershipFollower<T, C> {
+    pub fn from(elders: C::PublicKeySet) -> Self {
+        MembershipFollower {
+            elders,
+            gen: 0,
+            forced_reconfigs: Default::default(),
+            history: BTreeMap::default(),
+            checkpoint: None,
+            checkpoint_interval: Some(DEFAULT_CHECKPOINT_INTERVAL),
+        }
+    }
+
+    /// Starts following from a checkpoint, signed by `elders`, instead of from
+    /// the first generation.
+    pub fn from_checkpoint(elders: C::PublicKeySet, checkpoint: Checkpoint<T, C>) -> Result<Self> {
+        checkpoint.validate(&C::public_key(&elders))?;
+        let mut follower = Self::from(elders);
+        follower.gen = checkpoint.gen;
+        follower.checkpoint = Some(checkpoint);
+        Ok(follower)
+    }
+
+    pub fn force_join(&mut self, actor: T) {
+        self.force_reconfig(Reconfig::Join(actor))
+    }
+
+    pub fn force_leave(&mut self, actor: T) {
+        self.force_reconfig(Reconfig::Leave(actor))
+    }
+
+    fn force_reconfig(&mut self, reconfig: Reconfig<T>) {
+        let forced_reconfigs = self.forced_reconfigs.entry(self.gen).or_default();
+
+        // remove any opposing reconfigs for this actor
+        match &reconfig {
+            Reconfig::Join(actor) => forced_reconfigs.remove(&Reconfig::Leave(actor.clone())),
+            Reconfig::Leave(actor) => forced_reconfigs.remove(&Reconfig::Join(actor.clone())),
+        };
+        forced_reconfigs.insert(reconfig);
+    }
+
+    /// Installs the elder keys that decide the generations after `self.gen`.
+    pub fn rotate_elders(&mut self, elders: C::PublicKeySet) {
+        self.elders = elders;
+    }
+
+    pub fn members(&self, gen: Generation) -> Result<BTreeSet<T>> {
+        members_at(
+            self.checkpoint.as_ref(),
+            &self.forced_reconfigs,
+            &self.history,
+            gen,
+        )
+    }
+
+    /// Handles a vote of the elders, returns the decision it proves if it is for
+    /// the next generation.
+    ///
+    /// Votes for the generations we already know the decision of are ignored.
+    pub fn handle_signed_vote(
+        &mut self,
+        signed_vote: SignedVote<Reconfig<T>, C>,
+    ) -> Result<Option<Decision<Reconfig<T>, C>>> {
+        let gen = signed_vote.vote.gen;
+        if gen <= self.gen {
+            return Ok(None);
+        }
+        if gen != self.gen + 1 {
+            return Err(Error::BadGeneration {
+                requested_gen: gen,
+                gen: self.gen,
+            });
+        }
+
+        signed_vote.validate(&self.elders, &BTreeSet::new())?;
+        match signed_vote.vote_count().get_decision(&self.elders)? {
+            Some(proposals) => {
+                let decision = Decision {
+                    generation: gen,
+                    proposals,
+                };
+                self.apply_decision(decision.clone())?;
+                Ok(Some(decision))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Handles a decision of the elders, returns whether it was for the next
+    /// generation.
+    pub fn handle_decision(&mut self, decision: Decision<Reconfig<T>, C>) -> Result<bool> {
+        if decision.generation <= self.gen {
+            return Ok(false);
+        }
+        if decision.generation != self.gen + 1 {
+            return Err(Error::BadGeneration {
+                requested_gen: decision.generation,
+                gen: self.gen,
+            });
+        }
+        if decision.proposals.is_empty() {
+            return Err(Error::DecisionHasNoVotes);
+        }
+
+        decision.validate(&C::public_key(&self.elders))?;
+        self.apply_decision(decision)?;
+        Ok(true)
+    }
+
+    fn apply_decision(&mut self, decision: Decision<Reconfig<T>, C>) -> Result<()> {
+        info!(
+            "[FLW] gen {} decided {:?}",
+            decision.generation,
+            BTreeSet::from_iter(decision.proposals.keys())
+        );
+        self.gen = decision.generation;
+        self.history.insert(self.gen, decision);
+        self.maybe_checkpoint()
+    }
+
+    // Mirrors the checkpointing of `Membership`, so a follower's history is
+    // bounded as well.
+    fn maybe_checkpoint(&mut self) -> Result<()> {
+        let interval = match self.checkpoint_interval {
+            Some(interval) if interval > 0 => interval,
+            _ => return Ok(()),
+        };
+        let checkpoint_gen = self.checkpoint.as_ref().map(|c| c.gen).unwrap_or(0);
+        if self.gen - checkpoint_gen < 2 * interval {
+            return Ok(());
+        }
+
+        let gen = self.gen - interval;
+        let members = self.members(gen)?;
+        let decision = self
+            .history
+            .get(&gen)
+            .cloned()
+            .ok_or(Error::InvalidGeneration(gen))?;
+
+        self.history = self.history.split_off(&(gen + 1));
+        self.forced_reconfigs = self.forced_reconfigs.split_off(&(gen + 1));
+        self.checkpoint = Some(Checkpoint {
+            gen,
+            members,
+            decision,
+            signature: None,
+        });
+        Ok(())
+    }
+}
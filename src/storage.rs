@@ -0,0 +1,159 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use core::fmt::Debug;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    Bls, Checkpoint, Decision, Generation, Proposition, Reconfig, Result, SignatureScheme,
+    SignedVote,
+};
+
+const LEN_PREFIX_SIZE: usize = std::mem::size_of::<u64>();
+
+/// An entry in the write-ahead log of a [`crate::Membership`] instance.
+///
+/// The index of the variant is written in the log, so new variants must be
+/// added last to keep reading the logs written before them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(serialize = "", deserialize = "T: Deserialize<'de>"))]
+pub enum Record<T: Proposition, C: SignatureScheme = Bls> {
+    /// A reconfig forced at the given generation
+    ForcedReconfig(Generation, Reconfig<T>),
+    /// A vote received from an elder that was accepted by `handle_signed_vote`
+    Vote(SignedVote<Reconfig<T>, C>),
+    /// A vote we cast
+    Cast(SignedVote<Reconfig<T>, C>),
+    /// The decision reached for a generation
+    Decided(Decision<Reconfig<T>, C>),
+    /// The elder key set (and number of elders) installed for the given generation
+    RotateElders(Generation, C::PublicKeySet, usize),
+    /// The checkpoint a compacted log starts from
+    Checkpoint(Checkpoint<T, C>),
+    /// The elders were announced to be rotated for the given generation
+    ExpectElderRotation(Generation),
+    /// A vote for the next generation kept aside until its elders are rotated
+    HeldVote(SignedVote<Reconfig<T>, C>),
+}
+
+/// Durable storage for the write-ahead log of a [`crate::Membership`] instance.
+pub trait Storage<T: Proposition, C: SignatureScheme = Bls>: Debug + Send {
+    /// Appends a record to the log, the record MUST be durable once this returns.
+    fn append(&mut self, record: &Record<T, C>) -> Result<()>;
+
+    /// Reads back all records in the order they were appended.
+    fn records(&self) -> Result<Vec<Record<T, C>>>;
+
+    /// Replaces the log with `records`, to drop the records of the generations
+    /// pruned by a checkpoint. The log MUST either hold `records` or be left as
+    /// it was once this returns.
+    fn compact(&mut self, records: &[Record<T, C>]) -> Result<()>;
+}
+
+/// A write-ahead log kept in a single file.
+///
+/// Each record is bincode encoded and prefixed with its length, the file is
+/// synced to disk after every append. A record that was only partially written
+/// when we crashed is discarded when the file is re-opened. The log is compacted
+/// by writing the records to a new file that is then renamed over the log.
+#[derive(Debug)]
+pub struct FileStorage {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let valid_len = frames(&bytes)
+            .map(|frame| frame.len() + LEN_PREFIX_SIZE)
+            .sum::<usize>();
+        if valid_len < bytes.len() {
+            log::warn!(
+                "discarding {} bytes of a partially written record in {path:?}",
+                bytes.len() - valid_len
+            );
+            file.set_len(valid_len as u64)?;
+            file.sync_data()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Self { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl<T, C> Storage<T, C> for FileStorage
+where
+    T: Proposition + DeserializeOwned,
+    C: SignatureScheme,
+{
+    fn append(&mut self, record: &Record<T, C>) -> Result<()> {
+        self.file.write_all(&frame(record)?)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    fn records(&self) -> Result<Vec<Record<T, C>>> {
+        let bytes = std::fs::read(&self.path)?;
+        frames(&bytes)
+            .map(|frame| Ok(bincode::deserialize(frame)?))
+            .collect()
+    }
+
+    fn compact(&mut self, records: &[Record<T, C>]) -> Result<()> {
+        let mut compacted_path = self.path.clone().into_os_string();
+        compacted_path.push(".compact");
+
+        let mut compacted = File::create(&compacted_path)?;
+        for record in records {
+            compacted.write_all(&frame(record)?)?;
+        }
+        compacted.sync_all()?;
+        std::fs::rename(&compacted_path, &self.path)?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+/// Encodes `record` in a length prefixed frame.
+fn frame<T: Proposition, C: SignatureScheme>(record: &Record<T, C>) -> Result<Vec<u8>> {
+    let record_bytes = bincode::serialize(record)?;
+    let mut frame = Vec::with_capacity(LEN_PREFIX_SIZE + record_bytes.len());
+    frame.extend((record_bytes.len() as u64).to_le_bytes());
+    frame.extend(record_bytes);
+    Ok(frame)
+}
+
+/// Iterates over the complete length prefixed frames in `bytes`.
+fn frames(mut bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let (len_bytes, rest) = bytes.split_first_chunk::<LEN_PREFIX_SIZE>()?;
+        let len = u64::from_le_bytes(*len_bytes) as usize;
+        if rest.len() < len {
+            return None;
+        }
+        let (frame, rest) = rest.split_at(len);
+        bytes = rest;
+        Some(frame)
+    })
+}
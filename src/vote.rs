@@ -1,26 +1,26 @@
 use std::collections::{BTreeMap, BTreeSet};
 
-use blsttc::{PublicKeySet, SignatureShare};
 use core::fmt::Debug;
 use serde::{Deserialize, Serialize};
 
 use crate::sn_membership::Generation;
-use crate::{Candidate, Error, Fault, NodeId, Result, VoteCount};
+use crate::{Bls, Candidate, Error, Fault, NodeId, Result, SignatureScheme, VoteCount};
 
 pub trait Proposition: Ord + Clone + Debug + Serialize {}
 impl<T: Ord + Clone + Debug + Serialize> Proposition for T {}
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub enum Ballot<T: Proposition> {
+#[serde(bound(serialize = "", deserialize = "T: Deserialize<'de>"))]
+pub enum Ballot<T: Proposition, C: SignatureScheme = Bls> {
     Propose(T),
-    Merge(BTreeSet<SignedVote<T>>),
+    Merge(BTreeSet<SignedVote<T, C>>),
     SuperMajority {
-        votes: BTreeSet<SignedVote<T>>,
-        proposals: BTreeMap<T, (NodeId, SignatureShare)>,
+        votes: BTreeSet<SignedVote<T, C>>,
+        proposals: BTreeMap<T, (NodeId, C::SignatureShare)>,
     },
 }
 
-impl<T: Proposition> Debug for Ballot<T> {
+impl<T: Proposition, C: SignatureScheme> Debug for Ballot<T, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Ballot::Propose(r) => write!(f, "P({r:?})"),
@@ -35,9 +35,9 @@ impl<T: Proposition> Debug for Ballot<T> {
     }
 }
 
-pub fn simplify_votes<T: Proposition>(
-    signed_votes: &BTreeSet<SignedVote<T>>,
-) -> BTreeSet<SignedVote<T>> {
+pub fn simplify_votes<T: Proposition, C: SignatureScheme>(
+    signed_votes: &BTreeSet<SignedVote<T, C>>,
+) -> BTreeSet<SignedVote<T, C>> {
     let mut simpler_votes = BTreeSet::new();
     for v in signed_votes.iter() {
         let this_vote_is_superseded = signed_votes
@@ -52,8 +52,8 @@ pub fn simplify_votes<T: Proposition>(
     simpler_votes
 }
 
-pub fn proposals<T: Proposition>(
-    votes: &BTreeSet<SignedVote<T>>,
+pub fn proposals<T: Proposition, C: SignatureScheme>(
+    votes: &BTreeSet<SignedVote<T, C>>,
     known_faulty: &BTreeSet<NodeId>,
 ) -> BTreeSet<T> {
     BTreeSet::from_iter(
@@ -66,7 +66,7 @@ pub fn proposals<T: Proposition>(
     )
 }
 
-impl<T: Proposition> Ballot<T> {
+impl<T: Proposition, C: SignatureScheme> Ballot<T, C> {
     pub fn as_proposal(&self) -> Option<&T> {
         match &self {
             Ballot::Propose(p) => Some(p),
@@ -88,13 +88,14 @@ impl<T: Proposition> Ballot<T> {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct Vote<T: Proposition> {
+#[serde(bound(serialize = "", deserialize = "T: Deserialize<'de>"))]
+pub struct Vote<T: Proposition, C: SignatureScheme = Bls> {
     pub gen: Generation,
-    pub ballot: Ballot<T>,
-    pub faults: BTreeSet<Fault<T>>,
+    pub ballot: Ballot<T, C>,
+    pub faults: BTreeSet<Fault<T, C>>,
 }
 
-impl<T: Proposition> Debug for Vote<T> {
+impl<T: Proposition, C: SignatureScheme> Debug for Vote<T, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "G{}-{:?}", self.gen, self.ballot)?;
 
@@ -105,13 +106,13 @@ impl<T: Proposition> Debug for Vote<T> {
     }
 }
 
-impl<T: Proposition> Vote<T> {
+impl<T: Proposition, C: SignatureScheme> Vote<T, C> {
     pub fn validate(
         &self,
-        voters: &PublicKeySet,
-        valid_votes_memo: &BTreeSet<SignatureShare>,
+        voters: &C::PublicKeySet,
+        valid_votes_memo: &BTreeSet<C::SignatureShare>,
     ) -> Result<()> {
-        let validate_child_votes = |child_votes: &BTreeSet<SignedVote<T>>| {
+        let validate_child_votes = |child_votes: &BTreeSet<SignedVote<T, C>>| {
             for child_vote in child_votes {
                 let child_gen = child_vote.vote.gen;
                 let merge_gen = self.gen;
@@ -148,7 +149,9 @@ impl<T: Proposition> Vote<T> {
                     Err(Error::SuperMajorityProposalsDoesNotMatchVoteProposals)
                 } else if proposals
                     .iter()
-                    .try_for_each(|(p, (id, sig))| crate::verify_sig_share(&p, sig, *id, voters))
+                    .try_for_each(|(p, (id, sig))| {
+                        crate::verify_sig_share::<C, _>(&p, sig, *id, voters)
+                    })
                     .is_err()
                 {
                     Err(Error::InvalidElderSignature)
@@ -163,8 +166,9 @@ impl<T: Proposition> Vote<T> {
         matches!(self.ballot, Ballot::SuperMajority { .. })
     }
 
+    /// The bytes signed by the voter.
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(&self)?)
+        Ok(crate::signing::signing_bytes(&self)?)
     }
 
     pub fn faulty_ids(&self) -> BTreeSet<NodeId> {
@@ -187,19 +191,20 @@ impl<T: Proposition> Vote<T> {
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct SignedVote<T: Proposition> {
-    pub vote: Vote<T>,
+#[serde(bound(serialize = "", deserialize = "T: Deserialize<'de>"))]
+pub struct SignedVote<T: Proposition, C: SignatureScheme = Bls> {
+    pub vote: Vote<T, C>,
     pub voter: NodeId,
-    pub sig: SignatureShare,
+    pub sig: C::SignatureShare,
 }
 
-impl<T: Proposition> Debug for SignedVote<T> {
+impl<T: Proposition, C: SignatureScheme> Debug for SignedVote<T, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}@{}", self.vote, self.voter)
     }
 }
 
-impl<T: Proposition> SignedVote<T> {
+impl<T: Proposition, C: SignatureScheme> SignedVote<T, C> {
     pub fn candidate(&self) -> Candidate<T> {
         match &self.vote.ballot {
             Ballot::SuperMajority { votes, .. } => VoteCount::count(votes, &self.vote.faulty_ids())
@@ -213,16 +218,16 @@ impl<T: Proposition> SignedVote<T> {
         }
     }
 
-    pub fn validate_signature(&self, voters: &PublicKeySet) -> Result<()> {
-        crate::verify_sig_share(&self.vote, &self.sig, self.voter, voters)
+    pub fn validate_signature(&self, voters: &C::PublicKeySet) -> Result<()> {
+        crate::verify_sig_share::<C, _>(&self.vote, &self.sig, self.voter, voters)
     }
 
     /// Validates a vote recursively all the way down to the proposition (T)
     /// Assumes those propositions are correct, they MUST be checked beforehand by the caller
     pub fn validate(
         &self,
-        voters: &PublicKeySet,
-        valid_votes_cache: &BTreeSet<SignatureShare>,
+        voters: &C::PublicKeySet,
+        valid_votes_cache: &BTreeSet<C::SignatureShare>,
     ) -> Result<()> {
         self.validate_signature(voters)?;
         self.vote.validate(voters, valid_votes_cache)?;
@@ -232,10 +237,10 @@ impl<T: Proposition> SignedVote<T> {
 
     pub fn detect_byzantine_faults(
         &self,
-        voters: &PublicKeySet,
-        existing_votes: &BTreeMap<NodeId, SignedVote<T>>,
-        valid_votes_cache: &BTreeSet<SignatureShare>,
-    ) -> std::result::Result<(), BTreeMap<NodeId, Fault<T>>> {
+        voters: &C::PublicKeySet,
+        existing_votes: &BTreeMap<NodeId, SignedVote<T, C>>,
+        valid_votes_cache: &BTreeSet<C::SignatureShare>,
+    ) -> std::result::Result<(), BTreeMap<NodeId, Fault<T, C>>> {
         let mut faults = BTreeMap::new();
         for vote in self.unpack_votes() {
             if valid_votes_cache.contains(&vote.sig) {
@@ -302,7 +307,7 @@ impl<T: Proposition> SignedVote<T> {
         }
     }
 
-    pub fn vote_count(&self) -> VoteCount<T> {
+    pub fn vote_count(&self) -> VoteCount<T, C> {
         VoteCount::count([self], &self.vote.faulty_ids())
     }
 }
@@ -3,9 +3,7 @@ use std::{
     collections::{BTreeMap, BTreeSet},
 };
 
-use blsttc::{PublicKeySet, Signature, SignatureShare};
-
-use crate::{Ballot, NodeId, Proposition, Result, SignedVote};
+use crate::{Ballot, Bls, NodeId, Proposition, Result, SignatureScheme, SignedVote};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Candidate<T> {
@@ -23,12 +21,12 @@ impl<T> Default for Candidate<T> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SuperMajorityCount<T> {
+pub struct SuperMajorityCount<T, C: SignatureScheme = Bls> {
     pub count: usize,
-    pub proposals: BTreeMap<T, BTreeMap<u64, SignatureShare>>,
+    pub proposals: BTreeMap<T, BTreeMap<NodeId, C::SignatureShare>>,
 }
 
-impl<T> Default for SuperMajorityCount<T> {
+impl<T, C: SignatureScheme> Default for SuperMajorityCount<T, C> {
     fn default() -> Self {
         Self {
             count: 0,
@@ -38,13 +36,13 @@ impl<T> Default for SuperMajorityCount<T> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct VoteCount<T> {
+pub struct VoteCount<T, C: SignatureScheme = Bls> {
     pub candidates: BTreeMap<Candidate<T>, usize>,
-    pub super_majorities: BTreeMap<Candidate<T>, SuperMajorityCount<T>>,
+    pub super_majorities: BTreeMap<Candidate<T>, SuperMajorityCount<T, C>>,
     pub voters: BTreeSet<NodeId>,
 }
 
-impl<T> Default for VoteCount<T> {
+impl<T, C: SignatureScheme> Default for VoteCount<T, C> {
     fn default() -> Self {
         Self {
             candidates: Default::default(),
@@ -54,14 +52,14 @@ impl<T> Default for VoteCount<T> {
     }
 }
 
-impl<T: Proposition> VoteCount<T> {
-    pub fn count<V: Borrow<SignedVote<T>>>(
+impl<T: Proposition, C: SignatureScheme> VoteCount<T, C> {
+    pub fn count<V: Borrow<SignedVote<T, C>>>(
         votes: impl IntoIterator<Item = V>,
         faulty: &BTreeSet<NodeId>,
     ) -> Self {
-        let mut count: VoteCount<T> = VoteCount::default();
+        let mut count: VoteCount<T, C> = VoteCount::default();
 
-        let mut votes_by_honest_voter: BTreeMap<NodeId, SignedVote<T>> = Default::default();
+        let mut votes_by_honest_voter: BTreeMap<NodeId, SignedVote<T, C>> = Default::default();
 
         for vote in votes.into_iter() {
             for unpacked_vote in vote.borrow().unpack_votes() {
@@ -96,7 +94,7 @@ impl<T: Proposition> VoteCount<T> {
                         .proposals
                         .entry(t.clone())
                         .or_default()
-                        .insert(*id as u64, sig.clone());
+                        .insert(*id, sig.clone());
                 }
             }
 
@@ -120,13 +118,14 @@ impl<T: Proposition> VoteCount<T> {
 
     pub fn super_majority_with_most_votes(
         &self,
-    ) -> Option<(&Candidate<T>, &SuperMajorityCount<T>)> {
+    ) -> Option<(&Candidate<T>, &SuperMajorityCount<T, C>)> {
         self.super_majorities
             .iter()
             .max_by_key(|(_, sm_count)| sm_count.count)
     }
 
-    pub fn is_split_vote(&self, voters: &PublicKeySet, n_voters: usize) -> bool {
+    pub fn is_split_vote(&self, voters: &C::PublicKeySet, n_voters: usize) -> bool {
+        let threshold = C::threshold(voters);
         let most_votes = self
             .candidate_with_most_votes()
             .map(|(_, c)| c)
@@ -139,25 +138,28 @@ impl<T: Proposition> VoteCount<T> {
 
         // We're in a split vote if even in the best case scenario where all
         // remaining votes are not enough to take us above the threshold.
-        self.voters.len() > voters.threshold() && predicted_votes <= voters.threshold()
+        self.voters.len() > threshold && predicted_votes <= threshold
     }
 
-    pub fn do_we_have_supermajority(&self, voters: &PublicKeySet) -> bool {
+    pub fn do_we_have_supermajority(&self, voters: &C::PublicKeySet) -> bool {
         let most_votes = self
             .candidate_with_most_votes()
             .map(|(_, c)| c)
             .unwrap_or_default();
 
-        most_votes > voters.threshold()
+        most_votes > C::threshold(voters)
     }
 
-    pub fn get_decision(&self, voters: &PublicKeySet) -> Result<Option<BTreeMap<T, Signature>>> {
+    pub fn get_decision(
+        &self,
+        voters: &C::PublicKeySet,
+    ) -> Result<Option<BTreeMap<T, C::Signature>>> {
         if let Some((_candidate, sm_count)) = self.super_majority_with_most_votes() {
-            if sm_count.count > voters.threshold() {
+            if sm_count.count > C::threshold(voters) {
                 let proposals = sm_count
                     .proposals
                     .iter()
-                    .map(|(prop, sigs)| Ok((prop.clone(), voters.combine_signatures(sigs)?)))
+                    .map(|(prop, sigs)| Ok((prop.clone(), C::combine_shares(voters, sigs)?)))
                     .collect::<Result<_>>()?;
                 return Ok(Some(proposals));
             }
@@ -0,0 +1,226 @@
+//! The wire format of the messages sent between the parties.
+//!
+//! Each message is sent in a frame:
+//!
+//! ```text
+//! | version: u8 | protocol: u8 | length: u32 LE | payload: length bytes |
+//! ```
+//!
+//! The payload is the bincode encoding of the message, with a fixed
+//! configuration. The frames can be read back to back from a stream, and the
+//! protocol tells which message to decode before decoding it.
+//!
+//! The wire format is only the transport encoding, the bytes that are signed
+//! are encoded by [`crate::signing`].
+
+use bincode::Options;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::mvba::bundle::Bundle;
+use crate::{Error, Proposition, Result, SignatureScheme, SignedVote};
+
+/// The version of the wire format, the first byte of a frame.
+pub const WIRE_VERSION: u8 = 1;
+
+/// The size of the frame header.
+pub const HEADER_LEN: usize = 6;
+
+/// The protocol of the message in a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ProtocolId {
+    /// A [`SignedVote`] of the membership or the handover.
+    Vote = 1,
+    /// A [`Bundle`] of an MVBA instance.
+    Mvba = 2,
+}
+
+impl TryFrom<u8> for ProtocolId {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(ProtocolId::Vote),
+            2 => Ok(ProtocolId::Mvba),
+            _ => Err(Error::UnknownProtocol(value)),
+        }
+    }
+}
+
+/// A message that is sent in a frame.
+pub trait WireMessage: Serialize + DeserializeOwned {
+    const PROTOCOL: ProtocolId;
+}
+
+impl<T, C> WireMessage for SignedVote<T, C>
+where
+    T: Proposition + DeserializeOwned,
+    C: SignatureScheme,
+{
+    const PROTOCOL: ProtocolId = ProtocolId::Vote;
+}
+
+impl<P: Serialize + DeserializeOwned> WireMessage for Bundle<P> {
+    const PROTOCOL: ProtocolId = ProtocolId::Mvba;
+}
+
+// The bincode configuration of the payloads.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .reject_trailing_bytes()
+}
+
+/// Encodes `msg` in a frame.
+pub fn encode<M: WireMessage>(msg: &M) -> Result<Vec<u8>> {
+    let payload = options().serialize(msg)?;
+    let len = u32::try_from(payload.len()).map_err(|_| Error::FrameTooLarge(payload.len()))?;
+
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.push(WIRE_VERSION);
+    frame.push(M::PROTOCOL as u8);
+    frame.extend(len.to_le_bytes());
+    frame.extend(payload);
+    Ok(frame)
+}
+
+/// Decodes the message of the frame at the start of `bytes`.
+/// It returns the message, and the length of the frame.
+///
+/// `Error::TruncatedFrame` is returned if `bytes` doesn't hold the whole frame yet.
+pub fn decode<M: WireMessage>(bytes: &[u8]) -> Result<(M, usize)> {
+    let (protocol, len) = header(bytes)?;
+    if protocol != M::PROTOCOL {
+        return Err(Error::WrongProtocol {
+            expected: M::PROTOCOL,
+            got: protocol,
+        });
+    }
+    let payload = bytes
+        .get(HEADER_LEN..HEADER_LEN + len)
+        .ok_or(Error::TruncatedFrame)?;
+    let msg = options().with_limit(len as u64).deserialize(payload)?;
+    Ok((msg, HEADER_LEN + len))
+}
+
+/// The protocol of the frame at the start of `bytes`.
+pub fn protocol(bytes: &[u8]) -> Result<ProtocolId> {
+    Ok(header(bytes)?.0)
+}
+
+// header reads the protocol and the payload length of the frame.
+fn header(bytes: &[u8]) -> Result<(ProtocolId, usize)> {
+    let header = bytes.get(..HEADER_LEN).ok_or(Error::TruncatedFrame)?;
+    if header[0] != WIRE_VERSION {
+        return Err(Error::UnsupportedWireVersion(header[0]));
+    }
+    let protocol = ProtocolId::try_from(header[1])?;
+    let len = u32::from_le_bytes(header[2..].try_into().expect("4 bytes"));
+    Ok((protocol, len as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ballot, Vote};
+    use blsttc::SecretKeyShare;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn signed_vote(gen: u64) -> SignedVote<u8> {
+        let mut sk_bytes = [0; 32];
+        sk_bytes[31] = 1;
+        let sk = SecretKeyShare::from_bytes(sk_bytes).unwrap();
+        let vote = Vote {
+            gen,
+            ballot: Ballot::Propose(5),
+            faults: Default::default(),
+        };
+        let sig = sk.sign(vote.to_bytes().unwrap());
+        SignedVote {
+            vote,
+            voter: 2,
+            sig,
+        }
+    }
+
+    #[test]
+    fn test_wire_golden() {
+        let vote = signed_vote(3);
+        let frame = encode(&vote).unwrap();
+
+        // version | protocol | length | gen | Propose | 5 | no faults | voter | signature
+        // The BLS signatures are deterministic, the signature pins the signing
+        // bytes of the vote too.
+        assert_eq!(
+            hex(&frame),
+            concat!(
+                "01",
+                "01",
+                "7d000000",
+                "0300000000000000",
+                "00000000",
+                "05",
+                "0000000000000000",
+                "0200000000000000",
+                "90484d9dde2ab51a0277bf465a2bc7828f2cc630aea324cd5c814e15c939e48f",
+                "8820fb42009967524ec2cd49f33206150c75c88be1a664bc74eb47528e46cbeb",
+                "b6c93f774093b64ac5707158fb0a2ffbf358f2ad0f0909ce3bbf1b8cf558506e"
+            )
+        );
+        assert_eq!(protocol(&frame).unwrap(), ProtocolId::Vote);
+        assert_eq!(decode(&frame).unwrap(), (vote, frame.len()));
+    }
+
+    #[test]
+    fn test_back_to_back_frames() {
+        let mut stream = encode(&signed_vote(1)).unwrap();
+        stream.extend(encode(&signed_vote(2)).unwrap());
+
+        let (first, len): (SignedVote<u8>, _) = decode(&stream).unwrap();
+        let (second, _): (SignedVote<u8>, _) = decode(&stream[len..]).unwrap();
+        assert_eq!((first.vote.gen, second.vote.gen), (1, 2));
+
+        // the second frame is not whole yet
+        let result = decode::<SignedVote<u8>>(&stream[len..stream.len() - 1]);
+        assert!(matches!(result, Err(Error::TruncatedFrame)));
+        let result = decode::<SignedVote<u8>>(&stream[len..len + 3]);
+        assert!(matches!(result, Err(Error::TruncatedFrame)));
+    }
+
+    #[test]
+    fn test_invalid_frames() {
+        let frame = encode(&signed_vote(1)).unwrap();
+
+        let mut later = frame.clone();
+        later[0] = WIRE_VERSION + 1;
+        let result = decode::<SignedVote<u8>>(&later);
+        assert!(matches!(result, Err(Error::UnsupportedWireVersion(2))));
+
+        let mut unknown = frame.clone();
+        unknown[1] = 0xff;
+        assert!(matches!(
+            protocol(&unknown),
+            Err(Error::UnknownProtocol(0xff))
+        ));
+
+        let result = decode::<Bundle<u8>>(&frame);
+        assert!(matches!(
+            result,
+            Err(Error::WrongProtocol {
+                expected: ProtocolId::Mvba,
+                got: ProtocolId::Vote
+            })
+        ));
+
+        // the payload has to fill the frame
+        let mut padded = frame.clone();
+        padded.push(0);
+        padded[2..HEADER_LEN].copy_from_slice(&(frame.len() as u32 - 5).to_le_bytes());
+        let result = decode::<SignedVote<u8>>(&padded);
+        assert!(matches!(result, Err(Error::Encoding(_))));
+    }
+}
@@ -9,7 +9,10 @@ use log::info;
 use rand::prelude::{IteratorRandom, StdRng};
 use rand::Rng;
 
-use sn_consensus::{Ballot, Error, Handover, NodeId, Result, SignedVote, Vote, VoteResponse};
+use sn_consensus::liveness::DEFAULT_STALL_TIMEOUT;
+use sn_consensus::{
+    Action, Ballot, Error, Handover, NodeId, Result, SignedVote, Vote, VoteResponse,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Packet {
@@ -21,7 +24,6 @@ pub struct Packet {
 #[derive(Default, Debug)]
 pub struct Net {
     pub procs: Vec<Handover<u8>>,
-    pub proposals: BTreeSet<u8>,
     pub packets: BTreeMap<NodeId, VecDeque<Packet>>,
     pub delivered_packets: Vec<Packet>,
 }
@@ -34,12 +36,13 @@ impl Net {
         );
         let elders_sk = SecretKeySet::random(threshold, &mut rng);
 
-        let procs = Vec::from_iter((1..=n).map(|i| {
+        let procs = Vec::from_iter((1..=n as NodeId).map(|i| {
             Handover::from(
-                (i as u8, elders_sk.secret_key_share(i)),
+                (i, elders_sk.secret_key_share(i)),
                 elders_sk.public_keys(),
                 n,
                 0,
+                DEFAULT_STALL_TIMEOUT,
             )
         }));
         Self {
@@ -220,6 +223,23 @@ impl Net {
         Ok(())
     }
 
+    /// Advance every proc's logical clock, queueing whatever they send out.
+    pub fn tick(&mut self) -> Result<()> {
+        for i in 0..self.procs.len() {
+            let source = self.procs[i].id();
+            for action in self.procs[i].tick()? {
+                match action {
+                    Action::Send(dest, vote) => {
+                        self.enqueue_packets([Packet { source, dest, vote }])
+                    }
+                    Action::Broadcast(vote) => self.broadcast(source, vote),
+                    Action::Decided(_) | Action::FaultDetected(..) => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn purge_empty_queues(&mut self) {
         self.packets = core::mem::take(&mut self.packets)
             .into_iter()
@@ -9,8 +9,8 @@ use log::info;
 use rand::prelude::{IteratorRandom, StdRng};
 use rand::Rng;
 use sn_consensus::{
-    consensus::VoteResponse, Ballot, Decision, Error, Generation, Membership, NodeId, Reconfig,
-    Result, SignedVote, Vote,
+    consensus::VoteResponse, Action, Ballot, Decision, Error, Generation, Membership,
+    MembershipConfig, NodeId, Reconfig, Result, SignedVote, Vote,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,7 +24,6 @@ pub struct Packet {
 pub struct Net {
     pub procs: Vec<Membership<u8>>,
     pub reconfigs_by_gen: BTreeMap<Generation, BTreeSet<Reconfig<u8>>>,
-    pub members_at_gen: BTreeMap<Generation, BTreeSet<u8>>,
     pub packets: BTreeMap<NodeId, VecDeque<Packet>>,
     pub delivered_packets: Vec<Packet>,
     pub decisions: BTreeMap<Generation, Decision<Reconfig<u8>>>,
@@ -35,9 +34,10 @@ impl Net {
         let elders_sk = SecretKeySet::random(threshold as usize, &mut rng);
         let procs = Vec::from_iter((1u8..(n + 1)).map(|i| {
             Membership::from(
-                (i, elders_sk.secret_key_share(i as u64)),
+                (i as NodeId, elders_sk.secret_key_share(i as u64)),
                 elders_sk.public_keys(),
                 n as usize,
+                MembershipConfig::default(),
             )
         }));
         Self {
@@ -196,7 +196,8 @@ impl Net {
                 assert_eq!(net_d.proposals, proc_d.proposals);
             }
             (None, Some(proc_d)) => {
-                assert!(proc_d.validate(&proc.consensus.elders.public_key()).is_ok());
+                let elders = &proc.consensus_at_gen(packet_gen)?.elders;
+                assert!(proc_d.validate(&elders.public_key()).is_ok());
                 self.decisions.insert(packet_gen, proc_d);
             }
             (None | Some(_), None) => (),
@@ -264,6 +265,21 @@ impl Net {
         );
     }
 
+    /// Advance every proc's logical clock, queueing whatever they send out.
+    pub fn tick(&mut self) -> Result<()> {
+        for i in 0..self.procs.len() {
+            let source = self.procs[i].id();
+            for action in self.procs[i].tick()? {
+                match action {
+                    Action::Send(dest, vote) => self.send(source, dest, vote),
+                    Action::Broadcast(vote) => self.broadcast(source, vote),
+                    Action::Decided(_) | Action::FaultDetected(..) => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn generate_msc(&self, name: &str) -> Result<()> {
         // See: http://www.mcternan.me.uk/mscgen/
         let mut msc = String::from(
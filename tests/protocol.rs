@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use blsttc::SecretKeySet;
+use rand::{prelude::StdRng, Rng, SeedableRng};
+use sn_consensus::liveness::DEFAULT_STALL_TIMEOUT;
+use sn_consensus::mvba::{consensus::Consensus, tag::Domain};
+use sn_consensus::{
+    Action, Actions, Handover, Membership, MembershipConfig, NodeId, Protocol, Reconfig, Result,
+};
+
+/// A single event loop that can host any of the protocols.
+struct EventLoop<P: Protocol> {
+    procs: BTreeMap<NodeId, P>,
+    queue: VecDeque<(NodeId, NodeId, P::Message)>,
+    decisions: BTreeMap<NodeId, Vec<P::Decision>>,
+}
+
+impl<P: Protocol> EventLoop<P> {
+    fn new(procs: impl IntoIterator<Item = (NodeId, P)>) -> Self {
+        Self {
+            procs: BTreeMap::from_iter(procs),
+            queue: VecDeque::new(),
+            decisions: BTreeMap::new(),
+        }
+    }
+
+    fn perform(&mut self, source: NodeId, actions: Actions<P>) {
+        for action in actions {
+            match action {
+                Action::Send(dest, msg) => self.queue.push_back((source, dest, msg)),
+                Action::Broadcast(msg) => {
+                    for dest in self.procs.keys() {
+                        self.queue.push_back((source, *dest, msg.clone()));
+                    }
+                }
+                Action::Decided(decision) => {
+                    self.decisions.entry(source).or_default().push(decision)
+                }
+                Action::FaultDetected(id, fault) => panic!("unexpected fault by {id}: {fault:?}"),
+            }
+        }
+    }
+
+    fn run(&mut self, rng: &mut StdRng) {
+        loop {
+            while !self.queue.is_empty() {
+                let (source, dest, msg) = self
+                    .queue
+                    .remove(rng.gen_range(0..self.queue.len()))
+                    .unwrap();
+                let proc = self.procs.get_mut(&dest).unwrap();
+                let actions = proc.handle_message(source, msg).unwrap();
+                self.perform(dest, actions);
+            }
+
+            // the network has gone quiet, let the procs act on the timeout
+            let ids = Vec::from_iter(self.procs.keys().copied());
+            for id in ids {
+                let actions = self.procs.get_mut(&id).unwrap().tick().unwrap();
+                self.perform(id, actions);
+            }
+            if self.queue.is_empty() {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_event_loop_hosts_membership() -> Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(2, &mut rng);
+    let mut event_loop = EventLoop::new((1..=4).map(|id| {
+        let proc = Membership::<u8>::from(
+            (id, elders_sk.secret_key_share(id)),
+            elders_sk.public_keys(),
+            4,
+            MembershipConfig::default(),
+        );
+        (id, proc)
+    }));
+
+    let vote = event_loop
+        .procs
+        .get_mut(&1)
+        .unwrap()
+        .propose(Reconfig::Join(1))?;
+    event_loop.perform(1, vec![Action::Broadcast(vote)]);
+    event_loop.run(&mut rng);
+
+    assert_eq!(event_loop.decisions.len(), 4);
+    for (id, decisions) in event_loop.decisions {
+        assert_eq!(decisions.len(), 1, "{id} decided more than once");
+        assert_eq!(decisions[0].generation, 1);
+        assert!(decisions[0].proposals.contains_key(&Reconfig::Join(1)));
+        assert!(decisions[0]
+            .validate(&elders_sk.public_keys().public_key())
+            .is_ok());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_event_loop_hosts_handover() -> Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(2, &mut rng);
+    let mut event_loop = EventLoop::new((1..=4).map(|id| {
+        let proc = Handover::<u8>::from(
+            (id, elders_sk.secret_key_share(id)),
+            elders_sk.public_keys(),
+            4,
+            0,
+            DEFAULT_STALL_TIMEOUT,
+        );
+        (id, proc)
+    }));
+
+    for id in 1..=4 {
+        let vote = event_loop.procs.get_mut(&id).unwrap().propose(id as u8)?;
+        event_loop.perform(id, vec![Action::Broadcast(vote)]);
+    }
+    event_loop.run(&mut rng);
+
+    assert_eq!(event_loop.decisions.len(), 4);
+    let decision = event_loop.decisions[&1][0].clone();
+    for decisions in event_loop.decisions.values() {
+        assert_eq!(decisions, &vec![decision.clone()]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_event_loop_hosts_mvba() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(2, &mut rng);
+    let parties = Vec::from_iter(0..4);
+    let mut event_loop = EventLoop::new(parties.iter().map(|id| {
+        let proc = Consensus::init(
+            Domain::new("test-protocol", 0),
+            *id,
+            elders_sk.secret_key_share(id),
+            elders_sk.public_keys(),
+            parties.clone(),
+            |_, _: &char| true,
+        );
+        (*id, proc)
+    }));
+
+    for id in parties.iter() {
+        let proposal = rng.gen_range('a'..='z');
+        let outgoings = event_loop
+            .procs
+            .get_mut(id)
+            .unwrap()
+            .propose(proposal)
+            .unwrap();
+        let actions = Vec::from_iter(outgoings.into_iter().map(Action::from));
+        event_loop.perform(*id, actions);
+    }
+    event_loop.run(&mut rng);
+
+    assert_eq!(event_loop.decisions.len(), 4);
+    let decision = event_loop.decisions[&0][0].clone();
+    assert!(decision
+        .validate(&elders_sk.public_keys().public_key())
+        .unwrap());
+    for decisions in event_loop.decisions.values() {
+        assert_eq!(decisions, &vec![decision.clone()]);
+    }
+}
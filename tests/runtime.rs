@@ -0,0 +1,163 @@
+#![cfg(feature = "tokio")]
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use blsttc::SecretKeySet;
+use rand::{prelude::StdRng, SeedableRng};
+use sn_consensus::liveness::DEFAULT_STALL_TIMEOUT;
+use sn_consensus::mvba::{consensus::Consensus, tag::Domain};
+use sn_consensus::runtime::{channel_network, Event, Runner, Transport};
+use sn_consensus::{Action, Handover, Membership, MembershipConfig, NodeId, Reconfig};
+use tokio::time::timeout;
+
+const TICK: Duration = Duration::from_millis(10);
+const DEADLINE: Duration = Duration::from_secs(30);
+
+/// Drops the first broadcasts sent through it.
+struct Lossy<Tr> {
+    inner: Tr,
+    drops: usize,
+}
+
+impl<M: Send + 'static, Tr: Transport<M>> Transport<M> for Lossy<Tr> {
+    async fn send(&mut self, recipient: NodeId, message: M) {
+        self.inner.send(recipient, message).await
+    }
+
+    async fn broadcast(&mut self, message: M) {
+        if self.drops > 0 {
+            self.drops -= 1;
+        } else {
+            self.inner.broadcast(message).await
+        }
+    }
+
+    async fn recv(&mut self) -> Option<(NodeId, M)> {
+        self.inner.recv().await
+    }
+}
+
+#[tokio::test]
+async fn test_runner_decides_membership() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(2, &mut rng);
+    let mut transports = channel_network(1..=4);
+
+    let mut handles = Vec::from_iter((1..=4).map(|id| {
+        let proc = Membership::<u8>::from(
+            (id, elders_sk.secret_key_share(id)),
+            elders_sk.public_keys(),
+            4,
+            MembershipConfig::default(),
+        );
+        // the proposal of the first elder is lost, the tick has to re-send it
+        let transport = Lossy {
+            inner: transports.remove(&id).unwrap(),
+            drops: if id == 1 { 1 } else { 0 },
+        };
+        Runner::new(proc, transport).tick_interval(TICK).spawn()
+    }));
+
+    handles[0]
+        .call(|proc| Ok(vec![Action::Broadcast(proc.propose(Reconfig::Join(1))?)]))
+        .await
+        .unwrap();
+
+    for handle in handles.iter_mut() {
+        match timeout(DEADLINE, handle.next_event()).await.unwrap() {
+            Some(Event::Decided(decision)) => {
+                assert_eq!(decision.generation, 1);
+                assert!(decision.proposals.contains_key(&Reconfig::Join(1)));
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    for handle in handles {
+        let proc = handle.stop().await.unwrap();
+        assert_eq!(proc.gen, 1);
+        assert_eq!(proc.members(1).unwrap(), BTreeSet::from_iter([1]));
+    }
+}
+
+#[tokio::test]
+async fn test_runner_decides_handover() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(2, &mut rng);
+    let mut transports = channel_network(1..=4);
+
+    let mut handles = Vec::from_iter((1..=4).map(|id| {
+        let proc = Handover::<u8>::from(
+            (id, elders_sk.secret_key_share(id)),
+            elders_sk.public_keys(),
+            4,
+            0,
+            DEFAULT_STALL_TIMEOUT,
+        );
+        Runner::new(proc, transports.remove(&id).unwrap())
+            .tick_interval(TICK)
+            .spawn()
+    }));
+
+    handles[0]
+        .call(|proc| Ok(vec![Action::Broadcast(proc.propose(7)?)]))
+        .await
+        .unwrap();
+
+    let mut decisions = Vec::new();
+    for handle in handles.iter_mut() {
+        match timeout(DEADLINE, handle.next_event()).await.unwrap() {
+            Some(Event::Decided(decision)) => decisions.push(decision),
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+    assert!(decisions.iter().all(|d| d == &decisions[0]));
+    assert!(decisions[0].proposals.contains_key(&7));
+}
+
+#[tokio::test]
+async fn test_runner_decides_mvba() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(2, &mut rng);
+    let parties = Vec::from_iter(0..4);
+    let mut transports = channel_network(parties.clone());
+
+    let mut handles = Vec::from_iter(parties.iter().map(|id| {
+        let proc = Consensus::init(
+            Domain::new("test-runtime", 0),
+            *id,
+            elders_sk.secret_key_share(id),
+            elders_sk.public_keys(),
+            parties.clone(),
+            |_, _: &u8| true,
+        );
+        Runner::new(proc, transports.remove(id).unwrap())
+            .tick_interval(TICK)
+            .spawn()
+    }));
+
+    for (id, handle) in parties.iter().zip(handles.iter()) {
+        let proposal = *id as u8;
+        handle
+            .call(move |proc| {
+                Ok(Vec::from_iter(
+                    proc.propose(proposal)?.into_iter().map(Action::from),
+                ))
+            })
+            .await
+            .unwrap();
+    }
+
+    let mut decisions = Vec::new();
+    for handle in handles.iter_mut() {
+        match timeout(DEADLINE, handle.next_event()).await.unwrap() {
+            Some(Event::Decided(decision)) => decisions.push(decision),
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+    assert!(decisions.iter().all(|d| d == &decisions[0]));
+    assert!(decisions[0]
+        .validate(&elders_sk.public_keys().public_key())
+        .unwrap());
+}
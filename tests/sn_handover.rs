@@ -1,9 +1,11 @@
 use blsttc::{SecretKeySet, SecretKeyShare};
 use log::info;
 use rand::{prelude::StdRng, Rng, SeedableRng};
+use std::collections::BTreeSet;
 
 mod handover_net;
 use handover_net::{Net, Packet};
+use sn_consensus::liveness::DEFAULT_STALL_TIMEOUT;
 use sn_consensus::{Ballot, Error, Handover, Result, SignedVote, Vote};
 
 static INIT: std::sync::Once = std::sync::Once::new();
@@ -78,6 +80,112 @@ fn test_handover_one_faulty_node_and_many_packet_drops() {
     assert_eq!(first_voters_value, Some(1));
 }
 
+#[test]
+fn test_handover_ticks_recover_from_packet_drops() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let p0 = net.procs[0].id();
+    let p3 = net.procs[3].id();
+
+    let vote = net.procs[0].propose(1)?;
+    net.broadcast(p0, vote);
+
+    // p3 receives the proposal, everything sent to it after that is lost
+    let mut p3_received = 0;
+    while let Some(source) = net.packets.keys().next().cloned() {
+        if net.packets[&source][0].dest == p3 {
+            p3_received += 1;
+            if p3_received > 1 {
+                net.drop_packet_from_source(source);
+                net.purge_empty_queues();
+                continue;
+            }
+        }
+        net.deliver_packet_from_source(source)?;
+    }
+
+    for i in 0..3 {
+        assert_eq!(net.consensus_value(i), Some(1));
+    }
+    assert_eq!(net.consensus_value(3), None);
+
+    // nobody steps in by hand, the ticks have to notice p3 is stuck
+    let mut ticks = 0;
+    while net.consensus_value(3).is_none() {
+        assert!(ticks < 3 * net.procs[3].liveness.stall_timeout);
+        net.tick()?;
+        net.drain_queued_packets()?;
+        ticks += 1;
+    }
+    assert_eq!(net.consensus_value(3), Some(1));
+
+    // once everyone has decided, ticking is quiet
+    for _ in 0..10 {
+        net.tick()?;
+    }
+    assert!(net.packets.is_empty());
+
+    net.generate_msc("handover_ticks_recover_from_packet_drops.msc")?;
+    Ok(())
+}
+
+#[test]
+fn test_handover_ticks_reach_elders_we_never_heard_from() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let elders = BTreeSet::from_iter(net.procs.iter().map(|p| p.id()));
+    for proc in net.procs.iter_mut() {
+        proc.liveness.set_elders(elders.clone());
+    }
+    let p0 = net.procs[0].id();
+    let p3 = net.procs[3].id();
+
+    let vote = net.procs[0].propose(1)?;
+    net.broadcast(p0, vote);
+
+    // p3 doesn't get a single vote, so it never votes either
+    while let Some(source) = net.packets.keys().next().cloned() {
+        if net.packets[&source][0].dest == p3 {
+            net.drop_packet_from_source(source);
+            net.purge_empty_queues();
+            continue;
+        }
+        net.deliver_packet_from_source(source)?;
+    }
+    assert_eq!(net.consensus_value(0), Some(1));
+    assert_eq!(net.consensus_value(3), None);
+
+    let mut ticks = 0;
+    while net.consensus_value(3).is_none() {
+        assert!(ticks < 3 * net.procs[3].liveness.stall_timeout);
+        net.tick()?;
+        net.drain_queued_packets()?;
+        ticks += 1;
+    }
+
+    for _ in 0..10 {
+        net.tick()?;
+    }
+    assert!(net.packets.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_handover_takes_the_stall_timeout() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(0, &mut rng);
+    let proc = Handover::<u8>::from(
+        (0, elders_sk.secret_key_share(0)),
+        elders_sk.public_keys(),
+        1,
+        0,
+        7,
+    );
+    assert_eq!(proc.liveness.stall_timeout, 7);
+}
+
 #[test]
 fn test_handover_reject_voter_changing_proposal_when_one_is_in_progress() -> Result<()> {
     init();
@@ -88,6 +196,7 @@ fn test_handover_reject_voter_changing_proposal_when_one_is_in_progress() -> Res
         elders_sk.public_keys(),
         1,
         0,
+        DEFAULT_STALL_TIMEOUT,
     );
 
     proc.propose(111)?;
@@ -109,6 +218,7 @@ fn test_handover_reject_vote_from_non_member() -> Result<()> {
         elders_sk.public_keys(),
         1,
         0,
+        DEFAULT_STALL_TIMEOUT,
     );
     let elders_sk = SecretKeySet::random(0, &mut rng);
     let mut p1 = Handover::<u8>::from(
@@ -116,6 +226,7 @@ fn test_handover_reject_vote_from_non_member() -> Result<()> {
         elders_sk.public_keys(),
         1,
         0,
+        DEFAULT_STALL_TIMEOUT,
     );
 
     let vote = p1.propose(111)?;
@@ -155,6 +266,7 @@ fn test_handover_reject_votes_with_invalid_signatures() -> Result<()> {
         elders_sk.public_keys(),
         1,
         0,
+        DEFAULT_STALL_TIMEOUT,
     );
     let ballot = Ballot::Propose(rng.gen());
     let gen = proc.gen;
@@ -178,11 +290,11 @@ fn test_handover_reject_votes_with_invalid_signatures() -> Result<()> {
 fn test_handover_split_vote() -> eyre::Result<()> {
     init();
     let mut rng = StdRng::from_seed([0u8; 32]);
-    for nprocs in 1..7 {
+    for nprocs in 1..7usize {
         println!("[TEST] testing with {nprocs} elders");
 
         // make network of nprocs elders
-        let mut net = Net::with_procs((nprocs * 2 + 2) / 3, nprocs, &mut rng);
+        let mut net = Net::with_procs((nprocs * 2).div_ceil(3), nprocs, &mut rng);
 
         // make each elder propose a different thing
         for i in 0..net.procs.len() {
@@ -208,7 +320,7 @@ fn test_handover_split_vote() -> eyre::Result<()> {
 fn test_handover_round_robin_split_vote() -> eyre::Result<()> {
     init();
     let mut rng = StdRng::from_seed([0u8; 32]);
-    for nprocs in 1..7 {
+    for nprocs in 1..7usize {
         println!("[TEST] testing with {nprocs} elder(s)");
 
         // make network of nprocs elders
@@ -247,9 +359,9 @@ fn test_handover_round_robin_split_vote() -> eyre::Result<()> {
 #[test]
 fn test_handover_simple_proposal() {
     // make network of n elders
-    let n = 4;
+    let n: usize = 4;
     let mut rng = StdRng::from_seed([0u8; 32]);
-    let mut net = Net::with_procs((n * 2 + 2) / 3, n, &mut rng);
+    let mut net = Net::with_procs((n * 2).div_ceil(3), n, &mut rng);
 
     // release a proposal
     let p0 = net.procs[0].id();
@@ -15,7 +15,11 @@ mod membership_net;
 
 use quickcheck::{Arbitrary, Gen, TestResult};
 use quickcheck_macros::quickcheck;
-use sn_consensus::{Ballot, Error, Generation, Membership, Reconfig, Result, SignedVote, Vote};
+use sn_consensus::{
+    Action, Ballot, Bls, CapacityPolicy, Decision, Error, FileStorage, Generation, Membership,
+    MembershipConfig, MembershipFollower, MembershipPolicy, NodeId, Reconfig, Record, Result,
+    SignatureScheme, SignedVote, Storage, Vote, VoteResponse,
+};
 
 static INIT: std::sync::Once = std::sync::Once::new();
 
@@ -34,6 +38,7 @@ fn test_membership_reject_changing_reconfig_when_one_is_in_progress() -> Result<
         (0, elders_sk.secret_key_share(0)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
     proc.propose(Reconfig::Join(rng.gen()))?;
     assert!(matches!(
@@ -52,12 +57,14 @@ fn test_membership_reject_vote_from_non_member() -> Result<()> {
         (0, elders_sk.secret_key_share(0)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
     let elders_sk = SecretKeySet::random(0, &mut rng);
     let mut p1 = Membership::<u8>::from(
         (1, elders_sk.secret_key_share(1)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
 
     let vote = p1.propose(Reconfig::Join(rng.gen()))?;
@@ -71,16 +78,17 @@ fn test_membership_reject_join_if_actor_is_already_a_member() {
     init();
     let mut rng = StdRng::from_seed([0u8; 32]);
     let elders_sk = SecretKeySet::random(0, &mut rng);
-    let mut proc = Membership::from(
+    let mut proc = Membership::<i32>::from(
         (0, elders_sk.secret_key_share(0)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
-    proc.force_join(111);
+    proc.force_join(111).unwrap();
 
     assert!(matches!(
         proc.propose(Reconfig::Join(111)),
-        Err(Error::JoinRequestForExistingMember { .. })
+        Err(Error::JoinRequestForExistingMember)
     ));
 }
 
@@ -89,16 +97,17 @@ fn test_membership_reject_leave_if_actor_is_not_a_member() {
     init();
     let mut rng = StdRng::from_seed([0u8; 32]);
     let elders_sk = SecretKeySet::random(0, &mut rng);
-    let mut proc = Membership::from(
+    let mut proc = Membership::<i32>::from(
         (0, elders_sk.secret_key_share(0)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
-    proc.force_join(111);
+    proc.force_join(111).unwrap();
 
     assert!(matches!(
         proc.propose(Reconfig::Leave(222)),
-        Err(Error::LeaveRequestForNonMember { .. })
+        Err(Error::LeaveRequestForNonMember)
     ));
 }
 
@@ -111,6 +120,7 @@ fn test_membership_reject_votes_with_invalid_signatures() -> Result<()> {
         (0, elders_sk.secret_key_share(0)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
     let ballot = Ballot::Propose(Reconfig::Join(rng.gen()));
     let gen = proc.gen + 1;
@@ -332,6 +342,102 @@ impl Arbitrary for Instruction {
     }
 }
 
+#[test]
+fn test_membership_ticks_recover_from_packet_drops() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let p0 = net.procs[0].id();
+    let p3 = net.procs[3].id();
+
+    let vote = net.procs[0].propose(Reconfig::Join(1))?;
+    net.broadcast(p0, vote);
+
+    // p3 receives the proposal, everything sent to it after that is lost
+    let mut p3_received = 0;
+    while let Some(source) = net.packets.keys().next().cloned() {
+        if net.packets[&source][0].dest == p3 {
+            p3_received += 1;
+            if p3_received > 1 {
+                net.packets.get_mut(&source).unwrap().pop_front();
+                net.purge_empty_queues();
+                continue;
+            }
+        }
+        net.deliver_packet_from_source(source)?;
+    }
+
+    assert!(net.procs[..3].iter().all(|p| p.gen == 1));
+    assert_eq!(net.proc(p3).gen, 0);
+    assert!(net.proc(p3).liveness.undecided_ticks().is_none());
+
+    // nobody steps in by hand, the ticks have to notice p3 is stuck
+    let mut ticks = 0;
+    while net.proc(p3).gen == 0 {
+        assert!(ticks < 3 * net.proc(p3).liveness.stall_timeout);
+        net.tick()?;
+        net.drain_queued_packets()?;
+        ticks += 1;
+    }
+    assert_eq!(net.proc(p3).members(1)?, BTreeSet::from_iter([1]));
+
+    // once everyone has decided, ticking is quiet
+    for _ in 0..10 {
+        net.tick()?;
+    }
+    assert!(net.packets.is_empty());
+
+    net.generate_msc("membership_ticks_recover_from_packet_drops.msc")?;
+    Ok(())
+}
+
+#[test]
+fn test_membership_ticks_reach_elders_we_never_heard_from() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let elders = BTreeSet::from_iter(net.procs.iter().map(|p| p.id()));
+    for proc in net.procs.iter_mut() {
+        proc.liveness.set_elders(elders.clone());
+    }
+    let p0 = net.procs[0].id();
+    let p3 = net.procs[3].id();
+
+    let vote = net.procs[0].propose(Reconfig::Join(1))?;
+    net.broadcast(p0, vote);
+
+    // p3 doesn't get a single vote, so it never votes either
+    while let Some(source) = net.packets.keys().next().cloned() {
+        if net.packets[&source][0].dest == p3 {
+            net.packets.get_mut(&source).unwrap().pop_front();
+            net.purge_empty_queues();
+            continue;
+        }
+        net.deliver_packet_from_source(source)?;
+    }
+    assert!(net.procs[..3].iter().all(|p| p.gen == 1));
+    assert!(net.procs[..3]
+        .iter()
+        .all(|p| !p.liveness.heard_from().contains(&p3)));
+    assert_eq!(net.proc(p3).gen, 0);
+
+    let mut ticks = 0;
+    while net.proc(p3).gen == 0 {
+        assert!(ticks < 3 * net.proc(p3).liveness.stall_timeout);
+        net.tick()?;
+        net.drain_queued_packets()?;
+        ticks += 1;
+    }
+    assert_eq!(net.proc(p3).members(1)?, BTreeSet::from_iter([1]));
+
+    // the elders are only caught up once
+    for _ in 0..10 {
+        net.tick()?;
+    }
+    assert!(net.packets.is_empty());
+    Ok(())
+}
+
 #[test]
 fn test_membership_interpreter_qc1() -> Result<()> {
     init();
@@ -508,7 +614,7 @@ fn test_membership_interpreter_qc4() -> Result<()> {
         }
     }
 
-    let proc_at_max_gen = procs_by_gen[max_gen].get(0).ok_or(Error::NoMembers)?;
+    let proc_at_max_gen = procs_by_gen[max_gen].first().ok_or(Error::NoMembers)?;
     assert!(super_majority(
         procs_by_gen[max_gen].len(),
         proc_at_max_gen.consensus.n_elders
@@ -526,6 +632,7 @@ fn test_membership_procs_refuse_to_propose_competing_votes() -> Result<()> {
         (0, elders_sk.secret_key_share(0)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
 
     proc.propose(Reconfig::Join(0_u8))?;
@@ -559,10 +666,11 @@ fn test_membership_validate_reconfig_rejects_when_members_at_capacity() -> Resul
         (0, elders_sk.secret_key_share(0usize)),
         elders_sk.public_keys(),
         1,
+        MembershipConfig::default(),
     );
 
     for m in 0..7 {
-        proc.force_join(m);
+        proc.force_join(m)?;
     }
 
     assert!(matches!(
@@ -573,6 +681,97 @@ fn test_membership_validate_reconfig_rejects_when_members_at_capacity() -> Resul
     Ok(())
 }
 
+#[test]
+fn test_membership_capacity_is_configurable() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(0, &mut rng);
+    let mut proc = Membership::<u8>::from(
+        (0, elders_sk.secret_key_share(0usize)),
+        elders_sk.public_keys(),
+        1,
+        MembershipConfig {
+            policy: Box::new(CapacityPolicy { max_members: 2 }),
+            ..Default::default()
+        },
+    );
+
+    proc.force_join(0)?;
+    assert!(proc
+        .validate_reconfig(Reconfig::Join(1), proc.gen + 1)
+        .is_ok());
+
+    proc.force_join(1)?;
+    assert!(matches!(
+        proc.validate_reconfig(Reconfig::Join(2), proc.gen + 1),
+        Err(Error::MembersAtCapacity)
+    ));
+    Ok(())
+}
+
+/// Refuses to let a member that has left join again.
+#[derive(Debug, Default)]
+struct NoRejoinPolicy {
+    left: BTreeSet<u8>,
+}
+
+impl MembershipPolicy<u8> for NoRejoinPolicy {
+    fn validate_reconfig(
+        &self,
+        reconfig: &Reconfig<u8>,
+        _members: &BTreeSet<u8>,
+        _gen: Generation,
+    ) -> Result<()> {
+        match reconfig {
+            Reconfig::Join(actor) if self.left.contains(actor) => {
+                Err(Error::PolicyViolation(format!("{actor} has left before")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn on_decision(&mut self, _gen: Generation, reconfigs: &BTreeSet<Reconfig<u8>>) {
+        for reconfig in reconfigs {
+            if let Reconfig::Leave(actor) = reconfig {
+                self.left.insert(*actor);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_membership_policy_is_consulted_on_proposals_and_votes() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    for proc in net.procs.iter_mut() {
+        proc.config.policy = Box::new(NoRejoinPolicy::default());
+    }
+
+    let p0 = net.procs[0].id();
+    for reconfig in [Reconfig::Join(1), Reconfig::Leave(1)] {
+        let vote = net.proc_mut(p0).propose(reconfig)?;
+        net.broadcast(p0, vote);
+        net.drain_queued_packets()?;
+    }
+    assert!(net.procs.iter().all(|p| p.gen == 2));
+
+    assert!(matches!(
+        net.proc_mut(p0).propose(Reconfig::Join(1)),
+        Err(Error::PolicyViolation(_))
+    ));
+
+    // A vote signed by an elder without the policy is rejected by elders with it
+    let p1 = net.procs[1].id();
+    net.proc_mut(p1).config.policy = Box::new(CapacityPolicy::default());
+    let vote = net.proc_mut(p1).propose(Reconfig::Join(1))?;
+    assert!(matches!(
+        net.proc_mut(p0).handle_signed_vote(vote),
+        Err(Error::PolicyViolation(_))
+    ));
+    Ok(())
+}
+
 #[test]
 fn test_membership_bft_consensus_qc1() -> Result<()> {
     init();
@@ -689,7 +888,7 @@ fn test_membership_bft_consensus_qc3() -> Result<()> {
     let mut net = Net::with_procs((2 * n) / 3, n, &mut rng);
     let faulty = 1;
     let proposer_a = 2;
-    let proposer_b = n;
+    let proposer_b = n as u64;
     {
         let vote = net
             .proc_mut(proposer_a)
@@ -1167,7 +1366,7 @@ fn test_membership_ensure_we_are_validating_super_majority_proposals() -> Result
         }
     }
 
-    let proc_at_max_gen = procs_by_gen[max_gen].get(0).ok_or(Error::NoMembers)?;
+    let proc_at_max_gen = procs_by_gen[max_gen].first().ok_or(Error::NoMembers)?;
     assert!(super_majority(
         procs_by_gen[max_gen].len(),
         proc_at_max_gen.consensus.n_elders
@@ -1209,7 +1408,7 @@ fn prop_interpreter(n: u8, instructions: Vec<Instruction>, seed: u128) -> eyre::
                             .insert(reconfig);
                         net.broadcast(q_id, vote);
                     }
-                    Err(Error::JoinRequestForExistingMember { .. }) => {
+                    Err(Error::JoinRequestForExistingMember) => {
                         assert!(q.members(q.gen)?.contains(&p));
                     }
                     Err(Error::AttemptedFaultyProposal) => {
@@ -1243,7 +1442,7 @@ fn prop_interpreter(n: u8, instructions: Vec<Instruction>, seed: u128) -> eyre::
                             .insert(reconfig);
                         net.broadcast(q_id, vote);
                     }
-                    Err(Error::LeaveRequestForNonMember { .. }) => {
+                    Err(Error::LeaveRequestForNonMember) => {
                         assert!(!q.members(q.gen)?.contains(&p));
                     }
                     Err(Error::AttemptedFaultyProposal) => {
@@ -1313,7 +1512,7 @@ fn prop_interpreter(n: u8, instructions: Vec<Instruction>, seed: u128) -> eyre::
         }
     }
 
-    let proc_at_max_gen = procs_by_gen[max_gen].get(0).ok_or(Error::NoMembers)?;
+    let proc_at_max_gen = procs_by_gen[max_gen].first().ok_or(Error::NoMembers)?;
     assert!(super_majority(
         procs_by_gen[max_gen].len(),
         proc_at_max_gen.consensus.n_elders
@@ -1344,10 +1543,11 @@ fn prop_validate_reconfig(
         (1, elders_sk.secret_key_share(1usize)),
         elders_sk.public_keys(),
         (3 * threshold / 2) as usize,
+        MembershipConfig::default(),
     );
 
     for m in initial_members.iter().copied() {
-        proc.force_join(m);
+        proc.force_join(m)?;
     }
 
     let reconfig = match join_or_leave {
@@ -1362,7 +1562,7 @@ fn prop_validate_reconfig(
             if proc_members.contains(&member) {
                 assert!(matches!(
                     valid_res,
-                    Err(Error::JoinRequestForExistingMember { .. })
+                    Err(Error::JoinRequestForExistingMember)
                 ));
             } else if initial_members.len() >= 7 {
                 assert!(matches!(valid_res, Err(Error::MembersAtCapacity)));
@@ -1374,10 +1574,7 @@ fn prop_validate_reconfig(
             if proc_members.contains(&member) {
                 assert!(valid_res.is_ok());
             } else {
-                assert!(matches!(
-                    valid_res,
-                    Err(Error::LeaveRequestForNonMember { .. })
-                ));
+                assert!(matches!(valid_res, Err(Error::LeaveRequestForNonMember)));
             }
         }
     };
@@ -1551,3 +1748,830 @@ fn prop_bft_consensus(
 
     Ok(())
 }
+
+// sign_checkpoints has the elders exchange their signature shares over their checkpoint.
+fn sign_checkpoints(procs: &mut [Membership<u8>]) -> Result<()> {
+    let mut shares = Vec::new();
+    for proc in procs.iter() {
+        shares.push((proc.id(), proc.sign_checkpoint()?.unwrap()));
+    }
+    for proc in procs.iter_mut() {
+        for (voter, (gen, share)) in shares.iter().cloned() {
+            proc.handle_checkpoint_share(voter, gen, share)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_membership_checkpoints_prune_history() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+
+    // procs[0] never checkpoints so we can compare against the full history
+    net.procs[0].config.checkpoint_interval = None;
+    for proc in net.procs.iter_mut().skip(1) {
+        proc.config.checkpoint_interval = Some(2);
+    }
+
+    for gen in 1..=9u8 {
+        let reconfig = if gen % 2 == 1 {
+            Reconfig::Join(gen)
+        } else {
+            Reconfig::Leave(gen - 1)
+        };
+        let proposer = net.procs[0].id();
+        let vote = net.proc_mut(proposer).propose(reconfig)?;
+        net.broadcast(proposer, vote);
+        net.drain_queued_packets()?;
+    }
+
+    let elders = net.procs[1].consensus.elders.public_key();
+    assert!(matches!(
+        net.procs[1].checkpoint.as_ref().unwrap().validate(&elders),
+        Err(Error::UnsignedCheckpoint)
+    ));
+    sign_checkpoints(&mut net.procs[1..])?;
+
+    let reference = &net.procs[0];
+    assert_eq!(reference.gen, 9);
+    assert!(reference.checkpoint.is_none());
+    assert_eq!(reference.history.len(), 9);
+
+    for proc in net.procs.iter().skip(1) {
+        assert_eq!(proc.gen, 9);
+        let checkpoint = proc.checkpoint.as_ref().unwrap();
+        assert_eq!(checkpoint.gen, 6);
+        assert!(checkpoint.validate(&elders).is_ok());
+
+        // the signature covers the members
+        let mut forged = checkpoint.clone();
+        forged.members.insert(42);
+        assert!(matches!(
+            forged.validate(&elders),
+            Err(Error::InvalidElderSignature)
+        ));
+        assert_eq!(Vec::from_iter(proc.history.keys().copied()), vec![7, 8, 9]);
+
+        for gen in 6..=9 {
+            assert_eq!(proc.members(gen)?, reference.members(gen)?);
+        }
+        assert!(matches!(
+            proc.members(5),
+            Err(Error::PrunedGeneration {
+                requested_gen: 5,
+                checkpoint_gen: 6
+            })
+        ));
+        assert_eq!(proc.anti_entropy(6)?.len(), 3);
+    }
+
+    // Procs that checkpoint still validate new proposals against the current members
+    let p1 = net.procs[1].id();
+    assert!(matches!(
+        net.proc_mut(p1).propose(Reconfig::Join(9)),
+        Err(Error::JoinRequestForExistingMember)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_membership_elders_with_wide_ids_reach_decision() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(1, &mut rng);
+    let ids = [300, 70_000, u32::MAX as NodeId + 1];
+    let mut procs = Vec::from_iter(ids.iter().map(|id| {
+        Membership::<u8>::from(
+            (*id, elders_sk.secret_key_share(*id)),
+            elders_sk.public_keys(),
+            ids.len(),
+            MembershipConfig::default(),
+        )
+    }));
+
+    let mut queue = vec![procs[0].propose(Reconfig::Join(1))?];
+    while let Some(vote) = queue.pop() {
+        for proc in procs.iter_mut() {
+            if let VoteResponse::Broadcast(vote) = proc.handle_signed_vote(vote.clone())? {
+                queue.push(vote);
+            }
+        }
+    }
+
+    for proc in procs {
+        assert_eq!(proc.gen, 1);
+        assert_eq!(proc.members(1)?, BTreeSet::from_iter([1]));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_membership_rotates_elders_at_generation_boundary() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let old_elders = net.procs[0].consensus.elders.clone();
+
+    let p0 = net.procs[0].id();
+    let vote = net.proc_mut(p0).propose(Reconfig::Join(1))?;
+    net.broadcast(p0, vote);
+    net.drain_queued_packets()?;
+    assert!(net.procs.iter().all(|p| p.gen == 1));
+
+    // A vote for the next generation signed with the old keys
+    let stale_vote = net.procs[1].sign_vote(Vote {
+        gen: 2,
+        ballot: Ballot::Propose(Reconfig::Join(9)),
+        faults: Default::default(),
+    })?;
+
+    let new_elders_sk = SecretKeySet::random(2, &mut rng);
+    for proc in net.procs.iter_mut() {
+        let id = proc.id();
+        proc.rotate_elders(
+            (id, new_elders_sk.secret_key_share(id)),
+            new_elders_sk.public_keys(),
+            4,
+        )?;
+    }
+
+    assert!(matches!(
+        net.procs[0].handle_signed_vote(stale_vote),
+        Err(Error::InvalidElderSignature)
+    ));
+
+    let vote = net.proc_mut(p0).propose(Reconfig::Join(2))?;
+    net.broadcast(p0, vote);
+    net.drain_queued_packets()?;
+
+    let old_pk = old_elders.public_key();
+    let new_pk = new_elders_sk.public_keys().public_key();
+    for proc in net.procs.iter() {
+        assert_eq!(proc.gen, 2);
+        assert_eq!(proc.members(2)?, BTreeSet::from_iter([1, 2]));
+
+        let gen_1 = proc.consensus_at_gen(1)?.decision.clone().unwrap();
+        assert!(gen_1.validate(&old_pk).is_ok());
+        assert!(gen_1.validate(&new_pk).is_err());
+
+        let gen_2 = proc.consensus_at_gen(2)?.decision.clone().unwrap();
+        assert!(gen_2.validate(&new_pk).is_ok());
+        assert!(gen_2.validate(&old_pk).is_err());
+    }
+
+    // Elders can not be rotated once we've started voting on a generation
+    net.proc_mut(p0).propose(Reconfig::Join(3))?;
+    assert!(matches!(
+        net.proc_mut(p0).rotate_elders(
+            (p0, new_elders_sk.secret_key_share(p0)),
+            new_elders_sk.public_keys(),
+            4
+        ),
+        Err(Error::ElderRotationAfterVoting(3))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_membership_keeps_votes_until_elders_are_rotated() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+
+    let p0 = net.procs[0].id();
+    let vote = net.proc_mut(p0).propose(Reconfig::Join(1))?;
+    net.broadcast(p0, vote);
+    net.drain_queued_packets()?;
+    assert!(net.procs.iter().all(|p| p.gen == 1));
+
+    for proc in net.procs.iter_mut() {
+        proc.expect_elder_rotation()?;
+    }
+
+    // An elder that votes before it rotates signs with the old keys
+    let stale_vote = net.procs[1].sign_vote(Vote {
+        gen: 2,
+        ballot: Ballot::Propose(Reconfig::Join(9)),
+        faults: Default::default(),
+    })?;
+    assert_eq!(
+        net.procs[0].handle_signed_vote(stale_vote)?,
+        VoteResponse::WaitingForMoreVotes
+    );
+    assert!(matches!(
+        net.procs[0].propose(Reconfig::Join(2)),
+        Err(Error::AwaitingElderRotation(2))
+    ));
+
+    // Every elder but p0 rotates and decides gen 2 with the new keys
+    let new_elders_sk = SecretKeySet::random(2, &mut rng);
+    for proc in net.procs.iter_mut().skip(1) {
+        let id = proc.id();
+        let actions = proc.rotate_elders(
+            (id, new_elders_sk.secret_key_share(id)),
+            new_elders_sk.public_keys(),
+            4,
+        )?;
+        assert!(actions.is_empty());
+    }
+    let p1 = net.procs[1].id();
+    let vote = net.proc_mut(p1).propose(Reconfig::Join(2))?;
+    net.broadcast(p1, vote);
+    net.drain_queued_packets()?;
+    assert_eq!(net.proc(p0).gen, 1);
+    assert!(net.proc(p0).consensus.votes.is_empty());
+
+    // p0 catches up from the votes it kept once it has the new keys
+    let actions = net.proc_mut(p0).rotate_elders(
+        (p0, new_elders_sk.secret_key_share(p0)),
+        new_elders_sk.public_keys(),
+        4,
+    )?;
+    assert!(actions.iter().any(|a| matches!(a, Action::Decided(_))));
+
+    let new_pk = new_elders_sk.public_keys().public_key();
+    for proc in net.procs.iter() {
+        assert_eq!(proc.gen, 2);
+        assert_eq!(proc.members(2)?, BTreeSet::from_iter([1, 2]));
+        let gen_2 = proc.consensus_at_gen(2)?.decision.clone().unwrap();
+        assert!(gen_2.validate(&new_pk).is_ok());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_file_storage_reads_log_written_before_elder_rotation() -> Result<()> {
+    // The records as they were laid out before the elder rotation was recorded
+    #[derive(serde::Serialize)]
+    enum OldRecord {
+        ForcedReconfig(Generation, Reconfig<u8>),
+        Vote(SignedVote<Reconfig<u8>>),
+        Cast(SignedVote<Reconfig<u8>>),
+        Decided(Decision<Reconfig<u8>>),
+    }
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(0, &mut rng);
+    let mut proc = Membership::<u8>::from(
+        (0, elders_sk.secret_key_share(0)),
+        elders_sk.public_keys(),
+        1,
+        MembershipConfig::default(),
+    );
+    let vote = proc.propose(Reconfig::Join(1))?;
+    let decision = proc.consensus_at_gen(1)?.decision.clone().unwrap();
+
+    let wal = wal_path("test_file_storage_reads_log_written_before_elder_rotation");
+    let mut bytes = Vec::new();
+    for record in [
+        OldRecord::ForcedReconfig(0, Reconfig::Join(7)),
+        OldRecord::Vote(vote.clone()),
+        OldRecord::Cast(vote.clone()),
+        OldRecord::Decided(decision.clone()),
+    ] {
+        let record_bytes = bincode::serialize(&record)?;
+        bytes.extend((record_bytes.len() as u64).to_le_bytes());
+        bytes.extend(record_bytes);
+    }
+    std::fs::write(&wal, bytes)?;
+
+    let storage = FileStorage::open(&wal)?;
+    assert_eq!(
+        Storage::<u8>::records(&storage)?,
+        vec![
+            Record::ForcedReconfig(0, Reconfig::Join(7)),
+            Record::Vote(vote.clone()),
+            Record::Cast(vote),
+            Record::Decided(decision),
+        ]
+    );
+
+    let _ = std::fs::remove_file(&wal);
+    Ok(())
+}
+
+fn wal_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("{name}_{}.wal", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_membership_recover_rebuilds_state_from_storage() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let wal = wal_path("test_membership_recover_rebuilds_state_from_storage");
+
+    let secret_key = net.procs[0].consensus.secret_key.clone();
+    let elders = net.procs[0].consensus.elders.clone();
+    net.procs[0] = Membership::recover(
+        secret_key.clone(),
+        elders.clone(),
+        4,
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+
+    for proc in net.procs.iter_mut() {
+        proc.force_join(100)?;
+    }
+
+    for gen in 1..=3u8 {
+        let proposer = net.procs[gen as usize].id();
+        let vote = net.proc_mut(proposer).propose(Reconfig::Join(gen))?;
+        net.broadcast(proposer, vote);
+        net.drain_queued_packets()?;
+    }
+
+    // We cast a vote for the next generation but crash before it leaves the process
+    net.procs[0].propose(Reconfig::Join(50))?;
+
+    let recovered = Membership::recover(
+        secret_key,
+        elders,
+        4,
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+    let proc = &net.procs[0];
+
+    assert_eq!(recovered.gen, 3);
+    assert_eq!(recovered.gen, proc.gen);
+    assert_eq!(recovered.consensus, proc.consensus);
+    assert_eq!(recovered.history, proc.history);
+    assert_eq!(recovered.forced_reconfigs, proc.forced_reconfigs);
+    assert_eq!(recovered.members(3)?, BTreeSet::from_iter([1, 2, 3, 100]));
+
+    let _ = std::fs::remove_file(&wal);
+    Ok(())
+}
+
+#[test]
+fn test_membership_recovered_proc_refuses_to_vote_twice() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(0, &mut rng);
+    let wal = wal_path("test_membership_recovered_proc_refuses_to_vote_twice");
+
+    let mut proc = Membership::<u8>::recover(
+        (1, elders_sk.secret_key_share(1u64)),
+        elders_sk.public_keys(),
+        2,
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+    proc.propose(Reconfig::Join(1))?;
+    drop(proc);
+
+    let mut proc = Membership::<u8>::recover(
+        (1, elders_sk.secret_key_share(1u64)),
+        elders_sk.public_keys(),
+        2,
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+    assert!(matches!(
+        proc.propose(Reconfig::Join(2)),
+        Err(Error::AttemptedFaultyProposal)
+    ));
+
+    let _ = std::fs::remove_file(&wal);
+    Ok(())
+}
+
+#[test]
+fn test_file_storage_discards_partially_written_record() -> Result<()> {
+    let wal = wal_path("test_file_storage_discards_partially_written_record");
+
+    let mut storage = FileStorage::open(&wal)?;
+    Storage::<u8>::append(&mut storage, &Record::ForcedReconfig(0, Reconfig::Join(1)))?;
+    Storage::<u8>::append(&mut storage, &Record::ForcedReconfig(0, Reconfig::Join(2)))?;
+    drop(storage);
+
+    // Simulate a crash half way through writing a third record
+    let mut bytes = std::fs::read(&wal)?;
+    let full_len = bytes.len();
+    bytes.extend(16u64.to_le_bytes());
+    bytes.extend([0u8; 5]);
+    std::fs::write(&wal, bytes)?;
+
+    let storage = FileStorage::open(&wal)?;
+    assert_eq!(std::fs::metadata(&wal)?.len(), full_len as u64);
+    assert_eq!(
+        Storage::<u8>::records(&storage)?,
+        vec![
+            Record::ForcedReconfig(0, Reconfig::Join(1)),
+            Record::ForcedReconfig(0, Reconfig::Join(2)),
+        ]
+    );
+
+    let _ = std::fs::remove_file(&wal);
+    Ok(())
+}
+
+#[test]
+fn test_membership_recover_across_elder_rotation() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let wal = wal_path("test_membership_recover_across_elder_rotation");
+
+    let secret_key = net.procs[0].consensus.secret_key.clone();
+    let elders = net.procs[0].consensus.elders.clone();
+    net.procs[0] = Membership::recover(
+        secret_key.clone(),
+        elders.clone(),
+        4,
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+
+    let p0 = net.procs[0].id();
+    let vote = net.proc_mut(p0).propose(Reconfig::Join(1))?;
+    net.broadcast(p0, vote);
+    net.drain_queued_packets()?;
+
+    let new_elders_sk = SecretKeySet::random(2, &mut rng);
+    for proc in net.procs.iter_mut() {
+        let id = proc.id();
+        proc.rotate_elders(
+            (id, new_elders_sk.secret_key_share(id)),
+            new_elders_sk.public_keys(),
+            4,
+        )?;
+    }
+
+    let vote = net.proc_mut(p0).propose(Reconfig::Join(2))?;
+    net.broadcast(p0, vote);
+    net.drain_queued_packets()?;
+
+    assert!(matches!(
+        Membership::<u8>::recover(
+            secret_key.clone(),
+            elders.clone(),
+            4,
+            MembershipConfig::default(),
+            FileStorage::open(&wal)?,
+        ),
+        Err(Error::MissingRotatedSecretKey(2))
+    ));
+
+    let recovered = Membership::recover_with_rotations(
+        secret_key,
+        elders,
+        4,
+        BTreeMap::from_iter([(2, (p0, new_elders_sk.secret_key_share(p0)))]),
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+    let proc = &net.procs[0];
+
+    assert_eq!(recovered.gen, 2);
+    assert_eq!(recovered.consensus, proc.consensus);
+    assert_eq!(recovered.history, proc.history);
+
+    let _ = std::fs::remove_file(&wal);
+    Ok(())
+}
+
+#[test]
+fn test_membership_recover_while_awaiting_elder_rotation() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let wal = wal_path("test_membership_recover_while_awaiting_elder_rotation");
+
+    let secret_key = net.procs[0].consensus.secret_key.clone();
+    let elders = net.procs[0].consensus.elders.clone();
+    net.procs[0] = Membership::recover(
+        secret_key.clone(),
+        elders.clone(),
+        4,
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+
+    let p0 = net.procs[0].id();
+    let vote = net.proc_mut(p0).propose(Reconfig::Join(1))?;
+    net.broadcast(p0, vote);
+    net.drain_queued_packets()?;
+    for proc in net.procs.iter_mut() {
+        proc.expect_elder_rotation()?;
+    }
+
+    // the others decide gen 2 with the new keys, p0 keeps their votes
+    let new_elders_sk = SecretKeySet::random(2, &mut rng);
+    for proc in net.procs.iter_mut().skip(1) {
+        let id = proc.id();
+        proc.rotate_elders(
+            (id, new_elders_sk.secret_key_share(id)),
+            new_elders_sk.public_keys(),
+            4,
+        )?;
+    }
+    let p1 = net.procs[1].id();
+    let vote = net.proc_mut(p1).propose(Reconfig::Join(2))?;
+    net.broadcast(p1, vote);
+    net.drain_queued_packets()?;
+    assert_eq!(net.proc(p0).gen, 1);
+
+    // p0 crashes before it gets the new keys
+    let mut recovered = Membership::<u8>::recover(
+        secret_key,
+        elders,
+        4,
+        MembershipConfig::default(),
+        FileStorage::open(&wal)?,
+    )?;
+    assert_eq!(recovered.gen, 1);
+    assert!(matches!(
+        recovered.propose(Reconfig::Join(3)),
+        Err(Error::AwaitingElderRotation(2))
+    ));
+
+    let actions = recovered.rotate_elders(
+        (p0, new_elders_sk.secret_key_share(p0)),
+        new_elders_sk.public_keys(),
+        4,
+    )?;
+    assert!(actions.iter().any(|a| matches!(a, Action::Decided(_))));
+    assert_eq!(recovered.gen, 2);
+    assert_eq!(recovered.members(2)?, net.procs[1].members(2)?);
+
+    let _ = std::fs::remove_file(&wal);
+    Ok(())
+}
+
+#[test]
+fn test_membership_checkpoint_compacts_storage() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    let wal = wal_path("test_membership_checkpoint_compacts_storage");
+    let config = || MembershipConfig {
+        checkpoint_interval: Some(2),
+        ..Default::default()
+    };
+
+    let secret_key = net.procs[0].consensus.secret_key.clone();
+    let elders = net.procs[0].consensus.elders.clone();
+    net.procs[0] = Membership::recover(
+        secret_key.clone(),
+        elders.clone(),
+        4,
+        config(),
+        FileStorage::open(&wal)?,
+    )?;
+    for proc in net.procs.iter_mut().skip(1) {
+        proc.config.checkpoint_interval = Some(2);
+    }
+
+    let p0 = net.procs[0].id();
+    let vote = net.proc_mut(p0).propose(Reconfig::Join(1))?;
+    net.broadcast(p0, vote);
+    net.drain_queued_packets()?;
+
+    // the keys installed before the checkpoint are still used after it
+    let new_elders_sk = SecretKeySet::random(2, &mut rng);
+    for proc in net.procs.iter_mut() {
+        let id = proc.id();
+        proc.rotate_elders(
+            (id, new_elders_sk.secret_key_share(id)),
+            new_elders_sk.public_keys(),
+            4,
+        )?;
+    }
+
+    for gen in 2..=7u8 {
+        let reconfig = if gen % 2 == 1 {
+            Reconfig::Join(gen)
+        } else {
+            Reconfig::Leave(gen - 1)
+        };
+        let vote = net.proc_mut(p0).propose(reconfig)?;
+        net.broadcast(p0, vote);
+        net.drain_queued_packets()?;
+    }
+    sign_checkpoints(&mut net.procs)?;
+
+    let proc = &net.procs[0];
+    assert_eq!(proc.gen, 7);
+    assert_eq!(proc.checkpoint.as_ref().map(|c| c.gen), Some(4));
+    assert!(proc.checkpoint.as_ref().unwrap().signature.is_some());
+
+    let records = Storage::<u8>::records(&FileStorage::open(&wal)?)?;
+    assert_eq!(
+        records[0],
+        Record::Checkpoint(proc.checkpoint.clone().unwrap())
+    );
+    assert_eq!(
+        records[1],
+        Record::RotateElders(2, new_elders_sk.public_keys(), 4)
+    );
+    for record in &records[2..] {
+        match record {
+            Record::Vote(vote) | Record::Cast(vote) => assert!(vote.vote.gen > 4),
+            Record::Decided(decision) => assert!(decision.generation > 4),
+            record => panic!("unexpected record {record:?}"),
+        }
+    }
+
+    let recovered = Membership::recover_with_rotations(
+        secret_key,
+        elders,
+        4,
+        BTreeMap::from_iter([(2, (p0, new_elders_sk.secret_key_share(p0)))]),
+        config(),
+        FileStorage::open(&wal)?,
+    )?;
+    assert_eq!(recovered.gen, 7);
+    assert_eq!(recovered.checkpoint, proc.checkpoint);
+    assert_eq!(recovered.consensus, proc.consensus);
+    assert_eq!(recovered.history, proc.history);
+    assert_eq!(recovered.members(7)?, proc.members(7)?);
+
+    let _ = std::fs::remove_file(&wal);
+    Ok(())
+}
+
+#[test]
+fn test_follower_tracks_members_from_anti_entropy() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, 4, &mut rng);
+    // the reference keeps the whole history, the other elders checkpoint it
+    for proc in net.procs.iter_mut().skip(1) {
+        proc.config.checkpoint_interval = Some(2);
+    }
+
+    for gen in 1..=5u8 {
+        let reconfig = if gen % 2 == 1 {
+            Reconfig::Join(gen)
+        } else {
+            Reconfig::Leave(gen - 1)
+        };
+        let proposer = net.procs[0].id();
+        let vote = net.proc_mut(proposer).propose(reconfig)?;
+        net.broadcast(proposer, vote);
+        net.drain_queued_packets()?;
+    }
+
+    let reference = &net.procs[0];
+    let elders = reference.consensus.elders.clone();
+    let mut follower = MembershipFollower::<u8>::from(elders.clone());
+    follower.checkpoint_interval = Some(2);
+
+    let votes = reference.anti_entropy(0)?;
+    for vote in votes.iter().cloned() {
+        follower.handle_signed_vote(vote)?;
+    }
+    assert_eq!(follower.gen, 5);
+    for gen in 2..=5 {
+        assert_eq!(follower.members(gen)?, reference.members(gen)?);
+    }
+
+    // votes for generations we already follow are ignored
+    assert_eq!(follower.handle_signed_vote(votes[0].clone())?, None);
+
+    // a follower can pick up from a checkpoint signed by the elders
+    let checkpoint = follower.checkpoint.clone().unwrap();
+    assert_eq!(checkpoint.gen, 2);
+    assert!(matches!(
+        MembershipFollower::<u8>::from_checkpoint(elders.clone(), checkpoint),
+        Err(Error::UnsignedCheckpoint)
+    ));
+    sign_checkpoints(&mut net.procs[1..])?;
+    let reference = &net.procs[0];
+    let checkpoint = net.procs[1].checkpoint.clone().unwrap();
+    assert_eq!(checkpoint.gen, 2);
+    let mut late_follower = MembershipFollower::<u8>::from_checkpoint(elders.clone(), checkpoint)?;
+    for gen in 3..=5 {
+        let decision = reference.consensus_at_gen(gen)?.decision.clone().unwrap();
+        assert!(late_follower.handle_decision(decision)?);
+    }
+    assert_eq!(late_follower.members(5)?, reference.members(5)?);
+    Ok(())
+}
+
+#[test]
+fn test_follower_rejects_votes_from_non_elders() -> Result<()> {
+    init();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let net = Net::with_procs(2, 4, &mut rng);
+    let mut follower = MembershipFollower::<u8>::from(net.procs[0].consensus.elders.clone());
+
+    let other_sk = SecretKeySet::random(2, &mut rng);
+    let mut other = Membership::<u8>::from(
+        (1, other_sk.secret_key_share(1)),
+        other_sk.public_keys(),
+        4,
+        MembershipConfig::default(),
+    );
+    let vote = other.propose(Reconfig::Join(1))?;
+    assert!(matches!(
+        follower.handle_signed_vote(vote),
+        Err(Error::InvalidElderSignature)
+    ));
+
+    let reconfig = Reconfig::Join(1);
+    let mut decision = Decision {
+        generation: 1,
+        proposals: BTreeMap::from_iter([(
+            reconfig,
+            other_sk.secret_key().sign(bincode::serialize(&reconfig)?),
+        )]),
+    };
+    assert!(matches!(
+        follower.handle_decision(decision.clone()),
+        Err(Error::InvalidElderSignature)
+    ));
+
+    decision.generation = 2;
+    assert!(matches!(
+        follower.handle_decision(decision.clone()),
+        Err(Error::BadGeneration {
+            requested_gen: 2,
+            gen: 0
+        })
+    ));
+
+    decision.generation = 1;
+    decision.proposals.clear();
+    assert!(matches!(
+        follower.handle_decision(decision),
+        Err(Error::DecisionHasNoVotes)
+    ));
+    assert_eq!(follower.gen, 0);
+    Ok(())
+}
+
+/// Runs a single join through elders signing with the given keys of `C`.
+fn membership_decides_with<C: SignatureScheme>(
+    secret_keys: Vec<C::SecretKeyShare>,
+    elders: C::PublicKeySet,
+) -> Result<()> {
+    let mut procs = Vec::from_iter(secret_keys.into_iter().enumerate().map(|(id, sk)| {
+        Membership::<u8, C>::from(
+            (id as NodeId, sk),
+            elders.clone(),
+            4,
+            MembershipConfig::default(),
+        )
+    }));
+
+    let mut queue = vec![procs[0].propose(Reconfig::Join(7))?];
+    while let Some(vote) = queue.pop() {
+        for proc in procs.iter_mut() {
+            if let VoteResponse::Broadcast(vote) = proc.handle_signed_vote(vote.clone())? {
+                queue.push(vote);
+            }
+        }
+    }
+
+    for proc in procs {
+        assert_eq!(proc.gen, 1);
+        let decision = proc.consensus_at_gen(1)?.decision.clone().unwrap();
+        assert!(decision.validate(&C::public_key(&elders)).is_ok());
+        assert_eq!(proc.members(1)?, BTreeSet::from_iter([7]));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_membership_decides_with_blsttc() -> Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let elders_sk = SecretKeySet::random(2, &mut rng);
+    let secret_keys = Vec::from_iter((0..4).map(|id| elders_sk.secret_key_share(id)));
+    membership_decides_with::<Bls>(secret_keys, elders_sk.public_keys())
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn test_membership_decides_with_ed25519() -> Result<()> {
+    use sn_consensus::{crypto::KeySet, ed25519::SecretKey, Ed25519};
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let secret_keys = Vec::from_iter((0..4).map(|_| SecretKey::random(&mut rng)));
+    let elders = KeySet::new(
+        2,
+        BTreeMap::from_iter((0..4).map(|id| (id, secret_keys[id as usize].public_key()))),
+    );
+    membership_decides_with::<Ed25519>(secret_keys, elders)
+}
+
+#[cfg(feature = "bad_crypto")]
+#[test]
+fn test_membership_decides_with_bad_crypto() -> Result<()> {
+    use sn_consensus::{bad_crypto::SecretKey, crypto::KeySet, BadCrypto};
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let secret_keys = Vec::from_iter((0..4).map(|_| SecretKey::random(&mut rng)));
+    let elders = KeySet::new(
+        2,
+        BTreeMap::from_iter((0..4).map(|id| (id, secret_keys[id as usize].public_key()))),
+    );
+    membership_decides_with::<BadCrypto>(secret_keys, elders)
+}